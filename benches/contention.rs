@@ -0,0 +1,78 @@
+//! Compares a [`ShardedPrefixWriter`] against a single
+//! `Arc<Mutex<PrefixWriter>>` shared by all threads.
+//!
+//! ```shell
+//! cargo bench --bench contention
+//! ```
+
+use prefix_writer::{
+    PrefixWriter,
+    ShardedPrefixWriter,
+};
+use std::{
+    io::Write,
+    sync::{
+        Arc,
+        Mutex,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+const THREADS: usize = 16;
+const LINES: usize = 20_000;
+const PREFIX: &str = "bench: ";
+
+fn naive() -> Duration {
+    let writer = Arc::new(Mutex::new(PrefixWriter::new(
+        PREFIX.to_owned(),
+        std::io::sink(),
+    )));
+
+    let start = Instant::now();
+
+    std::thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let writer = Arc::clone(&writer);
+
+            scope.spawn(move || {
+                for line in 0..LINES {
+                    let mut writer = writer.lock().expect("lock poisoned");
+                    write!(writer, "thread {thread} ").expect("write failed");
+                    writeln!(writer, "line {line}").expect("write failed");
+                }
+            });
+        }
+    });
+
+    start.elapsed()
+}
+
+fn sharded() -> Duration {
+    let writer = ShardedPrefixWriter::new(PREFIX.to_owned(), std::io::sink());
+
+    let start = Instant::now();
+
+    std::thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let mut handle = writer.handle();
+
+            scope.spawn(move || {
+                for line in 0..LINES {
+                    write!(handle, "thread {thread} ").expect("write failed");
+                    writeln!(handle, "line {line}").expect("write failed");
+                }
+            });
+        }
+    });
+
+    start.elapsed()
+}
+
+fn main() {
+    println!("threads: {THREADS}, lines per thread: {LINES}");
+    println!("naive:   {:?}", naive());
+    println!("sharded: {:?}", sharded());
+}
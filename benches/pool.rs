@@ -0,0 +1,92 @@
+//! Compares creating and dropping many writers with and without a
+//! [`BufferPool`], by time and by allocations.
+//!
+//! ```shell
+//! cargo bench --bench pool
+//! ```
+
+use prefix_writer::{
+    BufferPool,
+    PrefixWriter,
+};
+use std::{
+    alloc::{
+        GlobalAlloc,
+        Layout,
+        System,
+    },
+    io::Write,
+    sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const WRITERS: usize = 100_000;
+const PREFIX: &str = "test case: ";
+
+/// Writes of a short test case, with lines split between writes.
+fn write_case(writer: &mut impl Write) {
+    writer.write_all(b"running\nasser").expect("write failed");
+    writer.write_all(b"tion passed\nok").expect("write failed");
+    writer.flush().expect("flush failed");
+}
+
+/// Time taken and allocations made by running the test cases.
+fn measure(mut run: impl FnMut()) -> (Duration, usize) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    for _ in 0..WRITERS {
+        run();
+    }
+
+    (
+        start.elapsed(),
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+    )
+}
+
+fn main() {
+    let pool = BufferPool::new(64 * 1024);
+
+    let unpooled = measure(|| {
+        let mut writer = PrefixWriter::new(PREFIX.to_owned(), std::io::sink());
+        write_case(&mut writer);
+    });
+
+    let pooled = measure(|| {
+        let mut writer = pool.writer(PREFIX, std::io::sink());
+        write_case(&mut writer);
+    });
+
+    println!("writers: {WRITERS}");
+    for (name, (elapsed, allocations)) in [("unpooled", unpooled), ("pooled", pooled)] {
+        println!(
+            "{name:9} {elapsed:?}, {} allocations per writer",
+            allocations / WRITERS
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Drawing attention to the first line of interest.
+
+use std::io::Write;
+
+use crate::{
+    shared::Shared,
+    PrefixWriteError,
+    PrefixWriter,
+};
+
+/// Predicate deciding whether a line raises the alert.
+type AlertPredicate = Box<dyn Fn(&str) -> bool + Send>;
+
+/// Alert of [`PrefixWriter::with_alert_on`] and whether it fired.
+#[derive(Clone)]
+pub(crate) struct Alert {
+    predicate: Shared<AlertPredicate>,
+    bytes: Vec<u8>,
+    fired: bool,
+}
+
+impl std::fmt::Debug for Alert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Alert")
+            .field("bytes", &self.bytes)
+            .field("fired", &self.fired)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Alert {
+    pub(crate) fn fired(&self) -> bool {
+        self.fired
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Write the alert bytes, like a terminal bell `\x07`, right before
+    /// the first line the predicate matches, for example the first error,
+    /// to draw attention to it when monitoring. The alert fires only once
+    /// until it is rearmed with [`PrefixWriter::reset_alert`] or set
+    /// again, like after [`PrefixWriter::from_parts`]. Lines that are
+    /// dropped, like by muting or the sampling, do not fire it.
+    #[must_use]
+    pub fn with_alert_on(
+        self,
+        predicate: impl Fn(&str) -> bool + Send + 'static,
+        bytes: Vec<u8>,
+    ) -> Self {
+        Self {
+            alert: Some(Alert {
+                predicate: Shared::new(Box::new(predicate)),
+                bytes,
+                fired: false,
+            }),
+            ..self
+        }
+    }
+
+    /// Rearm the alert of [`PrefixWriter::with_alert_on`] so it fires
+    /// again on the next matching line.
+    pub fn reset_alert(&mut self) {
+        if let Some(ref mut alert) = self.alert {
+            alert.fired = false;
+        }
+    }
+
+    /// Write the alert if it did not fire yet and the line matches. The
+    /// alert stays armed when it can not be written.
+    pub(crate) fn alert(&mut self, content: &str) -> Result<(), PrefixWriteError> {
+        let Some(ref alert) = self.alert else {
+            return Ok(());
+        };
+
+        if alert.fired || !alert.predicate.lock()(content) {
+            return Ok(());
+        }
+
+        let bytes = alert.bytes.clone();
+        self.write_rendered_bytes(&bytes, 0, 0)?;
+
+        if let Some(ref mut alert) = self.alert {
+            alert.fired = true;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    fn is_error(line: &str) -> bool {
+        line.starts_with("error")
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            is_error,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn once() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_alert_on(is_error, b"\x07".to_vec());
+
+            writer.write_all(b"starting\nerror: first").unwrap();
+            writer.write_all(b"\nrunning\nerror: second\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "prefix: starting\n",
+                    "\x07prefix: error: first\n",
+                    "prefix: running\n",
+                    "prefix: error: second\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn reset() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_alert_on(is_error, b"[!] ".to_vec());
+
+            writer.write_all(b"error: first\nerror: second\n").unwrap();
+            writer.reset_alert();
+            writer.write_all(b"fine\nerror: third\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "[!] prefix: error: first\n",
+                    "prefix: error: second\n",
+                    "prefix: fine\n",
+                    "[!] prefix: error: third\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn muted_lines_do_not_fire() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_alert_on(is_error, b"\x07".to_vec());
+
+            writer.mute();
+            writer.write_all(b"error: muted\n").unwrap();
+            writer.unmute().unwrap();
+            writer.write_all(b"error: shown\n").unwrap();
+
+            assert_eq!(
+                "\x07prefix: error: shown\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Prefixing lines written to a [`tokio::io::AsyncWrite`].
+
+use std::{
+    io::Write,
+    pin::Pin,
+    task::{
+        ready,
+        Context,
+        Poll,
+    },
+};
+
+use tokio::io::{
+    AsyncRead,
+    AsyncReadExt,
+    AsyncWrite,
+    AsyncWriteExt,
+};
+
+use crate::{
+    incomplete_char_len,
+    PrefixWriter,
+    PrefixWriterBuilder,
+};
+
+/// Prefixes lines like a [`PrefixWriter`] but writes them to an async
+/// writer. Lines are prefixed with the options of a
+/// [`PrefixWriterBuilder`], see [`PrefixWriterBuilder::build_async`].
+///
+/// Dropping the writer can not wait for the async writer, so an
+/// incomplete line and prefixed lines not yet written are lost on drop.
+/// Call [`AsyncPrefixWriter::shutdown`] when done writing instead.
+#[derive(Debug)]
+pub struct AsyncPrefixWriter<W> {
+    staging: PrefixWriter<Vec<u8>>,
+    written: usize,
+    writer: W,
+}
+
+impl PrefixWriterBuilder {
+    /// Build an [`AsyncPrefixWriter`] with the configuration of the
+    /// builder writing the prefixed lines to the async writer.
+    pub fn build_async<W: AsyncWrite + Unpin>(self, writer: W) -> AsyncPrefixWriter<W> {
+        AsyncPrefixWriter {
+            staging: self.build(Vec::new()),
+            written: 0,
+            writer,
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncPrefixWriter<W> {
+    /// Create a new [`AsyncPrefixWriter`] using the prefix for prefixing
+    /// lines and the async writer for writing the output.
+    pub fn new(prefix: String, writer: W) -> Self {
+        PrefixWriter::builder(prefix).build_async(writer)
+    }
+
+    /// Write the incomplete line and all prefixed lines not yet written,
+    /// flush the async writer and return it. This is what dropping the
+    /// writer would do if dropping could wait, so call it before the
+    /// writer goes away.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the async writer.
+    pub async fn shutdown(mut self) -> std::io::Result<W> {
+        self.staging.flush()?;
+        std::future::poll_fn(|cx| self.poll_drain(cx)).await?;
+        self.writer.flush().await?;
+
+        Ok(self.writer)
+    }
+
+    /// Write the prefixed lines that are staged to the async writer.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        while self.written < self.staging.get_ref().len() {
+            let pending = &self.staging.get_ref()[self.written..];
+            let written = ready!(Pin::new(&mut self.writer).poll_write(cx, pending))?;
+
+            if written == 0 {
+                return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into()));
+            }
+
+            self.written += written;
+        }
+
+        self.staging.get_mut().clear();
+        self.written = 0;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncPrefixWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+
+        // Lines of earlier writes are written first, so the staged lines
+        // do not grow while the async writer does not keep up.
+        ready!(this.poll_drain(cx))?;
+
+        Poll::Ready(this.staging.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_drain(cx))?;
+        this.staging.flush()?;
+        ready!(this.poll_drain(cx))?;
+
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+
+        ready!(Pin::new(&mut *this).poll_flush(cx))?;
+
+        Pin::new(&mut this.writer).poll_shutdown(cx)
+    }
+}
+
+/// Copy everything from the reader to the writer, prefixing every line,
+/// and write the incomplete line at the end. Returns the number of bytes
+/// read. Characters split between reads are put back together before
+/// they are prefixed.
+///
+/// Dropping the future stops the copy. The bytes read up to then are
+/// either written as prefixed lines or not written at all, except for
+/// the unsent end of a line the writer only accepted partly.
+///
+/// # Errors
+///
+/// Returns the errors of the reader and the writer.
+pub async fn prefix_copy_async(
+    mut reader: impl AsyncRead + Unpin,
+    prefix: &str,
+    writer: impl AsyncWrite + Unpin,
+) -> std::io::Result<u64> {
+    let mut writer = AsyncPrefixWriter::new(prefix.to_owned(), writer);
+
+    let mut buffer = vec![0; 8 * 1024];
+    let (mut buffered, mut copied) = (0, 0);
+
+    loop {
+        let read = reader.read(&mut buffer[buffered..]).await?;
+        if read == 0 {
+            break;
+        }
+
+        buffered += read;
+        copied += read as u64;
+
+        // The start of a character that continues in the next read is
+        // kept back.
+        let complete = buffered - incomplete_char_len(&buffer[..buffered]);
+
+        writer.write_all(&buffer[..complete]).await?;
+
+        buffer.copy_within(complete..buffered, 0);
+        buffered -= complete;
+    }
+
+    writer.write_all(&buffer[..buffered]).await?;
+    writer.shutdown().await?;
+
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use tokio::io::{
+        AsyncReadExt,
+        AsyncWriteExt,
+    };
+
+    use std::{
+        pin::Pin,
+        task::{
+            Context,
+            Poll,
+        },
+    };
+    use tokio::io::AsyncWrite;
+
+    use super::{
+        prefix_copy_async,
+        AsyncPrefixWriter,
+    };
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Writer that is pending on every other write and takes at most two
+    /// bytes at a time.
+    #[derive(Debug, Default)]
+    struct PendingWriter {
+        output: Vec<u8>,
+        pending: bool,
+    }
+
+    impl AsyncWrite for PendingWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            let this = self.get_mut();
+
+            this.pending = !this.pending;
+            if this.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            let len = buf.len().min(2);
+            this.output.extend_from_slice(&buf[..len]);
+
+            Poll::Ready(Ok(len))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            prefix_copy_async,
+            AsyncPrefixWriter,
+            AsyncReadExt,
+            AsyncWriteExt,
+            PendingWriter,
+            PrefixWriter,
+            PREFIX,
+        };
+
+        const INPUT: &str = "grüße\r\n\n日本語\nend 👋";
+        const EXPECTED: &str = "prefix: grüße\n\nprefix: 日本語\nprefix: end 👋";
+
+        #[tokio::test]
+        async fn copy_tiny_reads() {
+            for size in [1, 2, 3, 5] {
+                let (mut client, server) = tokio::io::duplex(size);
+
+                let feeder = tokio::spawn(async move {
+                    client.write_all(INPUT.as_bytes()).await.unwrap();
+                });
+
+                let mut output = Vec::new();
+                let copied = prefix_copy_async(server, PREFIX, &mut output)
+                    .await
+                    .unwrap();
+                feeder.await.unwrap();
+
+                assert_eq!(INPUT.len() as u64, copied);
+                assert_eq!(EXPECTED, String::from_utf8(output).unwrap());
+            }
+        }
+
+        #[tokio::test]
+        async fn copy_pending_writer() {
+            let (mut client, server) = tokio::io::duplex(1);
+
+            let feeder = tokio::spawn(async move {
+                client.write_all(INPUT.as_bytes()).await.unwrap();
+            });
+
+            let mut writer = PendingWriter::default();
+            prefix_copy_async(server, PREFIX, &mut writer)
+                .await
+                .unwrap();
+            feeder.await.unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8(writer.output).unwrap());
+        }
+
+        #[tokio::test]
+        async fn shutdown_flushes_remainder() {
+            let (client, mut server) = tokio::io::duplex(8);
+
+            let reader = tokio::spawn(async move {
+                let mut got = String::new();
+                server.read_to_string(&mut got).await.unwrap();
+                got
+            });
+
+            let mut writer = AsyncPrefixWriter::new(PREFIX.to_owned(), client);
+            writer.write_all(b"first\nsec").await.unwrap();
+            writer.write_all(b"ond\nincomplete").await.unwrap();
+
+            let client = writer.shutdown().await.unwrap();
+            drop(client);
+
+            assert_eq!(
+                "prefix: first\nprefix: second\nprefix: incomplete",
+                reader.await.unwrap()
+            );
+        }
+
+        #[tokio::test]
+        async fn builder_options() {
+            let (client, mut server) = tokio::io::duplex(1024);
+
+            let mut writer = PrefixWriter::builder(PREFIX.to_owned())
+                .with_running_count(2)
+                .build_async(client);
+            writer.write_all(b"first\nsecond\n").await.unwrap();
+            writer.shutdown().await.unwrap().shutdown().await.unwrap();
+
+            let mut got = String::new();
+            server.read_to_string(&mut got).await.unwrap();
+
+            assert_eq!(" 1 prefix: first\n 2 prefix: second\n", got);
+        }
+    }
+}
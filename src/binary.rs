@@ -0,0 +1,316 @@
+//! Summarizing bursts of binary data instead of writing them as lines.
+
+use std::{
+    fmt::Write as _,
+    io::Write,
+};
+
+use crate::PrefixWriter;
+
+/// Number of bytes shown by [`BinaryDetection::with_hex_dump`].
+const HEX_DUMP_BYTES: usize = 64;
+
+/// When a line counts as binary data, see
+/// [`PrefixWriter::with_binary_detection`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BinaryDetection {
+    min_length: usize,
+    non_printable_fraction: f64,
+    hex_dump: bool,
+}
+
+impl Default for BinaryDetection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BinaryDetection {
+    /// Create a new [`BinaryDetection`] for lines of at least 1024 bytes
+    /// of which more than 30% are not printable.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            min_length: 1024,
+            non_printable_fraction: 0.3,
+            hex_dump: false,
+        }
+    }
+
+    /// Only check lines of at least `min_length` bytes, shorter lines are
+    /// always written.
+    #[must_use]
+    pub fn with_min_length(self, min_length: usize) -> Self {
+        Self { min_length, ..self }
+    }
+
+    /// Count a line as binary data when more than the fraction of its
+    /// bytes, between 0 and 1, are not printable.
+    #[must_use]
+    pub fn with_non_printable_fraction(self, non_printable_fraction: f64) -> Self {
+        Self {
+            non_printable_fraction,
+            ..self
+        }
+    }
+
+    /// Append the first 64 bytes of the line as hex to the summary.
+    #[must_use]
+    pub fn with_hex_dump(self, hex_dump: bool) -> Self {
+        Self { hex_dump, ..self }
+    }
+
+    /// The summary replacing the line, or `None` if it is not binary
+    /// data. The bytes of control characters besides tabs and of invalid
+    /// UTF-8 are not printable.
+    fn summarize(&self, line: &[u8]) -> Option<String> {
+        if line.len() < self.min_length || line.is_empty() {
+            return None;
+        }
+
+        let non_printable = line
+            .utf8_chunks()
+            .map(|chunk| {
+                let control = chunk
+                    .valid()
+                    .chars()
+                    .filter(|c| c.is_control() && *c != '\t')
+                    .map(char::len_utf8)
+                    .sum::<usize>();
+
+                control + chunk.invalid().len()
+            })
+            .sum::<usize>();
+
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = non_printable as f64 / line.len() as f64;
+
+        if fraction <= self.non_printable_fraction {
+            return None;
+        }
+
+        let mut summary = format!("[binary data: {} suppressed", format_size(line.len()));
+
+        if self.hex_dump {
+            summary.push_str(", starting with");
+            for byte in line.iter().take(HEX_DUMP_BYTES) {
+                let _ = write!(summary, " {byte:02x}");
+            }
+        }
+
+        summary.push(']');
+
+        Some(summary)
+    }
+}
+
+/// Format the size in bytes with a binary unit, like `1.4 MiB`.
+fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Replace lines that look like binary data, for example from an
+    /// accidental `cat` of a blob, with a prefixed summary like
+    /// `[binary data: 1.4 MiB suppressed]`. Lines are checked once they
+    /// are complete, so how the data was split into writes does not
+    /// matter. Lines are checked before invalid UTF-8 is replaced, so
+    /// sizes and the hex dump are of the original bytes.
+    #[must_use]
+    pub fn with_binary_detection(self, binary_detection: BinaryDetection) -> Self {
+        Self {
+            binary_detection: Some(binary_detection),
+            ..self
+        }
+    }
+
+    /// The summary of the complete line if it is binary data, checked on
+    /// the bytes of the line joined with the incomplete line of previous
+    /// writes, without the terminator.
+    pub(crate) fn summarize_binary(&self, line: &[u8]) -> Option<String> {
+        let binary_detection = self.binary_detection?;
+
+        let joined;
+        let line = match self.remainder {
+            Some(ref remainder) => {
+                // Without the bytes, like after `PrefixWriter::from_parts`,
+                // the decoded line is checked.
+                let raw = if self.raw_remainder.is_empty() {
+                    remainder.as_bytes()
+                } else {
+                    &self.raw_remainder
+                };
+
+                joined = [raw, line].concat();
+                &joined
+            }
+
+            None => line,
+        };
+
+        let delimiter = self.input_delimiter;
+        let mut line = line.strip_suffix(&[delimiter]).unwrap_or(line);
+        if delimiter == b'\n' {
+            line = line.strip_suffix(b"\r").unwrap_or(line);
+        }
+
+        binary_detection.summarize(line)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::{
+        format_size,
+        BinaryDetection,
+    };
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Bytes that are mostly not printable and contain no newline.
+    fn blob(length: usize) -> Vec<u8> {
+        (0..length)
+            .map(|index| match index % 4 {
+                0 => b'A',
+                1 => 0x00,
+                2 => 0x1b,
+                _ => 0xff,
+            })
+            .collect()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            blob,
+            format_size,
+            BinaryDetection,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn long_text_line() {
+            let line = "long line of text\twith tabs ".repeat(100);
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_binary_detection(BinaryDetection::new());
+            writeln!(writer, "{line}").unwrap();
+
+            assert_eq!(
+                format!("{PREFIX}{line}\n"),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn binary_burst() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_binary_detection(BinaryDetection::new());
+
+            writer.write_all(b"before\n").unwrap();
+            for chunk in blob(3 * 1024 * 1024 / 2).chunks(1000) {
+                writer.write_all(chunk).unwrap();
+            }
+            writer.write_all(b"\nafter\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "prefix: before\n",
+                    "prefix: [binary data: 1.5 MiB suppressed]\n",
+                    "prefix: after\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn short_binary_line() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_binary_detection(BinaryDetection::new());
+
+            writer.write_all(&blob(16)).unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                "prefix: A\0\u{1b}\u{fffd}A\0\u{1b}\u{fffd}A\0\u{1b}\u{fffd}A\0\u{1b}\u{fffd}",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn hex_dump() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_binary_detection(
+                    BinaryDetection::new()
+                        .with_min_length(8)
+                        .with_non_printable_fraction(0.5)
+                        .with_hex_dump(true),
+                );
+
+            writer
+                .write_all(b"\x7fELF\x02\x01\x01\0\0\0\0\0\n")
+                .unwrap();
+
+            assert_eq!(
+                "prefix: [binary data: 12 B suppressed, starting with 7f 45 4c 46 02 01 01 00 00 \
+                 00 00 00]\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn raw_bytes() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_binary_detection(
+                    BinaryDetection::new()
+                        .with_min_length(8)
+                        .with_hex_dump(true),
+                );
+
+            writer.write_all(b"\xff\xfehead").unwrap();
+            writer.write_all(b"\xc3").unwrap();
+            writer.write_all(b"\xa4\x80\x81\r\n").unwrap();
+            writer
+                .write_all(b"\xff\xfe\x00\x01\x02\x03\x04\x05")
+                .unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                concat!(
+                    "prefix: [binary data: 10 B suppressed, starting with ff fe 68 65 61 64 c3 a4 \
+                     80 81]\n",
+                    "prefix: [binary data: 8 B suppressed, starting with ff fe 00 01 02 03 04 05]",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn sizes() {
+            assert_eq!(
+                ["0 B", "1023 B", "1.0 KiB", "1.4 MiB", "2.0 GiB"],
+                [0, 1023, 1024, 1_468_006, 2 << 30].map(format_size)
+            );
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Prefixing lines with the source location that created the writer.
+
+use std::{
+    io::Write,
+    panic::Location,
+    path::Path,
+};
+
+use crate::PrefixWriter;
+
+/// Format of the prefix rendered by [`PrefixWriter::at_caller_with`].
+#[derive(Debug, Clone, Default)]
+pub struct CallerFormat {
+    file_name_only: bool,
+    column: bool,
+    tag: Option<String>,
+}
+
+impl CallerFormat {
+    /// Create a new [`CallerFormat`] rendering the full path and the
+    /// line, like `src/jobs/sync.rs:87: `.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only render the file name instead of the full path.
+    #[must_use]
+    pub fn with_file_name_only(self, file_name_only: bool) -> Self {
+        Self {
+            file_name_only,
+            ..self
+        }
+    }
+
+    /// Render the column after the line.
+    #[must_use]
+    pub fn with_column(self, column: bool) -> Self {
+        Self { column, ..self }
+    }
+
+    /// Render the tag in front of the location, like
+    /// `sync src/jobs/sync.rs:87: `.
+    #[must_use]
+    pub fn with_tag(self, tag: String) -> Self {
+        Self {
+            tag: Some(tag),
+            ..self
+        }
+    }
+
+    fn render(&self, location: &Location<'_>) -> String {
+        let file = if self.file_name_only {
+            Path::new(location.file())
+                .file_name()
+                .map_or(location.file().into(), |name| name.to_string_lossy())
+        } else {
+            location.file().into()
+        };
+
+        let location = if self.column {
+            format!("{file}:{}:{}", location.line(), location.column())
+        } else {
+            format!("{file}:{}", location.line())
+        };
+
+        match self.tag {
+            Some(ref tag) => format!("{tag} {location}: "),
+            None => format!("{location}: "),
+        }
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Create a new [`PrefixWriter`] prefixing lines with the source
+    /// location calling this, like `src/jobs/sync.rs:87: `.
+    #[track_caller]
+    pub fn at_caller(writer: W) -> Self {
+        Self::at_caller_with(&CallerFormat::new(), writer)
+    }
+
+    /// Like [`PrefixWriter::at_caller`] but renders the location in the
+    /// format.
+    #[track_caller]
+    pub fn at_caller_with(format: &CallerFormat, writer: W) -> Self {
+        Self::new(format.render(Location::caller()), writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::CallerFormat;
+    use crate::PrefixWriter;
+
+    fn output(mut writer: PrefixWriter<Vec<u8>>) -> String {
+        writer.write_all(b"line\n").unwrap();
+
+        String::from_utf8_lossy(writer.get_ref()).into_owned()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            output,
+            CallerFormat,
+            PrefixWriter,
+        };
+
+        #[test]
+        fn at_caller() {
+            let (writer, line) = (PrefixWriter::at_caller(Vec::new()), line!());
+
+            assert_eq!(format!("src/caller.rs:{line}: line\n"), output(writer));
+        }
+
+        #[test]
+        fn at_caller_with() {
+            let format = CallerFormat::new()
+                .with_file_name_only(true)
+                .with_column(true)
+                .with_tag("sync".to_owned());
+
+            let (writer, line) = (PrefixWriter::at_caller_with(&format, Vec::new()), line!());
+
+            assert_eq!(format!("sync caller.rs:{line}:35: line\n"), output(writer));
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Debugging helper that checks the output of a [`crate::PrefixWriter`]
+//! downstream.
+
+use std::io::Write;
+
+/// A line that did not hold up to the checks of a [`CheckedWriter`].
+/// Line numbers start at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// A non-empty line did not start with the expected prefix.
+    MissingPrefix {
+        /// Number of the offending line.
+        line: usize,
+        /// Content of the offending line.
+        content: String,
+    },
+
+    /// A line was longer than the configured maximum length.
+    TooLong {
+        /// Number of the offending line.
+        line: usize,
+        /// Length of the offending line in bytes.
+        length: usize,
+        /// Configured maximum length in bytes.
+        max: usize,
+    },
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingPrefix { line, content } => {
+                write!(f, "line {line} is missing the prefix: {content:?}")
+            }
+
+            Self::TooLong { line, length, max } => {
+                write!(f, "line {line} is {length} bytes long, maximum is {max}")
+            }
+        }
+    }
+}
+
+/// Passes everything through to the writer while reassembling the lines
+/// from whatever chunks it receives. Panics when a non-empty line does
+/// not start with the expected prefix or is longer than the maximum
+/// length, unless violations are recorded instead.
+#[derive(Debug)]
+pub struct CheckedWriter<W: Write> {
+    prefix: String,
+    writer: W,
+
+    max_line_length: Option<usize>,
+    record_violations: bool,
+
+    line: Vec<u8>,
+    line_number: usize,
+    violations: Vec<Violation>,
+}
+
+impl<W: Write> Write for CheckedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.writer.write(buf)?;
+
+        for byte in &buf[..written] {
+            if *byte == b'\n' {
+                self.check_line();
+            } else {
+                self.line.push(*byte);
+            }
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> CheckedWriter<W> {
+    /// Create a new [`CheckedWriter`] expecting every non-empty line to
+    /// start with the prefix and writing everything to the writer.
+    pub fn new(prefix: String, writer: W) -> Self {
+        Self {
+            prefix,
+            writer,
+
+            max_line_length: None,
+            record_violations: false,
+
+            line: Vec::new(),
+            line_number: 0,
+            violations: Vec::new(),
+        }
+    }
+
+    /// Set the maximum length of a line in bytes, without the newline.
+    #[must_use]
+    pub fn with_max_line_length(self, max_line_length: usize) -> Self {
+        Self {
+            max_line_length: Some(max_line_length),
+            ..self
+        }
+    }
+
+    /// Record violations so they can be retrieved with
+    /// [`CheckedWriter::violations`] instead of panicking.
+    #[must_use]
+    pub fn with_record_violations(self, record_violations: bool) -> Self {
+        Self {
+            record_violations,
+            ..self
+        }
+    }
+
+    /// Violations recorded so far.
+    #[must_use]
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Check the incomplete line that was written last as it will not
+    /// be completed anymore.
+    pub fn finish(&mut self) {
+        if !self.line.is_empty() {
+            self.check_line();
+        }
+    }
+
+    /// Get back the writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn check_line(&mut self) {
+        self.line_number += 1;
+
+        let line = String::from_utf8_lossy(&self.line).into_owned();
+        self.line.clear();
+
+        if !line.is_empty() && !line.starts_with(&self.prefix) {
+            self.violation(Violation::MissingPrefix {
+                line: self.line_number,
+                content: line.clone(),
+            });
+        }
+
+        if let Some(max) = self.max_line_length {
+            if line.len() > max {
+                self.violation(Violation::TooLong {
+                    line: self.line_number,
+                    length: line.len(),
+                    max,
+                });
+            }
+        }
+    }
+
+    fn violation(&mut self, violation: Violation) {
+        assert!(self.record_violations, "{violation}");
+
+        self.violations.push(violation);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::{
+        CheckedWriter,
+        Violation,
+    };
+
+    const PREFIX: &str = "prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            CheckedWriter,
+            Violation,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn passthrough() {
+            let mut writer = CheckedWriter::new(PREFIX.to_owned(), Vec::new());
+
+            writer.write_all(b"prefix: first\n\npre").unwrap();
+            writer.write_all(b"fix: second").unwrap();
+            writer.finish();
+
+            assert_eq!(
+                "prefix: first\n\nprefix: second",
+                String::from_utf8_lossy(&writer.into_inner())
+            );
+        }
+
+        #[test]
+        #[should_panic(expected = "line 2 is missing the prefix")]
+        fn missing_prefix_panics() {
+            let mut writer = CheckedWriter::new(PREFIX.to_owned(), Vec::new());
+
+            writer.write_all(b"prefix: first\nsecond\n").unwrap();
+        }
+
+        #[test]
+        fn record_violations() {
+            let mut writer = CheckedWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_max_line_length(11)
+                .with_record_violations(true);
+
+            writer.write_all(b"prefix: a\nb\nprefix: ").unwrap();
+            writer.write_all(b"long").unwrap();
+            writer.finish();
+
+            let expected = [
+                Violation::MissingPrefix {
+                    line: 2,
+                    content: "b".to_owned(),
+                },
+                Violation::TooLong {
+                    line: 3,
+                    length: 12,
+                    max: 11,
+                },
+            ];
+
+            assert_eq!(expected, writer.violations());
+        }
+    }
+}
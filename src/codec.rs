@@ -0,0 +1,181 @@
+//! [`tokio_util::codec::Encoder`] that prefixes items while framing
+//! them.
+
+use std::io::Write;
+
+use bytes::{
+    Bytes,
+    BytesMut,
+};
+use tokio_util::codec::Encoder;
+
+use crate::{
+    PrefixWriter,
+    PrefixWriterBuilder,
+};
+
+/// Encodes items as prefixed lines followed by a terminator. Items that
+/// contain newlines are split into multiple prefixed lines within the
+/// same frame. Every item is written with a [`PrefixWriter`], so the
+/// lines are rendered like by the writer and empty lines are not
+/// prefixed.
+#[derive(Debug)]
+pub struct PrefixEncoder {
+    writer: PrefixWriter<Vec<u8>>,
+}
+
+impl PrefixEncoder {
+    /// Create a new [`PrefixEncoder`] using the prefix for prefixing
+    /// lines. Lines are terminated with `\n`.
+    #[must_use]
+    pub fn new(prefix: String) -> Self {
+        Self::from_builder(PrefixWriterBuilder::builder(prefix))
+    }
+
+    /// Create a new [`PrefixEncoder`] rendering the lines with the options
+    /// of the builder, like the padding or the prefixes of
+    /// [`PrefixWriter::with_prefix_iter`], which continue from one item to
+    /// the next.
+    #[must_use]
+    pub fn from_builder(builder: PrefixWriterBuilder) -> Self {
+        Self {
+            writer: builder.build(Vec::new()),
+        }
+    }
+
+    /// Set a new prefix for [`PrefixEncoder`].
+    #[must_use]
+    pub fn with_prefix(self, prefix: String) -> Self {
+        Self {
+            writer: self.writer.with_prefix(prefix),
+        }
+    }
+
+    /// Set the terminator that is appended after every line.
+    #[must_use]
+    pub fn with_terminator(self, terminator: String) -> Self {
+        Self {
+            writer: self.writer.with_output_terminator(terminator),
+        }
+    }
+
+    /// Write the item as complete lines and move the rendered lines to
+    /// `dst`.
+    fn encode_bytes(&mut self, item: &[u8], dst: &mut BytesMut) -> std::io::Result<()> {
+        let delimiter = self.writer.input_delimiter;
+
+        self.writer.write_all(item)?;
+        if item.last() != Some(&delimiter) {
+            self.writer.write_all(&[delimiter])?;
+        }
+        self.writer.flush()?;
+
+        dst.extend_from_slice(self.writer.get_ref());
+        self.writer.get_mut().clear();
+
+        Ok(())
+    }
+}
+
+impl Encoder<String> for PrefixEncoder {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: String, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_bytes(item.as_bytes(), dst)
+    }
+}
+
+impl Encoder<Bytes> for PrefixEncoder {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode_bytes(&item, dst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use futures::SinkExt;
+    use pretty_assertions::assert_eq;
+    use tokio::io::AsyncReadExt;
+    use tokio_util::codec::FramedWrite;
+
+    use super::PrefixEncoder;
+    use crate::PrefixWriterBuilder;
+
+    const PREFIX: &str = "prefix: ";
+
+    async fn run<I>(encoder: PrefixEncoder, items: Vec<I>) -> String
+    where
+        PrefixEncoder: tokio_util::codec::Encoder<I, Error = std::io::Error>,
+    {
+        let (client, mut server) = tokio::io::duplex(1024);
+
+        let mut framed = FramedWrite::new(client, encoder);
+        for item in items {
+            framed.send(item).await.unwrap();
+        }
+        drop(framed);
+
+        let mut got = String::new();
+        server.read_to_string(&mut got).await.unwrap();
+
+        got
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            run,
+            Bytes,
+            PrefixEncoder,
+            PrefixWriterBuilder,
+            PREFIX,
+        };
+
+        #[tokio::test]
+        async fn strings() {
+            let items = vec![
+                "first".to_owned(),
+                "second\nthird".to_owned(),
+                String::new(),
+            ];
+
+            let got = run(PrefixEncoder::new(PREFIX.to_owned()), items).await;
+
+            assert_eq!("prefix: first\nprefix: second\nprefix: third\n\n", got);
+        }
+
+        #[tokio::test]
+        async fn bytes() {
+            let items = vec![Bytes::from_static(b"first\n\nsecond\n")];
+
+            let got = run(PrefixEncoder::new(PREFIX.to_owned()), items).await;
+
+            assert_eq!("prefix: first\n\nprefix: second\n", got);
+        }
+
+        #[tokio::test]
+        async fn terminator() {
+            let encoder = PrefixEncoder::new(PREFIX.to_owned()).with_terminator("\r\n".to_owned());
+            let items = vec!["first\nsecond".to_owned()];
+
+            let got = run(encoder, items).await;
+
+            assert_eq!("prefix: first\r\nprefix: second\r\n", got);
+        }
+
+        #[tokio::test]
+        async fn builder() {
+            let builder = PrefixWriterBuilder::builder(PREFIX.to_owned())
+                .with_prefix_iter(["1:".to_owned(), "2:".to_owned()].into_iter())
+                .with_prefix_column(4);
+            let items = vec!["first\nsecond".to_owned(), "third".to_owned()];
+
+            let got = run(PrefixEncoder::from_builder(builder), items).await;
+
+            assert_eq!("1:  first\n2:  second\nprefix: third\n", got);
+        }
+    }
+}
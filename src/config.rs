@@ -0,0 +1,629 @@
+//! Configuring a [`PrefixWriter`] from a compact option string, usually
+//! taken from an environment variable.
+
+use std::io::Write;
+
+use crate::{
+    PrefixWriter,
+    Sanitization,
+};
+
+/// Error while reading or parsing an option string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The environment variable is set but not valid unicode.
+    NotUnicode {
+        /// Name of the environment variable.
+        var: String,
+    },
+
+    /// An option has no `=` and therefore no value.
+    MissingValue {
+        /// Key of the option.
+        key: String,
+    },
+
+    /// A quoted value is not closed.
+    UnterminatedQuote {
+        /// Key of the option.
+        key: String,
+    },
+
+    /// A value ends with a backslash that does not escape anything.
+    TrailingEscape {
+        /// Key of the option.
+        key: String,
+    },
+
+    /// The key is not a known option.
+    UnknownKey {
+        /// Key of the option.
+        key: String,
+    },
+
+    /// The value is not valid for the option.
+    InvalidValue {
+        /// Key of the option.
+        key: String,
+        /// Value of the option.
+        value: String,
+        /// Description of the valid values.
+        expected: &'static str,
+    },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotUnicode { var } => {
+                write!(f, "environment variable `{var}` is not valid unicode")
+            }
+
+            Self::MissingValue { key } => write!(f, "missing `=` and value after key `{key}`"),
+
+            Self::UnterminatedQuote { key } => {
+                write!(f, "unterminated quote in the value of key `{key}`")
+            }
+
+            Self::TrailingEscape { key } => {
+                write!(f, "backslash at the end of the value of key `{key}`")
+            }
+
+            Self::UnknownKey { key } => write!(f, "unknown key `{key}`"),
+
+            Self::InvalidValue {
+                key,
+                value,
+                expected,
+            } => write!(
+                f,
+                "invalid value `{value}` for key `{key}`, expected {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Create a new [`PrefixWriter`] configured by the option string in
+    /// the environment variable `var`, using `prefix` unless the options
+    /// set one. A missing variable yields the default configuration.
+    ///
+    /// Options are separated by commas and have the form `key=value`,
+    /// like `prefix='web | ',message=true`. Values can be quoted with
+    /// single or double quotes to keep whitespace and commas, outside of
+    /// quotes surrounding whitespace is trimmed. A backslash escapes the
+    /// next character, inside and outside of quotes. When a key is given
+    /// more than once the last value wins.
+    ///
+    /// Supported keys:
+    ///
+    /// * `prefix`: the prefix.
+    /// * `marker`: the prefix for [`PrefixWriter::write_marker`].
+    /// * `message`: `true` or `false`, see [`PrefixWriter::with_message_mode`].
+    /// * `message_terminator`: `true` or `false`, see
+    ///   [`PrefixWriter::with_message_terminator`].
+    /// * `encoding`: `base64` or `hex`, see `with_line_encoder`. Needs the
+    ///   `encoding` feature.
+    /// * `timestamps`: `rfc3339` to start the prefix with the time, see
+    ///   [`PrefixWriter::with_timestamp_prefix`], `elapsed` to start it with
+    ///   the time since the previous line, see
+    ///   [`PrefixWriter::with_delta_time_prefix`], or `none`.
+    /// * `color`: `always`, `never` or `auto`, which is `never` when the
+    ///   `NO_COLOR` environment variable is set and `always` otherwise. `never`
+    ///   removes escape sequences from the lines, see
+    ///   [`PrefixWriter::with_sanitization`], and disables the colors of the
+    ///   highlighter.
+    /// * `empty`: what to do with empty lines, `keep` them, `skip` them,
+    ///   `squeeze` runs of them to one, see
+    ///   [`PrefixWriter::with_squeeze_empty_lines`], or `prefix` them, see
+    ///   [`PrefixWriter::with_prefix_empty_lines`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ConfigError`] when the variable is not unicode or the
+    /// options are invalid.
+    pub fn from_env(var: &str, prefix: String, writer: W) -> Result<Self, ConfigError> {
+        match std::env::var(var) {
+            Ok(options) => Self::from_options(&options, prefix, writer),
+            Err(std::env::VarError::NotPresent) => Ok(Self::new(prefix, writer)),
+            Err(std::env::VarError::NotUnicode(_)) => Err(ConfigError::NotUnicode {
+                var: var.to_owned(),
+            }),
+        }
+    }
+
+    /// Like [`PrefixWriter::from_env`] but with the option string given
+    /// directly.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ConfigError`] when the options are invalid.
+    pub fn from_options(options: &str, prefix: String, writer: W) -> Result<Self, ConfigError> {
+        parse_options(options)?
+            .into_iter()
+            .try_fold(Self::new(prefix, writer), |writer, (key, value)| {
+                apply_option(writer, key, value)
+            })
+    }
+}
+
+fn apply_option<W: Write>(
+    writer: PrefixWriter<W>,
+    key: String,
+    value: String,
+) -> Result<PrefixWriter<W>, ConfigError> {
+    let writer = match key.as_str() {
+        "prefix" => writer.with_prefix(value),
+        "marker" => writer.with_marker_prefix(value),
+        "message" => writer.with_message_mode(parse_bool(&key, value)?),
+        "message_terminator" => writer.with_message_terminator(parse_bool(&key, value)?),
+
+        #[cfg(feature = "encoding")]
+        "encoding" => match value.as_str() {
+            "base64" => writer.with_line_encoder(crate::Encoding::Base64),
+            "hex" => writer.with_line_encoder(crate::Encoding::Hex),
+            _ => {
+                return Err(ConfigError::InvalidValue {
+                    key,
+                    value,
+                    expected: "`base64` or `hex`",
+                })
+            }
+        },
+
+        #[cfg(not(feature = "encoding"))]
+        "encoding" => {
+            return Err(ConfigError::InvalidValue {
+                key,
+                value,
+                expected: "the `encoding` feature to be enabled",
+            })
+        }
+
+        "timestamps" => match value.as_str() {
+            "rfc3339" => writer
+                .with_timestamp_prefix(true)
+                .with_delta_time_prefix(false),
+            "elapsed" => writer
+                .with_timestamp_prefix(false)
+                .with_delta_time_prefix(true),
+            "none" => writer
+                .with_timestamp_prefix(false)
+                .with_delta_time_prefix(false),
+            _ => {
+                return Err(ConfigError::InvalidValue {
+                    key,
+                    value,
+                    expected: "`rfc3339`, `elapsed` or `none`",
+                })
+            }
+        },
+
+        "color" => {
+            let colors = match value.as_str() {
+                "always" => true,
+                "never" => false,
+                "auto" => std::env::var_os("NO_COLOR").is_none(),
+                _ => {
+                    return Err(ConfigError::InvalidValue {
+                        key,
+                        value,
+                        expected: "`always`, `never` or `auto`",
+                    })
+                }
+            };
+
+            #[cfg(feature = "highlight")]
+            let writer = writer.with_colors(colors);
+
+            if colors {
+                writer
+            } else {
+                writer.with_sanitization(Sanitization::new())
+            }
+        }
+
+        "empty" => match value.as_str() {
+            "keep" => writer,
+            "skip" => writer.with_blank_line_sink(std::io::sink()),
+            "squeeze" => writer.with_squeeze_empty_lines(true),
+            "prefix" => writer.with_prefix_empty_lines(true),
+            _ => {
+                return Err(ConfigError::InvalidValue {
+                    key,
+                    value,
+                    expected: "`keep`, `skip`, `squeeze` or `prefix`",
+                })
+            }
+        },
+
+        _ => return Err(ConfigError::UnknownKey { key }),
+    };
+
+    Ok(writer)
+}
+
+fn parse_bool(key: &str, value: String) -> Result<bool, ConfigError> {
+    match value.as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(ConfigError::InvalidValue {
+            key: key.to_owned(),
+            value,
+            expected: "`true` or `false`",
+        }),
+    }
+}
+
+/// Split an option string into its keys and unquoted, unescaped values.
+fn parse_options(options: &str) -> Result<Vec<(String, String)>, ConfigError> {
+    let mut parsed = Vec::new();
+    let mut chars = options.chars().peekable();
+
+    loop {
+        let mut key = String::new();
+        let mut has_value = false;
+
+        for c in chars.by_ref() {
+            match c {
+                '=' => {
+                    has_value = true;
+                    break;
+                }
+                ',' if key.trim().is_empty() => key.clear(),
+                ',' => break,
+                _ => key.push(c),
+            }
+        }
+
+        let key = key.trim().to_owned();
+
+        if !has_value {
+            if key.is_empty() {
+                return Ok(parsed);
+            }
+
+            return Err(ConfigError::MissingValue { key });
+        }
+
+        let value = parse_value(&mut chars, &key)?;
+        parsed.push((key, value));
+
+        if chars.peek().is_none() {
+            return Ok(parsed);
+        }
+    }
+}
+
+/// Parse a single value up to the next unquoted comma, which is consumed
+/// as well.
+fn parse_value(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    key: &str,
+) -> Result<String, ConfigError> {
+    let mut value = String::new();
+    let mut quote = None;
+    // Unquoted whitespace at the end of the value that will be trimmed.
+    let mut trailing_whitespace = 0;
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                let escaped = chars.next().ok_or_else(|| ConfigError::TrailingEscape {
+                    key: key.to_owned(),
+                })?;
+
+                value.push(escaped);
+                trailing_whitespace = 0;
+            }
+
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+
+            (',', None) => break,
+
+            (c, None) if c.is_whitespace() => {
+                if !value.is_empty() {
+                    value.push(c);
+                    trailing_whitespace += c.len_utf8();
+                }
+            }
+
+            (c, _) => {
+                value.push(c);
+                trailing_whitespace = 0;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(ConfigError::UnterminatedQuote {
+            key: key.to_owned(),
+        });
+    }
+
+    value.truncate(value.len() - trailing_whitespace);
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::Write,
+        time::Instant,
+    };
+
+    use super::{
+        parse_options,
+        ConfigError,
+    };
+    use crate::PrefixWriter;
+
+    fn parsed(options: &[(&str, &str)]) -> Vec<(String, String)> {
+        options
+            .iter()
+            .map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+            .collect()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            parse_options,
+            parsed,
+            ConfigError,
+            Instant,
+            PrefixWriter,
+            Write,
+        };
+
+        #[test]
+        fn parse_empty() {
+            assert_eq!(parsed(&[]), parse_options("").unwrap());
+            assert_eq!(parsed(&[]), parse_options(" , ,").unwrap());
+        }
+
+        #[test]
+        fn parse_unquoted() {
+            assert_eq!(
+                parsed(&[("prefix", "web |"), ("message", "true")]),
+                parse_options(" prefix = web | , message=true,").unwrap()
+            );
+        }
+
+        #[test]
+        fn parse_empty_value() {
+            assert_eq!(parsed(&[("prefix", "")]), parse_options("prefix=").unwrap());
+            assert_eq!(
+                parsed(&[("prefix", ""), ("marker", "")]),
+                parse_options("prefix=,marker=''").unwrap()
+            );
+        }
+
+        #[test]
+        fn parse_quoted() {
+            assert_eq!(
+                parsed(&[("prefix", " web, db | "), ("marker", "it's ")]),
+                parse_options(r#"prefix=' web, db | ',marker="it's ""#).unwrap()
+            );
+        }
+
+        #[test]
+        fn parse_mixed_quoting() {
+            assert_eq!(
+                parsed(&[("prefix", "web | db: ")]),
+                parse_options("prefix=web' | 'db': '").unwrap()
+            );
+        }
+
+        #[test]
+        fn parse_escapes() {
+            assert_eq!(
+                parsed(&[("prefix", "a,b\\c' "), ("marker", "'\" ")]),
+                parse_options(r#"prefix=a\,b\\c\'\ ,marker='\'\" '"#).unwrap()
+            );
+        }
+
+        #[test]
+        fn parse_errors() {
+            let key = || "prefix".to_owned();
+
+            assert_eq!(
+                ConfigError::MissingValue { key: key() },
+                parse_options("prefix").unwrap_err()
+            );
+            assert_eq!(
+                ConfigError::MissingValue { key: key() },
+                parse_options("prefix,message=true").unwrap_err()
+            );
+            assert_eq!(
+                ConfigError::UnterminatedQuote { key: key() },
+                parse_options("prefix='web").unwrap_err()
+            );
+            assert_eq!(
+                ConfigError::TrailingEscape { key: key() },
+                parse_options("prefix=web\\").unwrap_err()
+            );
+        }
+
+        #[test]
+        fn options() {
+            let mut writer = PrefixWriter::from_options(
+                "prefix='web | ',marker='web == ',message=true,message_terminator=false",
+                "default: ".to_owned(),
+                Vec::new(),
+            )
+            .unwrap();
+
+            writer.write_all(b"first\n").unwrap();
+            writer.write_marker("marker").unwrap();
+
+            assert_eq!(
+                "web | firstweb == marker",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        #[cfg(feature = "encoding")]
+        fn options_encoding() {
+            let mut writer =
+                PrefixWriter::from_options("encoding=hex", "p: ".to_owned(), Vec::new()).unwrap();
+
+            writer.write_all(b"a\n").unwrap();
+
+            assert_eq!("p: 61\n", String::from_utf8_lossy(writer.get_ref()));
+
+            let err = PrefixWriter::from_options("encoding=rot13", String::new(), Vec::new())
+                .unwrap_err();
+
+            assert_eq!(
+                "invalid value `rot13` for key `encoding`, expected `base64` or `hex`",
+                err.to_string()
+            );
+        }
+
+        #[test]
+        fn options_timestamps() {
+            let writer = |timestamps: &str| {
+                let mut writer = PrefixWriter::from_options(
+                    &format!("timestamps={timestamps}"),
+                    "web: ".to_owned(),
+                    Vec::new(),
+                )
+                .unwrap()
+                .with_clock(Instant::now);
+
+                writer.write_all(b"first\n").unwrap();
+                String::from_utf8_lossy(writer.get_ref()).into_owned()
+            };
+
+            let rfc3339 = writer("rfc3339");
+            let (timestamp, line) = rfc3339.split_once(' ').unwrap();
+            assert_eq!("web: first\n", line);
+            assert_eq!(24, timestamp.len());
+            assert!(timestamp.ends_with('Z'));
+
+            assert_eq!("+0.000s web: first\n", writer("elapsed"));
+            assert_eq!("web: first\n", writer("none"));
+        }
+
+        #[test]
+        fn options_color() {
+            let input = b"\x1b[31mred\x1b[0m\n";
+
+            let mut writer =
+                PrefixWriter::from_options("color=never", "web: ".to_owned(), Vec::new()).unwrap();
+            writer.write_all(input).unwrap();
+            assert_eq!("web: red\n", String::from_utf8_lossy(writer.get_ref()));
+
+            let mut writer =
+                PrefixWriter::from_options("color=always", "web: ".to_owned(), Vec::new()).unwrap();
+            writer.write_all(input).unwrap();
+            assert_eq!(
+                "web: \x1b[31mred\x1b[0m\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+
+            assert!(PrefixWriter::from_options("color=auto", String::new(), Vec::new()).is_ok());
+        }
+
+        #[test]
+        fn options_empty() {
+            let output = |empty: &str| {
+                let mut writer = PrefixWriter::from_options(
+                    &format!("prefix='> ',empty={empty}"),
+                    String::new(),
+                    Vec::new(),
+                )
+                .unwrap();
+
+                writer.write_all(b"first\n\n\nsecond\n").unwrap();
+                String::from_utf8_lossy(writer.get_ref()).into_owned()
+            };
+
+            assert_eq!("> first\n\n\n> second\n", output("keep"));
+            assert_eq!("> first\n> second\n", output("skip"));
+            assert_eq!("> first\n\n> second\n", output("squeeze"));
+            assert_eq!("> first\n> \n> \n> second\n", output("prefix"));
+        }
+
+        #[test]
+        fn options_example() {
+            let mut writer = PrefixWriter::from_options(
+                "prefix='web | ',timestamps=rfc3339,color=auto,empty=skip",
+                String::new(),
+                Vec::new(),
+            )
+            .unwrap();
+
+            writer.write_all(b"first\n\n").unwrap();
+
+            let output = String::from_utf8_lossy(writer.get_ref()).into_owned();
+            assert!(output.ends_with("Z web | first\n"), "{output}");
+        }
+
+        #[test]
+        fn options_errors() {
+            let err =
+                PrefixWriter::from_options("colour=auto", String::new(), Vec::new()).unwrap_err();
+            assert_eq!("unknown key `colour`", err.to_string());
+
+            let err =
+                PrefixWriter::from_options("color=yes", String::new(), Vec::new()).unwrap_err();
+            assert_eq!(
+                "invalid value `yes` for key `color`, expected `always`, `never` or `auto`",
+                err.to_string()
+            );
+
+            let err = PrefixWriter::from_options("timestamps=unix", String::new(), Vec::new())
+                .unwrap_err();
+            assert_eq!(
+                "invalid value `unix` for key `timestamps`, expected `rfc3339`, `elapsed` or \
+                 `none`",
+                err.to_string()
+            );
+
+            let err =
+                PrefixWriter::from_options("empty=drop", String::new(), Vec::new()).unwrap_err();
+            assert_eq!(
+                "invalid value `drop` for key `empty`, expected `keep`, `skip`, `squeeze` or \
+                 `prefix`",
+                err.to_string()
+            );
+
+            let err =
+                PrefixWriter::from_options("message=yes", String::new(), Vec::new()).unwrap_err();
+            assert_eq!(
+                "invalid value `yes` for key `message`, expected `true` or `false`",
+                err.to_string()
+            );
+        }
+
+        #[test]
+        fn from_env() {
+            const VAR: &str = "PREFIX_WRITER_TEST_FROM_ENV";
+
+            let mut writer =
+                PrefixWriter::from_env(VAR, "default: ".to_owned(), Vec::new()).unwrap();
+            writer.write_all(b"first\n").unwrap();
+            assert_eq!(
+                "default: first\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+
+            std::env::set_var(VAR, "prefix='env: '");
+
+            let mut writer =
+                PrefixWriter::from_env(VAR, "default: ".to_owned(), Vec::new()).unwrap();
+            writer.write_all(b"first\n").unwrap();
+            assert_eq!("env: first\n", String::from_utf8_lossy(writer.get_ref()));
+
+            std::env::remove_var(VAR);
+        }
+    }
+}
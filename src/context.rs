@@ -0,0 +1,140 @@
+//! Building the prefix of every line from the context of the line.
+
+use std::{
+    borrow::Cow,
+    io::Write,
+};
+
+use crate::{
+    prefix::ContextFn,
+    shared::Shared,
+    Prefix,
+    PrefixWriter,
+};
+
+/// Context of a line handed to the closure set by
+/// [`PrefixWriter::with_prefix_context`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineContext<'a> {
+    /// Number of the line in the output, starting at 1.
+    pub number: u64,
+
+    /// Index of the prefixed line within the current write, starting at
+    /// 1, see [`PrefixWriter::with_intra_write_index`].
+    pub index_in_write: u64,
+
+    /// Offset of the first byte of the line in the input, counting all
+    /// bytes ever written to the [`PrefixWriter`].
+    pub byte_offset: u64,
+
+    /// Content of the line without the terminator, before any
+    /// transformation.
+    pub content: &'a str,
+
+    /// Whether this is the last line of the current write or flush.
+    pub is_last: bool,
+}
+
+/// Source building the prefix of a line.
+pub(crate) type PrefixContext = Box<dyn Prefix + Send>;
+
+impl<W: Write> PrefixWriter<W> {
+    /// Build the prefix of every line with the closure from the context
+    /// of the line. This supersedes the simpler prefix options, the
+    /// prefix, [`PrefixWriter::with_prefix_iter`],
+    /// [`PrefixWriter::with_running_count`],
+    /// [`PrefixWriter::with_intra_write_index`] and the resource usage
+    /// are not used while it is set. Empty lines only call the closure
+    /// when they are prefixed, see
+    /// [`PrefixWriter::with_prefix_empty_lines`]. The prefix may borrow
+    /// from the context, like a part of the content of the line.
+    #[must_use]
+    pub fn with_prefix_context(
+        self,
+        prefix_context: impl for<'a> FnMut(&LineContext<'a>) -> Cow<'a, str> + Send + 'static,
+    ) -> Self {
+        Self {
+            prefix_context: Some(Shared::new(Box::new(ContextFn(prefix_context)))),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        borrow::Cow,
+        io::Write,
+    };
+
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            Cow,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn number_and_offset() {
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_prefix_context(|context| {
+                    let last = if context.is_last { " last" } else { "" };
+
+                    format!(
+                        "{}@{}{last} ({} bytes): ",
+                        context.number,
+                        context.byte_offset,
+                        context.content.len()
+                    )
+                    .into()
+                });
+
+            writer.write_all(b"first\nsec").unwrap();
+            writer.write_all(b"ond\r\n\nthird\n").unwrap();
+            writer.write_all(b"fourth").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                concat!(
+                    "1@0 (5 bytes): first\n",
+                    "2@6 (6 bytes): second\n",
+                    "\n",
+                    "4@15 last (5 bytes): third\n",
+                    "5@21 last (6 bytes): fourth",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn borrowed_from_content() {
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_prefix_context(|context| {
+                    match context.content.split_once(' ') {
+                        Some((level, _)) => Cow::Borrowed(level),
+                        None => Cow::Borrowed(PREFIX),
+                    }
+                });
+
+            writer
+                .write_all(
+                    b"info: first
+second
+",
+                )
+                .unwrap();
+
+            assert_eq!(
+                "info:info: first\nprefix: second\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Joining lines continued with a trailing backslash into one line.
+
+use std::io::Write;
+
+use crate::{
+    PrefixWriteError,
+    PrefixWriter,
+};
+
+/// Content of a line without a trailing single backslash, or `None` if
+/// the line is not continued. A line ending with two backslashes ends
+/// with an escaped backslash instead.
+fn continued(content: &str) -> Option<&str> {
+    content
+        .strip_suffix('\\')
+        .filter(|content| !content.ends_with('\\'))
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Join a line ending with a single backslash, like a continued shell
+    /// or make command, with the following lines into one line before
+    /// prefixing it, removing the backslash and the line break. A joined
+    /// line is held until its last line arrives, is flushed or would grow
+    /// beyond [`PrefixWriter::with_max_joined_length`], then it is
+    /// written with the backslash.
+    #[must_use]
+    pub fn with_join_continuations(self, join_continuations: bool) -> Self {
+        Self {
+            join_continuations,
+            ..self
+        }
+    }
+
+    /// Remove the leading whitespace of lines joined to the previous line
+    /// by [`PrefixWriter::with_join_continuations`], so indented
+    /// continuations do not leave gaps in the joined line.
+    #[must_use]
+    pub fn with_collapse_continuation_indent(self, collapse_continuation_indent: bool) -> Self {
+        Self {
+            collapse_continuation_indent,
+            ..self
+        }
+    }
+
+    /// Set the length in bytes a line joined by
+    /// [`PrefixWriter::with_join_continuations`] may reach. Defaults to
+    /// 64 KiB.
+    #[must_use]
+    pub fn with_max_joined_length(self, max_joined_length: usize) -> Self {
+        Self {
+            max_joined_length,
+            ..self
+        }
+    }
+
+    /// Join the line with the held continued line. Returns the line to
+    /// write, or `None` if the line continues and is held.
+    pub(crate) fn join_continuation(
+        &mut self,
+        content: &str,
+        original_terminator: &str,
+        terminator: &str,
+    ) -> Result<Option<String>, PrefixWriteError> {
+        let content = if self.held_continuation.is_some() && self.collapse_continuation_indent {
+            content.trim_start()
+        } else {
+            content
+        };
+        let (piece, continues) = continued(content).map_or((content, false), |piece| (piece, true));
+
+        let overflows = self
+            .held_continuation
+            .as_ref()
+            .is_some_and(|(held, _)| held.len() + piece.len() > self.max_joined_length);
+        if overflows {
+            self.release_continuation(terminator)?;
+        }
+
+        let joined = match self.held_continuation.take() {
+            Some((mut held, _)) => {
+                held.push_str(piece);
+                held
+            }
+            None => piece.to_owned(),
+        };
+
+        if continues {
+            self.held_continuation = Some((joined, original_terminator.to_owned()));
+            return Ok(None);
+        }
+
+        Ok(Some(joined))
+    }
+
+    /// Write the held continued line with its backslash. The line is kept
+    /// when writing fails.
+    pub(crate) fn release_continuation(
+        &mut self,
+        terminator: &str,
+    ) -> Result<(), PrefixWriteError> {
+        let Some((mut held, original_terminator)) = self.held_continuation.take() else {
+            return Ok(());
+        };
+
+        held.push('\\');
+
+        let own_terminator;
+        let terminator = if original_terminator.is_empty() {
+            terminator
+        } else {
+            own_terminator = self.terminator();
+            &own_terminator
+        };
+
+        if let Err(err) = self.emit_logical_line(&held, &original_terminator, terminator, true) {
+            held.pop();
+            self.held_continuation = Some((held, original_terminator));
+            return Err(err);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn two_lines() {
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_join_continuations(true);
+
+            writer.write_all(b"gcc -O2 \\\n-c main.c\nnext\n").unwrap();
+
+            assert_eq!(
+                "prefix: gcc -O2 -c main.c\nprefix: next\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn three_lines() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_join_continuations(true)
+                .with_collapse_continuation_indent(true);
+
+            writer
+                .write_all(b"cargo build \\\n    --release \\")
+                .unwrap();
+            assert!(writer.get_ref().is_empty());
+
+            writer
+                .write_all(b"\r\n    --locked\nescaped \\\\\n")
+                .unwrap();
+
+            assert_eq!(
+                "prefix: cargo build --release --locked\nprefix: escaped \\\\\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn trailing_backslash_at_end() {
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_join_continuations(true);
+
+            writer.write_all(b"first \\\nsecond \\\n").unwrap();
+            writer.flush().unwrap();
+            writer.write_all(b"incomplete \\").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                "prefix: first second \\\nprefix: incomplete \\",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn length_guard() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_join_continuations(true)
+                .with_max_joined_length(10);
+
+            writer.write_all(b"aaaa\\\nbbbb\\\ncccc\\\ndd\n").unwrap();
+
+            assert_eq!(
+                "prefix: aaaabbbb\\\nprefix: ccccdd\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
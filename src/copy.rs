@@ -0,0 +1,196 @@
+//! Copying buffered readers line by line.
+
+use std::io::{
+    BufRead,
+    Write,
+};
+
+use crate::{
+    is_empty_content,
+    render_line,
+};
+
+/// Copy everything from the buffered reader to the writer, prefixing
+/// every line the way a [`crate::PrefixWriter`] with the default options
+/// does, and write the last line even without a terminator. Returns the
+/// number of bytes read. Every line is read completely and written with
+/// a single write, so unlike with a [`crate::PrefixWriter`] nothing is
+/// buffered between writes. Invalid UTF-8 is replaced.
+///
+/// # Errors
+///
+/// Returns the errors of the reader and the writer.
+pub fn copy_bufread<R: BufRead, W: Write>(
+    prefix: &str,
+    mut reader: R,
+    mut writer: W,
+) -> std::io::Result<u64> {
+    let mut line = Vec::new();
+    let mut rendered = String::new();
+    let mut copied = 0;
+
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+
+        copied += read as u64;
+
+        let (content, terminator) = split_terminator(&line);
+
+        rendered.clear();
+        render_line(
+            prefix,
+            &String::from_utf8_lossy(content),
+            terminator,
+            &mut rendered,
+        );
+
+        writer.write_all(rendered.as_bytes())?;
+    }
+
+    writer.flush()?;
+
+    Ok(copied)
+}
+
+/// Like [`copy_bufread`] but the content of the lines is copied byte for
+/// byte instead of replacing invalid UTF-8, so bytes of OS strings and
+/// paths survive, like the surrogates that WTF-8 and
+/// [`std::ffi::OsStr::as_encoded_bytes`] encode on Windows or arbitrary
+/// bytes of file names on Unix. Only the prefix and `\n` terminators are
+/// added, `\r\n` terminators become `\n`.
+///
+/// # Errors
+///
+/// Returns the errors of the reader and the writer.
+pub fn copy_bufread_bytes<R: BufRead, W: Write>(
+    prefix: &str,
+    mut reader: R,
+    mut writer: W,
+) -> std::io::Result<u64> {
+    let mut line = Vec::new();
+    let mut rendered = Vec::new();
+    let mut copied = 0;
+
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+
+        copied += read as u64;
+
+        let (content, terminator) = split_terminator(&line);
+
+        rendered.clear();
+        if !is_empty_content(content) {
+            rendered.extend_from_slice(prefix.as_bytes());
+        }
+        rendered.extend_from_slice(content);
+        rendered.extend_from_slice(terminator.as_bytes());
+
+        writer.write_all(&rendered)?;
+    }
+
+    writer.flush()?;
+
+    Ok(copied)
+}
+
+/// Split a line read up to a line feed into its content and the
+/// terminator it is written with.
+fn split_terminator(line: &[u8]) -> (&[u8], &str) {
+    match line.strip_suffix(b"\n") {
+        Some(content) => (content.strip_suffix(b"\r").unwrap_or(content), "\n"),
+        None => (line, ""),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::{
+        BufReader,
+        Write,
+    };
+
+    use super::{
+        copy_bufread,
+        copy_bufread_bytes,
+    };
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+    const INPUT: &str = "first\r\n\nsecond\ngrüße\nend";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            copy_bufread,
+            copy_bufread_bytes,
+            BufReader,
+            PrefixWriter,
+            Write,
+            INPUT,
+            PREFIX,
+        };
+
+        #[test]
+        fn copy() {
+            let mut output = Vec::new();
+            let copied = copy_bufread(
+                PREFIX,
+                BufReader::with_capacity(3, INPUT.as_bytes()),
+                &mut output,
+            )
+            .unwrap();
+
+            assert_eq!(INPUT.len() as u64, copied);
+            assert_eq!(
+                "prefix: first\n\nprefix: second\nprefix: grüße\nprefix: end",
+                String::from_utf8(output).unwrap()
+            );
+        }
+
+        #[test]
+        fn same_as_writer() {
+            let mut output = Vec::new();
+            copy_bufread(PREFIX, BufReader::new(INPUT.as_bytes()), &mut output).unwrap();
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+            writer.write_all(INPUT.as_bytes()).unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(writer.get_ref(), &output);
+        }
+
+        #[test]
+        fn bytes_preserved() {
+            // A lone surrogate as encoded by WTF-8 and an invalid byte, both
+            // replaced by lossy decoding.
+            let input = b"surrogate \xed\xa0\x80 kept\r\n\ninvalid \xff\nend";
+
+            let mut output = Vec::new();
+            let copied =
+                copy_bufread_bytes(PREFIX, BufReader::with_capacity(3, &input[..]), &mut output)
+                    .unwrap();
+
+            assert_eq!(input.len() as u64, copied);
+            assert_eq!(
+                &b"prefix: surrogate \xed\xa0\x80 kept\n\nprefix: invalid \xff\nprefix: end"[..],
+                &output[..]
+            );
+
+            let mut valid = Vec::new();
+            copy_bufread_bytes(PREFIX, INPUT.as_bytes(), &mut valid).unwrap();
+            let mut lossy = Vec::new();
+            copy_bufread(PREFIX, INPUT.as_bytes(), &mut lossy).unwrap();
+
+            assert_eq!(lossy, valid);
+        }
+    }
+}
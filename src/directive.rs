@@ -0,0 +1,85 @@
+//! Taking the prefix from a directive at the start of the stream.
+
+use std::io::Write;
+
+use crate::PrefixWriter;
+
+impl<W: Write> PrefixWriter<W> {
+    /// Take the prefix from the first line of the stream when it starts
+    /// with the marker, for streams that declare their own prefix like
+    /// `#prefix: myapp | `. The rest of the line after the marker, as it
+    /// is, becomes the prefix of all following lines and the directive
+    /// line itself is not written. When the first line does not start
+    /// with the marker it is written as usual and the prefix is kept.
+    #[must_use]
+    pub fn with_prefix_directive(self, marker: &str) -> Self {
+        Self {
+            prefix_directive: Some(marker.to_owned()),
+            ..self
+        }
+    }
+
+    /// Adopt the prefix of the directive if this is the first line and
+    /// it is one, returning whether the line was consumed.
+    pub(crate) fn take_prefix_directive(&mut self, content: &str) -> bool {
+        let Some(marker) = self.prefix_directive.take() else {
+            return false;
+        };
+
+        match content.strip_prefix(marker.as_str()) {
+            Some(prefix) => {
+                prefix.clone_into(&mut self.prefix);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+    const MARKER: &str = "#prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            PrefixWriter,
+            Write,
+            MARKER,
+            PREFIX,
+        };
+
+        #[test]
+        fn directive() {
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_prefix_directive(MARKER);
+
+            writer.write_all(b"#prefix: myapp | \nfirst\n").unwrap();
+            writer.write_all(b"#prefix: other\nsecond\n").unwrap();
+
+            assert_eq!(
+                "myapp | first\nmyapp | #prefix: other\nmyapp | second\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn no_directive() {
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_prefix_directive(MARKER);
+
+            writer.write_all(b"first\n#prefix: myapp\n").unwrap();
+
+            assert_eq!(
+                "prefix: first\nprefix: #prefix: myapp\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! Syncing written lines to storage, for output that has to survive a
+//! crash once it was written.
+
+use std::{
+    fs::File,
+    io::Write,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use crate::{
+    PrefixWriteError,
+    PrefixWriter,
+    WriteStage,
+};
+
+/// When lines are synced to storage, see
+/// [`PrefixWriter::with_durability`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Never sync, leave it to the writer and the operating system.
+    #[default]
+    None,
+
+    /// Sync after every line.
+    EveryLine,
+
+    /// Sync after every `n` lines.
+    EveryN(usize),
+
+    /// Sync after a line once the duration passed since the last sync.
+    Interval(Duration),
+}
+
+/// Writer that can sync what was written to storage. Implemented for
+/// [`File`] with [`File::sync_data`] and as a no-op for writers without
+/// storage. Implementing it without a body makes syncing a no-op.
+pub trait SyncableWrite: Write {
+    /// Sync the written data to storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of syncing.
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SyncableWrite for File {
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        File::sync_data(self)
+    }
+}
+
+impl SyncableWrite for &File {
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        File::sync_data(self)
+    }
+}
+
+impl<W: SyncableWrite> SyncableWrite for std::io::BufWriter<W> {
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+        self.get_mut().sync_data()
+    }
+}
+
+impl<W: SyncableWrite + ?Sized> SyncableWrite for &mut W {
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        (**self).sync_data()
+    }
+}
+
+impl SyncableWrite for Vec<u8> {}
+impl SyncableWrite for std::io::Stdout {}
+impl SyncableWrite for std::io::Stderr {}
+
+/// Keeps track of the lines written since the last sync.
+pub(crate) struct Syncer<W> {
+    durability: Durability,
+    sync: fn(&mut W) -> std::io::Result<()>,
+
+    synced_lines: u64,
+    last_sync: Instant,
+}
+
+impl<W> std::fmt::Debug for Syncer<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Syncer")
+            .field("durability", &self.durability)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W> Syncer<W> {
+    pub(crate) fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// Whether the lines written so far have to be synced at `now`.
+    fn due(&self, lines_written: u64, now: Instant) -> bool {
+        let unsynced = lines_written - self.synced_lines;
+
+        unsynced > 0
+            && match self.durability {
+                Durability::None => false,
+                Durability::EveryLine => true,
+                Durability::EveryN(n) => unsynced >= n as u64,
+                Durability::Interval(interval) => {
+                    now.saturating_duration_since(self.last_sync) >= interval
+                }
+            }
+    }
+}
+
+impl<W: SyncableWrite> PrefixWriter<W> {
+    /// Sync lines to storage after they were written according to the
+    /// durability. A line is synced once it was written completely, the
+    /// writer is flushed before syncing. When syncing fails the lines
+    /// stay unsynced, the error is returned by the next write or flush
+    /// which tries to sync them again. Set it on the built writer, a
+    /// [`crate::PrefixWriterBuilder`] has no storage and
+    /// [`PrefixWriter::map_writer`] does not carry the durability over.
+    #[must_use]
+    pub fn with_durability(self, durability: Durability) -> Self {
+        let last_sync = self.clock.lock()();
+
+        Self {
+            syncer: Some(Syncer {
+                durability,
+                sync: |writer| {
+                    writer.flush()?;
+                    writer.sync_data()
+                },
+
+                synced_lines: self.lines_written,
+                last_sync,
+            }),
+            ..self
+        }
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Sync the written lines if the durability requires it.
+    pub(crate) fn sync_if_due(&mut self) -> Result<(), PrefixWriteError> {
+        let now = self.clock.lock()();
+
+        let Some(ref mut syncer) = self.syncer else {
+            return Ok(());
+        };
+
+        if !syncer.due(self.lines_written, now) {
+            return Ok(());
+        }
+
+        (syncer.sync)(&mut self.writer)
+            .map_err(|err| PrefixWriteError::new(err, self.lines_written, 0, WriteStage::Sync))?;
+
+        syncer.synced_lines = self.lines_written;
+        syncer.last_sync = now;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::Write,
+        sync::{
+            Arc,
+            Mutex,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    use super::{
+        Durability,
+        SyncableWrite,
+    };
+    use crate::{
+        PrefixWriter,
+        WriteStage,
+    };
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Counts how often it was synced and how many bytes were written at
+    /// every sync.
+    #[derive(Debug, Default)]
+    struct SyncCounter {
+        buffer: Vec<u8>,
+        syncs: Vec<usize>,
+        fail: bool,
+    }
+
+    impl Write for SyncCounter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.buffer.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SyncableWrite for SyncCounter {
+        fn sync_data(&mut self) -> std::io::Result<()> {
+            if self.fail {
+                return Err(std::io::Error::other("sync failed"));
+            }
+
+            self.syncs.push(self.buffer.len());
+            Ok(())
+        }
+    }
+
+    fn syncs(durability: Durability) -> Vec<usize> {
+        let mut writer = PrefixWriter::new(PREFIX.to_owned(), SyncCounter::default())
+            .with_durability(durability);
+
+        writer.write_all(b"a\nb\nc\nd\ne").unwrap();
+        writer.flush().unwrap();
+
+        writer.get_ref().syncs.clone()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            syncs,
+            Arc,
+            Durability,
+            Duration,
+            Instant,
+            Mutex,
+            PrefixWriter,
+            SyncCounter,
+            Write,
+            WriteStage,
+            PREFIX,
+        };
+
+        #[test]
+        fn policies() {
+            assert_eq!(Vec::<usize>::new(), syncs(Durability::None));
+            assert_eq!(vec![10, 20, 30, 40, 49], syncs(Durability::EveryLine));
+            assert_eq!(vec![20, 40], syncs(Durability::EveryN(2)));
+        }
+
+        #[test]
+        fn interval() {
+            let start = Instant::now();
+            let now = Arc::new(Mutex::new(start));
+
+            let clock = Arc::clone(&now);
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), SyncCounter::default())
+                .with_clock(move || *clock.lock().unwrap())
+                .with_durability(Durability::Interval(Duration::from_millis(100)));
+
+            writer.write_all(b"a\nb\n").unwrap();
+
+            *now.lock().unwrap() = start + Duration::from_millis(100);
+            writer.write_all(b"c\n").unwrap();
+            writer.write_all(b"d\n").unwrap();
+
+            *now.lock().unwrap() = start + Duration::from_millis(150);
+            writer.flush().unwrap();
+
+            *now.lock().unwrap() = start + Duration::from_millis(200);
+            writer.flush().unwrap();
+
+            assert_eq!(vec![20, 40], writer.get_ref().syncs);
+        }
+
+        #[test]
+        fn failed_sync() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), SyncCounter::default())
+                .with_durability(Durability::EveryLine);
+
+            writer.get_mut().fail = true;
+            assert_eq!(2, writer.write(b"a\nb\n").unwrap());
+
+            let err = writer.write_checked(b"c\n").unwrap_err();
+            assert_eq!(WriteStage::Sync, err.stage());
+            assert_eq!(1, err.line());
+
+            writer.get_mut().fail = false;
+            writer.write_all(b"b\nc\n").unwrap();
+
+            assert_eq!(
+                "prefix: a\nprefix: b\nprefix: c\n",
+                String::from_utf8_lossy(&writer.get_ref().buffer)
+            );
+            assert_eq!(vec![10, 20, 30], writer.get_ref().syncs);
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! Encodings for the content of lines.
+
+use base64::Engine;
+
+/// Encoding applied to the content of every line before it is prefixed.
+/// The prefix itself stays plain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Encoding {
+    /// Standard base64 with padding.
+    Base64,
+
+    /// Lowercase hexadecimal.
+    Hex,
+}
+
+impl Encoding {
+    /// Encode the content of a line.
+    pub(crate) fn encode(self, line: &str) -> String {
+        match self {
+            Self::Base64 => base64::engine::general_purpose::STANDARD.encode(line),
+            Self::Hex => hex::encode(line),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Errors with the context of the line that failed to be written.
+
+/// Part of a line that was being written when the writer failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteStage {
+    /// The prefix of the line.
+    Prefix,
+
+    /// The content of the line.
+    Payload,
+
+    /// The terminator of the line.
+    Terminator,
+
+    /// Syncing the line to storage after it was written completely, see
+    /// [`crate::PrefixWriter::with_durability`].
+    Sync,
+}
+
+impl std::fmt::Display for WriteStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Prefix => write!(f, "prefix"),
+            Self::Payload => write!(f, "payload"),
+            Self::Terminator => write!(f, "terminator"),
+            Self::Sync => write!(f, "sync"),
+        }
+    }
+}
+
+/// Error of the underlying writer together with where in the output it
+/// happened. Returned by [`crate::PrefixWriter::write_checked`] and
+/// wrapped in the [`std::io::Error`] returned by the
+/// [`std::io::Write`] implementation, from which it can be retrieved by
+/// downcasting.
+#[derive(Debug)]
+pub struct PrefixWriteError {
+    source: std::io::Error,
+
+    line: u64,
+    emitted: usize,
+    stage: WriteStage,
+}
+
+impl PrefixWriteError {
+    pub(crate) fn new(
+        source: std::io::Error,
+        line: u64,
+        emitted: usize,
+        stage: WriteStage,
+    ) -> Self {
+        Self {
+            source,
+            line,
+            emitted,
+            stage,
+        }
+    }
+
+    /// Number of the output line that failed to be written, starting at
+    /// 1.
+    #[must_use]
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// How many bytes of the failed line were already written.
+    #[must_use]
+    pub fn emitted(&self) -> usize {
+        self.emitted
+    }
+
+    /// Which part of the line failed to be written.
+    #[must_use]
+    pub fn stage(&self) -> WriteStage {
+        self.stage
+    }
+
+    /// Kind of the error of the underlying writer.
+    #[must_use]
+    pub fn kind(&self) -> std::io::ErrorKind {
+        self.source.kind()
+    }
+
+    /// Get back the error of the underlying writer.
+    #[must_use]
+    pub fn into_source(self) -> std::io::Error {
+        self.source
+    }
+}
+
+impl std::fmt::Display for PrefixWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "failed to write {} of line {} after {} bytes: {}",
+            self.stage, self.line, self.emitted, self.source
+        )
+    }
+}
+
+impl std::error::Error for PrefixWriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<PrefixWriteError> for std::io::Error {
+    fn from(err: PrefixWriteError) -> Self {
+        Self::new(err.kind(), err)
+    }
+}
+
+/// Error of [`crate::PrefixWriter::try_new`] for a prefix containing a
+/// character that would corrupt the output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPrefix {
+    character: char,
+    index: usize,
+}
+
+impl InvalidPrefix {
+    pub(crate) fn new(character: char, index: usize) -> Self {
+        Self { character, index }
+    }
+
+    /// The offending character.
+    #[must_use]
+    pub fn character(&self) -> char {
+        self.character
+    }
+
+    /// Byte index of the offending character in the prefix.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl std::fmt::Display for InvalidPrefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid character {:?} at index {} of the prefix",
+            self.character, self.index
+        )
+    }
+}
+
+impl std::error::Error for InvalidPrefix {}
@@ -0,0 +1,126 @@
+//! Extension trait to prefix any writer like an iterator adapter.
+
+use std::io::Write;
+
+use crate::{
+    PrefixWriter,
+    PrefixWriterBuilder,
+};
+
+/// Adds [`WriteExt::prefixed`] and [`WriteExt::prefixed_with`] to every
+/// writer so prefixing composes with other writer adapters.
+///
+/// Buffering the prefixed output, the prefix writer writes into the
+/// buffer:
+///
+/// ```
+/// use prefix_writer::WriteExt;
+/// use std::io::{
+///     BufWriter,
+///     Write,
+/// };
+///
+/// let mut writer = BufWriter::new(Vec::new()).prefixed("job: ");
+/// writeln!(writer, "started")?;
+/// writer.flush()?;
+///
+/// let buffered = writer.get_ref().get_ref();
+/// assert_eq!(b"job: started\n".as_slice(), buffered.as_slice());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// Buffering the input, the prefix writer gets bigger chunks:
+///
+/// ```
+/// use prefix_writer::WriteExt;
+/// use std::io::{
+///     BufWriter,
+///     Write,
+/// };
+///
+/// let mut writer = BufWriter::new(Vec::new().prefixed("job: "));
+/// writeln!(writer, "started")?;
+/// writer.flush()?;
+///
+/// let prefixed = writer.get_ref().get_ref();
+/// assert_eq!(b"job: started\n".as_slice(), prefixed.as_slice());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub trait WriteExt: Write + Sized {
+    /// Prefix everything written to this writer with the prefix.
+    fn prefixed(self, prefix: impl Into<String>) -> PrefixWriter<Self> {
+        PrefixWriter::new(prefix.into(), self)
+    }
+
+    /// Prefix everything written to this writer with the configuration
+    /// of the builder.
+    fn prefixed_with(self, builder: PrefixWriterBuilder) -> PrefixWriter<Self> {
+        builder.build(self)
+    }
+}
+
+impl<W: Write> WriteExt for W {}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::{
+        BufWriter,
+        Write,
+    };
+
+    use super::WriteExt;
+    use crate::PrefixWriter;
+
+    mod tests {
+        use super::{
+            assert_eq,
+            BufWriter,
+            PrefixWriter,
+            Write,
+            WriteExt,
+        };
+
+        #[test]
+        fn nested() {
+            let mut writer = Vec::new().prefixed("outer: ").prefixed("inner: ");
+
+            writer.write_all(b"first\nsec").unwrap();
+            writer.write_all(b"ond\n").unwrap();
+            writer.flush().unwrap();
+
+            let got = writer.get_ref().get_ref();
+
+            assert_eq!(
+                "outer: inner: first\nouter: inner: second\n",
+                String::from_utf8_lossy(got)
+            );
+        }
+
+        #[test]
+        fn buffered_between() {
+            let mut writer = BufWriter::new(Vec::new().prefixed("outer: ")).prefixed("inner: ");
+
+            writer.write_all(b"first\n").unwrap();
+            writer.flush().unwrap();
+
+            let got = writer.get_ref().get_ref().get_ref();
+
+            assert_eq!("outer: inner: first\n", String::from_utf8_lossy(got));
+        }
+
+        #[test]
+        fn prefixed_with() {
+            let builder = PrefixWriter::builder("job: ".to_owned()).with_message_mode(true);
+            let mut writer = Vec::new().prefixed_with(builder);
+
+            writer.write_all(b"first\nsecond").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                "job: first\njob: second",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
@@ -0,0 +1,377 @@
+//! Falling back to a secondary writer once the primary writer keeps
+//! failing.
+
+use std::{
+    io::{
+        ErrorKind,
+        Write,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use crate::Clock;
+
+/// Writes to the primary writer until it failed a number of times in a
+/// row, then writes everything to the secondary writer. A line noting the
+/// switch and the error is written to the secondary writer before.
+/// Optionally the primary writer is tried again periodically and used
+/// again once it accepts writes.
+///
+/// Meant to be wrapped in a [`crate::PrefixWriter`], which writes a line
+/// again after a failed write and continues a line that was partly
+/// written, so the line that failed on the primary writer is written to
+/// the secondary writer and no line is lost. A line only partially
+/// written to the primary writer is continued on the secondary writer.
+pub struct FallbackWriter<P: Write, S: Write> {
+    primary: P,
+    secondary: S,
+
+    max_failures: u32,
+    probe_interval: Option<Duration>,
+    clock: Clock,
+
+    failures: u32,
+    on_secondary: bool,
+    last_probe: Instant,
+}
+
+impl<P: Write + std::fmt::Debug, S: Write + std::fmt::Debug> std::fmt::Debug
+    for FallbackWriter<P, S>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FallbackWriter")
+            .field("primary", &self.primary)
+            .field("secondary", &self.secondary)
+            .field("max_failures", &self.max_failures)
+            .field("probe_interval", &self.probe_interval)
+            .field("failures", &self.failures)
+            .field("on_secondary", &self.on_secondary)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<P: Write, S: Write> FallbackWriter<P, S> {
+    /// Create a new [`FallbackWriter`] that switches to the secondary
+    /// writer after three failed writes in a row and never switches back.
+    pub fn new(primary: P, secondary: S) -> Self {
+        Self {
+            primary,
+            secondary,
+
+            max_failures: 3,
+            probe_interval: None,
+            clock: Box::new(Instant::now),
+
+            failures: 0,
+            on_secondary: false,
+            last_probe: Instant::now(),
+        }
+    }
+
+    /// Set after how many failed writes in a row to switch to the
+    /// secondary writer. Failures before are returned as they are.
+    /// Interrupted and would block errors are not counted.
+    #[must_use]
+    pub fn with_max_failures(self, max_failures: u32) -> Self {
+        Self {
+            max_failures,
+            ..self
+        }
+    }
+
+    /// Try the primary writer again when writing once the interval passed
+    /// since the switch or the last try, and switch back to it when it
+    /// accepts the write.
+    #[must_use]
+    pub fn with_probe_interval(self, probe_interval: Duration) -> Self {
+        Self {
+            probe_interval: Some(probe_interval),
+            ..self
+        }
+    }
+
+    /// Set the clock used to decide when to try the primary writer again.
+    /// Defaults to [`Instant::now`].
+    #[must_use]
+    pub fn with_clock(self, clock: impl Fn() -> Instant + Send + 'static) -> Self {
+        Self {
+            clock: Box::new(clock),
+            ..self
+        }
+    }
+
+    /// Whether writes currently go to the secondary writer.
+    #[must_use]
+    pub fn is_on_secondary(&self) -> bool {
+        self.on_secondary
+    }
+
+    /// Get a reference to the primary writer.
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    /// Get a reference to the secondary writer.
+    pub fn secondary(&self) -> &S {
+        &self.secondary
+    }
+
+    /// Get back the primary and the secondary writer.
+    pub fn into_inner(self) -> (P, S) {
+        (self.primary, self.secondary)
+    }
+
+    fn write_primary(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let err = match self.primary.write(buf) {
+            Ok(count) => {
+                self.failures = 0;
+                return Ok(count);
+            }
+
+            Err(err) if matches!(err.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock) => {
+                return Err(err);
+            }
+
+            Err(err) => err,
+        };
+
+        self.failures += 1;
+        if self.failures < self.max_failures {
+            return Err(err);
+        }
+
+        writeln!(
+            self.secondary,
+            "primary writer failed, writing here instead: {err}"
+        )?;
+
+        self.on_secondary = true;
+        self.last_probe = (self.clock)();
+
+        self.secondary.write(buf)
+    }
+
+    fn write_secondary(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let now = (self.clock)();
+
+        let probe_due = self
+            .probe_interval
+            .is_some_and(|interval| now.saturating_duration_since(self.last_probe) >= interval);
+
+        if probe_due {
+            self.last_probe = now;
+
+            if let Ok(count) = self.primary.write(buf) {
+                writeln!(
+                    self.secondary,
+                    "primary writer recovered, writing there again"
+                )?;
+
+                self.on_secondary = false;
+                self.failures = 0;
+
+                return Ok(count);
+            }
+        }
+
+        self.secondary.write(buf)
+    }
+}
+
+impl<P: Write, S: Write> Write for FallbackWriter<P, S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.on_secondary {
+            self.write_secondary(buf)
+        } else {
+            self.write_primary(buf)
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.on_secondary {
+            self.secondary.flush()
+        } else {
+            self.primary.flush()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::Write,
+        sync::{
+            Arc,
+            Mutex,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    use super::FallbackWriter;
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Fails every write while `failing` is set, and starts failing once
+    /// it accepted `fail_after` bytes if set.
+    #[derive(Debug, Default)]
+    struct FailingWriter {
+        buffer: Vec<u8>,
+        failing: bool,
+        fail_after: Option<usize>,
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.failing || self.fail_after == Some(0) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::BrokenPipe,
+                    "connection lost",
+                ));
+            }
+
+            let count = self
+                .fail_after
+                .map_or(buf.len(), |left| left.min(buf.len()));
+            self.fail_after = self.fail_after.map(|left| left - count);
+
+            self.buffer.write(&buf[..count])
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Write the lines one by one, writing each line again as long as it
+    /// fails like a caller would.
+    fn write_lines(
+        writer: &mut PrefixWriter<FallbackWriter<FailingWriter, Vec<u8>>>,
+        lines: &[&str],
+    ) {
+        for line in lines {
+            while writer.write_all(line.as_bytes()).is_err() {}
+        }
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            write_lines,
+            Arc,
+            Duration,
+            FailingWriter,
+            FallbackWriter,
+            Instant,
+            Mutex,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn switch_to_secondary() {
+            let fallback =
+                FallbackWriter::new(FailingWriter::default(), Vec::new()).with_max_failures(2);
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), fallback);
+
+            write_lines(&mut writer, &["first\n"]);
+
+            writer.get_mut().primary.failing = true;
+            assert!(writer.write_all(b"second\n").is_err());
+            assert!(!writer.get_ref().is_on_secondary());
+
+            write_lines(&mut writer, &["second\n", "third\n"]);
+            assert!(writer.get_ref().is_on_secondary());
+
+            let fallback = writer.get_ref();
+            assert_eq!(
+                "prefix: first\n",
+                String::from_utf8_lossy(&fallback.primary().buffer)
+            );
+            assert_eq!(
+                concat!(
+                    "primary writer failed, writing here instead: connection lost\n",
+                    "prefix: second\n",
+                    "prefix: third\n",
+                ),
+                String::from_utf8_lossy(fallback.secondary())
+            );
+        }
+
+        #[test]
+        fn partial_write() {
+            let fallback =
+                FallbackWriter::new(FailingWriter::default(), Vec::new()).with_max_failures(2);
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), fallback);
+
+            write_lines(&mut writer, &["first\n"]);
+            writer.get_mut().primary.fail_after = Some(3);
+            write_lines(&mut writer, &["second\n", "third\n"]);
+            assert!(writer.get_ref().is_on_secondary());
+
+            let fallback = writer.get_ref();
+            assert_eq!(
+                "prefix: first\npre",
+                String::from_utf8_lossy(&fallback.primary().buffer)
+            );
+            assert_eq!(
+                concat!(
+                    "primary writer failed, writing here instead: connection lost\n",
+                    "fix: second\n",
+                    "prefix: third\n",
+                ),
+                String::from_utf8_lossy(fallback.secondary())
+            );
+        }
+
+        #[test]
+        fn probe_primary() {
+            let start = Instant::now();
+            let now = Arc::new(Mutex::new(start));
+
+            let clock = Arc::clone(&now);
+            let fallback = FallbackWriter::new(FailingWriter::default(), Vec::new())
+                .with_max_failures(1)
+                .with_probe_interval(Duration::from_secs(1))
+                .with_clock(move || *clock.lock().unwrap());
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), fallback);
+
+            writer.get_mut().primary.failing = true;
+            write_lines(&mut writer, &["first\n"]);
+
+            *now.lock().unwrap() = start + Duration::from_secs(1);
+            write_lines(&mut writer, &["second\n"]);
+
+            writer.get_mut().primary.failing = false;
+            write_lines(&mut writer, &["third\n"]);
+
+            *now.lock().unwrap() = start + Duration::from_secs(2);
+            write_lines(&mut writer, &["fourth\n"]);
+            assert!(!writer.get_ref().is_on_secondary());
+
+            let fallback = writer.get_ref();
+            assert_eq!(
+                "prefix: fourth\n",
+                String::from_utf8_lossy(&fallback.primary().buffer)
+            );
+            assert_eq!(
+                concat!(
+                    "primary writer failed, writing here instead: connection lost\n",
+                    "prefix: first\n",
+                    "prefix: second\n",
+                    "prefix: third\n",
+                    "primary writer recovered, writing there again\n",
+                ),
+                String::from_utf8_lossy(fallback.secondary())
+            );
+        }
+    }
+}
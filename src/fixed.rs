@@ -0,0 +1,259 @@
+//! Prefix writer with a fixed capacity for incomplete lines that does
+//! not allocate after construction.
+
+use std::io::Write;
+
+use crate::is_empty_content;
+
+/// What a [`FixedPrefixWriter`] does when an incomplete line does not
+/// fit into its buffer anymore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Write out what is buffered so far. The rest of the line is written
+    /// without buffering until the line is complete, so the output is the
+    /// same as if the line was buffered.
+    #[default]
+    Emit,
+
+    /// Return an error of kind [`std::io::ErrorKind::OutOfMemory`].
+    Error,
+}
+
+/// Like [`crate::PrefixWriter`] but incomplete lines are kept in an
+/// inline buffer of `N` bytes, so writing never allocates. Bytes are
+/// passed through as they are, without any UTF-8 handling.
+#[derive(Debug)]
+pub struct FixedPrefixWriter<W: Write, const N: usize> {
+    prefix: String,
+    writer: W,
+
+    overflow: Overflow,
+
+    buffer: [u8; N],
+    buffered: usize,
+    line_started: bool,
+}
+
+impl<W: Write, const N: usize> Write for FixedPrefixWriter<W, N> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut consumed = 0;
+
+        for segment in buf.split_inclusive(|byte| *byte == b'\n') {
+            let result = match segment.strip_suffix(b"\n") {
+                Some(content) => self.write_line(content),
+                None => self.buffer_incomplete(segment),
+            };
+
+            if let Err(err) = result {
+                if consumed > 0 {
+                    return Ok(consumed);
+                }
+
+                return Err(err);
+            }
+
+            consumed += segment.len();
+        }
+
+        Ok(consumed)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buffered > 0 {
+            if !is_empty_content(&self.buffer[..self.buffered]) {
+                self.start_line()?;
+            }
+
+            self.writer.write_all(&self.buffer[..self.buffered])?;
+            self.buffered = 0;
+        }
+
+        self.line_started = false;
+
+        self.writer.flush()
+    }
+}
+
+impl<W: Write, const N: usize> FixedPrefixWriter<W, N> {
+    /// Create a new [`FixedPrefixWriter`] using the prefix for prefixing
+    /// lines and the writer for writing the output of the prefixed
+    /// lines.
+    pub fn new(prefix: String, writer: W) -> Self {
+        Self {
+            prefix,
+            writer,
+
+            overflow: Overflow::default(),
+
+            buffer: [0; N],
+            buffered: 0,
+            line_started: false,
+        }
+    }
+
+    /// Set what happens when an incomplete line exceeds the capacity.
+    #[must_use]
+    pub fn with_overflow(self, overflow: Overflow) -> Self {
+        Self { overflow, ..self }
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write the prefix unless the current line was already started.
+    fn start_line(&mut self) -> std::io::Result<()> {
+        if !self.line_started {
+            self.writer.write_all(self.prefix.as_bytes())?;
+            self.line_started = true;
+        }
+
+        Ok(())
+    }
+
+    /// Complete the current line with its last content.
+    fn write_line(&mut self, content: &[u8]) -> std::io::Result<()> {
+        if !self.is_empty_line(content) {
+            self.start_line()?;
+        }
+
+        self.writer.write_all(&self.buffer[..self.buffered])?;
+        self.writer.write_all(content)?;
+        self.writer.write_all(b"\n")?;
+
+        self.buffered = 0;
+        self.line_started = false;
+
+        Ok(())
+    }
+
+    /// Whether the current line completed with its last content is
+    /// empty and must not be prefixed.
+    fn is_empty_line(&self, content: &[u8]) -> bool {
+        match (self.buffered, content) {
+            (0, content) => is_empty_content(content),
+            (buffered, []) => is_empty_content(&self.buffer[..buffered]),
+            _ => false,
+        }
+    }
+
+    /// Keep an incomplete line in the buffer or apply the overflow
+    /// policy when it does not fit.
+    fn buffer_incomplete(&mut self, content: &[u8]) -> std::io::Result<()> {
+        let end = self.buffered + content.len();
+
+        if end <= N {
+            self.buffer[self.buffered..end].copy_from_slice(content);
+            self.buffered = end;
+
+            return Ok(());
+        }
+
+        match self.overflow {
+            Overflow::Emit => {
+                self.start_line()?;
+                self.writer.write_all(&self.buffer[..self.buffered])?;
+                self.buffered = 0;
+
+                self.writer.write_all(content)
+            }
+
+            // Creating the error from the kind alone does not allocate.
+            Overflow::Error => Err(std::io::ErrorKind::OutOfMemory.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::{
+        FixedPrefixWriter,
+        Overflow,
+    };
+
+    const PREFIX: &str = "prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            FixedPrefixWriter,
+            Overflow,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn lines() {
+            let mut writer = FixedPrefixWriter::<_, 16>::new(PREFIX.to_owned(), Vec::new());
+
+            writer.write_all(b"first\n\nsec").unwrap();
+            writer.write_all(b"ond\nthird").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                "prefix: first\n\nprefix: second\nprefix: third",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn empty_lines() {
+            let mut writer = FixedPrefixWriter::<_, 16>::new(PREFIX.to_owned(), Vec::new());
+
+            writer.write_all(b"first\r\n\r\n\r").unwrap();
+            writer.write_all(b"\n\r").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                "prefix: first\r\n\r\n\r\n\r",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn overflow_emit() {
+            let mut writer = FixedPrefixWriter::<_, 4>::new(PREFIX.to_owned(), Vec::new());
+
+            writer.write_all(b"fir").unwrap();
+            writer.write_all(b"st lo").unwrap();
+            assert_eq!(
+                "prefix: first lo",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+
+            writer.write_all(b"ng\nnext\n").unwrap();
+
+            assert_eq!(
+                "prefix: first long\nprefix: next\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn overflow_error() {
+            let mut writer = FixedPrefixWriter::<_, 4>::new(PREFIX.to_owned(), Vec::new())
+                .with_overflow(Overflow::Error);
+
+            writer.write_all(b"fir").unwrap();
+            let err = writer.write_all(b"st\nlonger").unwrap_err();
+            assert_eq!(std::io::ErrorKind::OutOfMemory, err.kind());
+
+            writer.write_all(b"er").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                "prefix: first\nprefix: er",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
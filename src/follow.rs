@@ -0,0 +1,403 @@
+//! Following a growing file like `tail -f` and prefixing what is
+//! appended.
+
+use std::{
+    fs::{
+        File,
+        Metadata,
+    },
+    io::{
+        Read,
+        Seek,
+        SeekFrom,
+        Write,
+    },
+    path::Path,
+    sync::atomic::{
+        AtomicBool,
+        Ordering,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use crate::PrefixWriter;
+
+/// Follow the file with the default options of [`Follower::new`], see
+/// [`Follower::follow`].
+///
+/// # Errors
+///
+/// Returns the errors of opening and reading the file and of writing to
+/// the sink.
+pub fn follow(
+    path: impl AsRef<Path>,
+    prefix: String,
+    sink: impl Write,
+    stop: &AtomicBool,
+) -> std::io::Result<()> {
+    Follower::new().follow(path, prefix, sink, stop)
+}
+
+/// Options for following a file.
+#[derive(Debug, Clone)]
+pub struct Follower {
+    from_start: bool,
+    poll_interval: Duration,
+    idle_flush: Option<Duration>,
+}
+
+impl Default for Follower {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Follower {
+    /// Create a new [`Follower`] that starts at the end of the file and
+    /// checks for new data every 100 milliseconds.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            from_start: false,
+            poll_interval: Duration::from_millis(100),
+            idle_flush: None,
+        }
+    }
+
+    /// Start with the existing content of the file instead of only
+    /// following what is appended.
+    #[must_use]
+    pub fn with_from_start(self, from_start: bool) -> Self {
+        Self { from_start, ..self }
+    }
+
+    /// Set how long to wait before checking the file for new data again.
+    #[must_use]
+    pub fn with_poll_interval(self, poll_interval: Duration) -> Self {
+        Self {
+            poll_interval,
+            ..self
+        }
+    }
+
+    /// Flush an incomplete line once nothing was appended for the delay,
+    /// so it is visible without waiting for the rest of the line. The
+    /// rest of the line then starts a new prefixed line.
+    #[must_use]
+    pub fn with_idle_flush(self, delay: Duration) -> Self {
+        Self {
+            idle_flush: Some(delay),
+            ..self
+        }
+    }
+
+    /// Write everything appended to the file prefixed to the sink until
+    /// `stop` is set. When the file is truncated it is followed from its
+    /// start again, when it is replaced, for example by log rotation, the
+    /// new file is followed from its start. In both cases an incomplete
+    /// last line is completed first, so the new content starts a new
+    /// prefixed line. The incomplete last line is flushed before
+    /// returning.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of opening and reading the file and of writing
+    /// to the sink.
+    pub fn follow(
+        &self,
+        path: impl AsRef<Path>,
+        prefix: String,
+        sink: impl Write,
+        stop: &AtomicBool,
+    ) -> std::io::Result<()> {
+        let path = path.as_ref();
+
+        let mut file = File::open(path)?;
+        let mut position = if self.from_start {
+            0
+        } else {
+            file.seek(SeekFrom::End(0))?
+        };
+
+        let mut writer = PrefixWriter::new(prefix, sink);
+        let mut buffer = [0; 4096];
+        let mut last_data = Instant::now();
+        let mut incomplete = false;
+        let mut line_open = false;
+
+        while !stop.load(Ordering::Relaxed) {
+            let count = file.read(&mut buffer)?;
+
+            if count > 0 {
+                writer.write_all(&buffer[..count])?;
+
+                position += count as u64;
+                last_data = Instant::now();
+                incomplete = buffer[count - 1] != b'\n';
+                line_open = incomplete;
+
+                continue;
+            }
+
+            match std::fs::metadata(path) {
+                Ok(metadata) if !same_file(&file.metadata()?, &metadata) => {
+                    finish_line(&mut writer, line_open)?;
+                    (incomplete, line_open) = (false, false);
+
+                    file = File::open(path)?;
+                    position = 0;
+                }
+
+                Ok(metadata) if metadata.len() < position => {
+                    finish_line(&mut writer, line_open)?;
+                    (incomplete, line_open) = (false, false);
+
+                    position = file.seek(SeekFrom::Start(0))?;
+                }
+
+                // A rotated file might not be replaced yet.
+                Ok(_) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+
+            if incomplete
+                && self
+                    .idle_flush
+                    .is_some_and(|delay| last_data.elapsed() >= delay)
+            {
+                writer.flush()?;
+                incomplete = false;
+            }
+
+            std::thread::sleep(self.poll_interval);
+        }
+
+        writer.flush()
+    }
+}
+
+/// Complete the last line of content that is no longer followed, also
+/// when it was already flushed while idle, and flush it.
+fn finish_line<W: Write>(writer: &mut PrefixWriter<W>, line_open: bool) -> std::io::Result<()> {
+    if line_open {
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()
+}
+
+/// Whether both metadata belong to the same file.
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+/// Whether both metadata belong to the same file. Replaced files can not
+/// be detected without inodes, only truncation.
+#[cfg(not(unix))]
+fn same_file(_: &Metadata, _: &Metadata) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        fs::OpenOptions,
+        io::Write,
+        path::PathBuf,
+        sync::{
+            atomic::{
+                AtomicBool,
+                Ordering,
+            },
+            Arc,
+            Mutex,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    use super::Follower;
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Writer that can be read while another thread writes to it.
+    #[derive(Debug, Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+        }
+
+        /// Wait until the buffer contains the expected output.
+        fn wait_for(&self, expected: &str) {
+            let start = Instant::now();
+
+            while self.contents() != expected && start.elapsed() < Duration::from_secs(5) {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+
+            assert_eq!(expected, self.contents());
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn temp_file(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "prefix_writer-follow-{}-{name}",
+            std::process::id()
+        ));
+
+        std::fs::write(&path, content).unwrap();
+
+        path
+    }
+
+    fn append(path: &PathBuf, content: &str) {
+        OpenOptions::new()
+            .append(true)
+            .open(path)
+            .unwrap()
+            .write_all(content.as_bytes())
+            .unwrap();
+    }
+
+    mod tests {
+        use super::{
+            append,
+            temp_file,
+            AtomicBool,
+            Duration,
+            Follower,
+            Ordering,
+            SharedBuffer,
+            PREFIX,
+        };
+
+        #[test]
+        fn appended_and_truncated() {
+            let path = temp_file("truncated", "existing\n");
+            let sink = SharedBuffer::default();
+            let stop = AtomicBool::new(false);
+
+            let follower = Follower::new().with_poll_interval(Duration::from_millis(5));
+
+            std::thread::scope(|scope| {
+                let follower =
+                    scope.spawn(|| follower.follow(&path, PREFIX.to_owned(), sink.clone(), &stop));
+
+                // Give the follower time to seek to the end.
+                std::thread::sleep(Duration::from_millis(50));
+
+                append(&path, "first\nsec");
+                append(&path, "ond\n");
+                sink.wait_for("prefix: first\nprefix: second\n");
+
+                std::fs::write(&path, "new\n").unwrap();
+                sink.wait_for("prefix: first\nprefix: second\nprefix: new\n");
+
+                append(&path, "incomplete");
+                std::thread::sleep(Duration::from_millis(50));
+
+                stop.store(true, Ordering::Relaxed);
+                follower.join().unwrap().unwrap();
+            });
+
+            sink.wait_for("prefix: first\nprefix: second\nprefix: new\nprefix: incomplete");
+
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn from_start_replaced_idle_flush() {
+            let path = temp_file("replaced", "existing\n");
+            let sink = SharedBuffer::default();
+            let stop = AtomicBool::new(false);
+
+            let follower = Follower::new()
+                .with_from_start(true)
+                .with_poll_interval(Duration::from_millis(5))
+                .with_idle_flush(Duration::from_millis(20));
+
+            std::thread::scope(|scope| {
+                let follower =
+                    scope.spawn(|| follower.follow(&path, PREFIX.to_owned(), sink.clone(), &stop));
+
+                sink.wait_for("prefix: existing\n");
+
+                let rotated = path.with_extension("1");
+                std::fs::rename(&path, &rotated).unwrap();
+                std::fs::write(&path, "rotated\npart").unwrap();
+                sink.wait_for("prefix: existing\nprefix: rotated\nprefix: part");
+
+                stop.store(true, Ordering::Relaxed);
+                follower.join().unwrap().unwrap();
+
+                std::fs::remove_file(&rotated).unwrap();
+            });
+
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn incomplete_lines_rotated_and_truncated() {
+            let path = temp_file("incomplete", "existing\nold");
+            let sink = SharedBuffer::default();
+            let stop = AtomicBool::new(false);
+
+            let follower = Follower::new()
+                .with_from_start(true)
+                .with_poll_interval(Duration::from_millis(5));
+
+            std::thread::scope(|scope| {
+                let follower =
+                    scope.spawn(|| follower.follow(&path, PREFIX.to_owned(), sink.clone(), &stop));
+
+                sink.wait_for("prefix: existing\n");
+                // Give the follower time to read the incomplete line.
+                std::thread::sleep(Duration::from_millis(50));
+
+                let rotated = path.with_extension("1");
+                std::fs::rename(&path, &rotated).unwrap();
+                std::fs::write(&path, "rotated\npart").unwrap();
+                sink.wait_for("prefix: existing\nprefix: old\nprefix: rotated\n");
+                std::thread::sleep(Duration::from_millis(50));
+
+                std::fs::write(&path, "new\n").unwrap();
+                sink.wait_for(concat!(
+                    "prefix: existing\n",
+                    "prefix: old\n",
+                    "prefix: rotated\n",
+                    "prefix: part\n",
+                    "prefix: new\n",
+                ));
+
+                stop.store(true, Ordering::Relaxed);
+                follower.join().unwrap().unwrap();
+
+                std::fs::remove_file(&rotated).unwrap();
+            });
+
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Lightly highlighting recognizable payloads, like JSON or file
+//! locations, with ANSI colors.
+
+use std::io::Write;
+
+use crate::{
+    shared::Shared,
+    PrefixWriter,
+};
+
+/// Lines longer than this many bytes are not highlighted.
+const MAX_HIGHLIGHT_LENGTH: usize = 4096;
+
+const RESET: &str = "\x1b[0m";
+const KEY: &str = "\x1b[34m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const LITERAL: &str = "\x1b[35m";
+const PATH: &str = "\x1b[36m";
+
+/// Highlights the payload of lines, see [`PrefixWriter::with_highlighter`].
+pub trait Highlighter {
+    /// Append the line with highlighting to `out`. Every escape sequence
+    /// started has to be reset before the end of the line, so the
+    /// highlighting does not bleed into the terminator or the next prefix.
+    fn highlight(&mut self, line: &str, out: &mut String);
+}
+
+/// Highlights lines that are JSON objects or arrays: keys, strings,
+/// numbers and literals get their own colors. Other lines stay as they
+/// are.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonHighlighter;
+
+/// Highlights file locations like `src/main.rs:42:7` as they appear in
+/// compiler errors and stack traces, the path and the line and column
+/// numbers in their own colors.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PathHighlighter;
+
+/// Append the token wrapped in the color.
+fn paint(out: &mut String, color: &str, token: &str) {
+    out.push_str(color);
+    out.push_str(token);
+    out.push_str(RESET);
+}
+
+impl Highlighter for JsonHighlighter {
+    fn highlight(&mut self, line: &str, out: &mut String) {
+        if !matches!(line.trim_start().chars().next(), Some('{' | '[')) {
+            out.push_str(line);
+            return;
+        }
+
+        let mut rest = line;
+        while let Some(next) = rest.chars().next() {
+            let token_len = match next {
+                '"' => {
+                    let mut escaped = false;
+                    let end = rest[1..]
+                        .char_indices()
+                        .find(|&(_, c)| {
+                            let closes = c == '"' && !escaped;
+                            escaped = c == '\\' && !escaped;
+                            closes
+                        })
+                        .map_or(rest.len(), |(index, _)| index + 2);
+
+                    let is_key = rest[end..].trim_start().starts_with(':');
+                    paint(out, if is_key { KEY } else { STRING }, &rest[..end]);
+                    end
+                }
+
+                '-' | '0'..='9' => {
+                    let end = rest
+                        .find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+                        .unwrap_or(rest.len());
+                    paint(out, NUMBER, &rest[..end]);
+                    end
+                }
+
+                _ => {
+                    let literal = ["true", "false", "null"]
+                        .into_iter()
+                        .find(|literal| rest.starts_with(literal));
+
+                    if let Some(literal) = literal {
+                        paint(out, LITERAL, literal);
+                        literal.len()
+                    } else {
+                        out.push(next);
+                        next.len_utf8()
+                    }
+                }
+            };
+
+            rest = &rest[token_len..];
+        }
+    }
+}
+
+impl PathHighlighter {
+    /// Split a word into the path, the line and column numbers of a
+    /// location and what follows them, like a `:` ending the location.
+    fn location(word: &str) -> Option<(&str, &str, &str)> {
+        let (path, numbers) = word.split_once(':')?;
+
+        if path.is_empty() || !(path.contains('/') || path.contains('.')) {
+            return None;
+        }
+
+        let numbers_end = numbers
+            .find(|c: char| !(c.is_ascii_digit() || c == ':'))
+            .unwrap_or(numbers.len());
+        let location = numbers[..numbers_end].trim_end_matches(':');
+
+        if location.is_empty() || location.split(':').count() > 2 {
+            return None;
+        }
+
+        Some((path, location, &numbers[location.len()..]))
+    }
+}
+
+impl Highlighter for PathHighlighter {
+    fn highlight(&mut self, line: &str, out: &mut String) {
+        for word in line.split_inclusive(char::is_whitespace) {
+            let (word, space) = word.split_at(word.trim_end().len());
+
+            match Self::location(word) {
+                Some((path, location, rest)) => {
+                    paint(out, PATH, path);
+                    out.push(':');
+                    paint(out, NUMBER, location);
+                    out.push_str(rest);
+                }
+                None => out.push_str(word),
+            }
+
+            out.push_str(space);
+        }
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Highlight the payload of every line with the highlighter, while
+    /// the prefix keeps its own style. Highlighting only runs when colors
+    /// are enabled, which they are unless the `NO_COLOR` environment
+    /// variable is set when this is called, see
+    /// [`PrefixWriter::with_colors`], and the output is not known to go
+    /// somewhere other than a terminal. Lines longer than 4 KiB and lines
+    /// that are wrapped are not highlighted.
+    #[must_use]
+    pub fn with_highlighter(self, highlighter: impl Highlighter + Send + 'static) -> Self {
+        Self {
+            highlighter: Some(Shared::new(Box::new(highlighter))),
+            colors: std::env::var_os("NO_COLOR").is_none(),
+            ..self
+        }
+    }
+
+    /// Enable or disable colors for [`PrefixWriter::with_highlighter`].
+    #[must_use]
+    pub fn with_colors(self, colors: bool) -> Self {
+        Self { colors, ..self }
+    }
+
+    /// Highlight the payload of the line, or `None` if it is not
+    /// highlighted because colors are disabled or it is too long or
+    /// wrapped.
+    pub(crate) fn highlight_unwrapped(&mut self, line: &str) -> Option<String> {
+        let colors = self.colors && self.terminal != Some(false);
+        let wrapped = self
+            .wrap_width
+            .is_some_and(|width| self.width_unit.measure(line) > width);
+
+        if !colors || wrapped || line.len() > MAX_HIGHLIGHT_LENGTH {
+            return None;
+        }
+
+        let highlighter = self.highlighter.as_ref()?;
+
+        let mut out = String::with_capacity(line.len());
+        highlighter.lock().highlight(line, &mut out);
+
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::{
+        Highlighter,
+        JsonHighlighter,
+        PathHighlighter,
+    };
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Highlight the line, showing escape sequences readable.
+    fn highlighted(mut highlighter: impl Highlighter, line: &str) -> String {
+        let mut out = String::new();
+        highlighter.highlight(line, &mut out);
+
+        out.replace("\x1b[0m", ">")
+            .replace("\x1b[34m", "<key:")
+            .replace("\x1b[32m", "<string:")
+            .replace("\x1b[33m", "<number:")
+            .replace("\x1b[35m", "<literal:")
+            .replace("\x1b[36m", "<path:")
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            highlighted,
+            JsonHighlighter,
+            PathHighlighter,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn json() {
+            assert_eq!(
+                concat!(
+                    r#"{<key:"id">: <number:42>, <key:"name">: <string:"a \"quoted\" ü">, "#,
+                    r#"<key:"tags">: [<literal:true>, <literal:null>, <number:-1.5e3>]}"#,
+                ),
+                highlighted(
+                    JsonHighlighter,
+                    r#"{"id": 42, "name": "a \"quoted\" ü", "tags": [true, null, -1.5e3]}"#
+                )
+            );
+            assert_eq!(
+                "not json: true 42",
+                highlighted(JsonHighlighter, "not json: true 42")
+            );
+        }
+
+        #[test]
+        fn paths() {
+            assert_eq!(
+                "error at <path:src/main.rs>:<number:42:7>: expected `;`, see \
+                 <path:Cargo.toml>:<number:3>",
+                highlighted(
+                    PathHighlighter,
+                    "error at src/main.rs:42:7: expected `;`, see Cargo.toml:3"
+                )
+            );
+            assert_eq!(
+                "time 12:30:00 and key:value and a:1:2:3",
+                highlighted(PathHighlighter, "time 12:30:00 and key:value and a:1:2:3")
+            );
+        }
+
+        #[test]
+        fn prefix_unstyled() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_highlighter(PathHighlighter)
+                .with_colors(true);
+
+            writer.write_all(b"at lib.rs:1\nplain\n").unwrap();
+
+            assert_eq!(
+                "prefix: at \x1b[36mlib.rs\x1b[0m:\x1b[33m1\x1b[0m\nprefix: plain\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn skipped() {
+            let long = format!("{{\"data\": \"{}\"}}", "x".repeat(5000));
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_highlighter(JsonHighlighter)
+                .with_colors(true);
+            writeln!(writer, "{long}").unwrap();
+            assert_eq!(
+                format!("{PREFIX}{long}\n"),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_highlighter(JsonHighlighter)
+                .with_colors(false);
+            writer.write_all(b"{\"id\": 1}\n").unwrap();
+            assert_eq!(
+                "prefix: {\"id\": 1}\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
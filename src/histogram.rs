@@ -0,0 +1,87 @@
+//! Counting the lengths of the lines written.
+
+use std::io::Write;
+
+use crate::PrefixWriter;
+
+impl<W: Write> PrefixWriter<W> {
+    /// Count the lines written by the length of their content in bytes,
+    /// without prefix and terminator, see
+    /// [`PrefixWriter::length_histogram`]. The boundaries split the
+    /// lengths into buckets, `[10, 80]` counts lines shorter than 10
+    /// bytes, lines from 10 to 79 bytes and lines of 80 bytes or more.
+    #[must_use]
+    pub fn with_length_histogram(self, mut boundaries: Vec<usize>) -> Self {
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        let counts = vec![0; boundaries.len() + 1];
+
+        Self {
+            length_histogram: Some((boundaries, counts)),
+            ..self
+        }
+    }
+
+    /// Number of lines written in every bucket of
+    /// [`PrefixWriter::with_length_histogram`], one more than there are
+    /// boundaries. Empty when the histogram is disabled.
+    #[must_use]
+    pub fn length_histogram(&self) -> &[u64] {
+        self.length_histogram
+            .as_ref()
+            .map_or(&[], |(_, counts)| counts)
+    }
+
+    /// Count a line of the length in the histogram.
+    pub(crate) fn count_length(&mut self, length: usize) {
+        if let Some((ref boundaries, ref mut counts)) = self.length_histogram {
+            counts[boundaries.partition_point(|boundary| *boundary <= length)] += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn counts() {
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_length_histogram(vec![10, 5]);
+
+            for length in [0, 4, 5, 9, 10, 100] {
+                writeln!(writer, "{}", "x".repeat(length)).unwrap();
+            }
+            writer.write_all(b"split ").unwrap();
+            writer.write_all(b"between writes\r\nunfinished").unwrap();
+
+            assert_eq!(&[2, 2, 3], writer.length_histogram());
+
+            writer.flush().unwrap();
+
+            assert_eq!(&[2, 2, 4], writer.length_histogram());
+        }
+
+        #[test]
+        fn disabled() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+            writer.write_all(b"line\n").unwrap();
+
+            assert!(writer.length_histogram().is_empty());
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Placing the prefix before or after the indentation of lines and
+//! normalizing the indentation.
+
+use std::{
+    borrow::Cow,
+    io::Write,
+};
+
+use crate::PrefixWriter;
+
+/// Where the prefix goes on lines that are indented, see
+/// [`PrefixWriter::with_indent_placement`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IndentPlacement {
+    /// The prefix goes before the indentation, `web |     item`.
+    #[default]
+    PrefixFirst,
+
+    /// The prefix goes after the indentation, `    web | item`, so
+    /// indented output like YAML keeps its shape. Spaces and tabs both
+    /// count as indentation and are written as they are. Lines of only
+    /// whitespace have no content to put the prefix in front of and are
+    /// prefixed before their whitespace.
+    IndentFirst,
+}
+
+impl IndentPlacement {
+    /// Split the line into the part written before the prefix and the
+    /// part written after it.
+    pub(crate) fn split(self, line: &str) -> (&str, &str) {
+        match self {
+            Self::PrefixFirst => ("", line),
+            Self::IndentFirst => {
+                let content_start = line
+                    .find(|character| !matches!(character, ' ' | '\t'))
+                    .unwrap_or(0);
+
+                line.split_at(content_start)
+            }
+        }
+    }
+}
+
+/// Replace the tabs in the indentation of the line with spaces up to the
+/// next tab stop, every `tab_width` columns. The rest of the line stays
+/// as it is.
+pub(crate) fn normalize_indent(line: &str, tab_width: usize) -> Cow<'_, str> {
+    let content_start = line
+        .find(|character| !matches!(character, ' ' | '\t'))
+        .unwrap_or(line.len());
+    let (indentation, content) = line.split_at(content_start);
+
+    if !indentation.contains('\t') {
+        return line.into();
+    }
+
+    let mut normalized = String::with_capacity(line.len() + tab_width);
+    for character in indentation.chars() {
+        let spaces = if character == '\t' {
+            tab_width - normalized.len() % tab_width
+        } else {
+            1
+        };
+        normalized.extend(std::iter::repeat_n(' ', spaces));
+    }
+    normalized.push_str(content);
+
+    normalized.into()
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Set whether the prefix goes before or after the indentation of
+    /// lines. Defaults to [`IndentPlacement::PrefixFirst`]. Segments of
+    /// lines wrapped by [`PrefixWriter::with_wrap_width`] after the first
+    /// one start with the prefix.
+    #[must_use]
+    pub fn with_indent_placement(self, indent_placement: IndentPlacement) -> Self {
+        Self {
+            indent_placement,
+            ..self
+        }
+    }
+
+    /// Normalize the indentation of lines to spaces, replacing tabs with
+    /// spaces up to the next tab stop every `tab_width` columns, for
+    /// output that mixes tabs and spaces. Only the leading whitespace is
+    /// changed, tabs later in the line are kept, and a `tab_width` of 0
+    /// counts as 1. The indentation is normalized before the prefix is
+    /// added and before [`PrefixWriter::with_indent_placement`] looks at
+    /// it.
+    #[must_use]
+    pub fn with_normalize_indent(self, tab_width: usize) -> Self {
+        Self {
+            normalize_indent: Some(tab_width.max(1)),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::IndentPlacement;
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "web | ";
+    const YAML: &str = "services:\n  web:\n    ports:\n      - 80\n\n  \n\tdb: {}\n";
+
+    fn rendered(indent_placement: IndentPlacement) -> String {
+        let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+            .with_indent_placement(indent_placement);
+
+        writer.write_all(YAML.as_bytes()).unwrap();
+
+        String::from_utf8_lossy(writer.get_ref()).into_owned()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            rendered,
+            IndentPlacement,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn prefix_first() {
+            assert_eq!(
+                "web | services:\nweb |   web:\nweb |     ports:\nweb |       - 80\n\nweb |   \
+                 \nweb | \tdb: {}\n",
+                rendered(IndentPlacement::PrefixFirst)
+            );
+        }
+
+        #[test]
+        fn indent_first() {
+            assert_eq!(
+                "web | services:\n  web | web:\n    web | ports:\n      web | - 80\n\nweb |   \
+                 \n\tweb | db: {}\n",
+                rendered(IndentPlacement::IndentFirst)
+            );
+        }
+
+        #[test]
+        fn normalize_indent() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_normalize_indent(4)
+                .with_indent_placement(IndentPlacement::PrefixFirst);
+
+            writer
+                .write_all(b"\tone tab\n  \tspaces then tab\n\t  tab then spaces\n     five\nx\tinner\ttab\n\t\n")
+                .unwrap();
+
+            assert_eq!(
+                concat!(
+                    "web |     one tab\n",
+                    "web |     spaces then tab\n",
+                    "web |       tab then spaces\n",
+                    "web |      five\n",
+                    "web | x\tinner\ttab\n",
+                    "web |     \n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn normalize_indent_first() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_normalize_indent(2)
+                .with_indent_placement(IndentPlacement::IndentFirst);
+
+            writer.write_all(b"\t\tdeep\n \titem\n").unwrap();
+
+            assert_eq!(
+                "    web | deep\n  web | item\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
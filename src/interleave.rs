@@ -0,0 +1,191 @@
+//! Merging the output of many readers into one writer.
+
+use std::io::{
+    Read,
+    Write,
+};
+
+use crate::ShardedPrefixWriter;
+
+/// Copy every source into the sink on its own thread, prefixing each
+/// line with the name of its source. Lines appear in the order they are
+/// completed and are never mixed with lines of other sources. Returns
+/// once every source reached its end. The incomplete last line of a
+/// source is completed with a newline, so it can not be mixed with the
+/// lines of sources that are still running.
+///
+/// A source that fails does not stop the other sources. Its incomplete
+/// last line is still written and the first error of all sources is
+/// returned once every source is done.
+///
+/// # Errors
+///
+/// Returns the first error of reading a source or writing to the sink.
+pub fn interleave(
+    sources: Vec<(String, Box<dyn Read + Send>)>,
+    sink: impl Write + Send,
+) -> std::io::Result<()> {
+    let shared = ShardedPrefixWriter::new(String::new(), sink);
+
+    std::thread::scope(|scope| {
+        let threads = sources
+            .into_iter()
+            .map(|(name, mut source)| {
+                let mut handle = shared.handle_with_prefix(name);
+
+                scope.spawn(move || copy_source(&mut source, &mut handle))
+            })
+            .collect::<Vec<_>>();
+
+        threads
+            .into_iter()
+            .map(|thread| {
+                thread
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .fold(Ok(()), Result::and)
+    })
+}
+
+/// Copy a single source and complete its incomplete last line, even when
+/// reading fails.
+fn copy_source(source: &mut dyn Read, handle: &mut impl Write) -> std::io::Result<()> {
+    let mut buffer = [0; 4096];
+    let mut complete = true;
+
+    let copied = loop {
+        match source.read(&mut buffer) {
+            Ok(0) => break Ok(()),
+            Ok(count) => {
+                handle.write_all(&buffer[..count])?;
+                complete = buffer[count - 1] == b'\n';
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => break Err(err),
+        }
+    };
+
+    if !complete {
+        handle.write_all(b"\n")?;
+    }
+
+    handle.flush()?;
+
+    copied
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        collections::VecDeque,
+        io::Read,
+        time::Duration,
+    };
+
+    use super::interleave;
+
+    /// Reader returning its chunks one by one with a delay before each.
+    struct StaggeredReader {
+        chunks: VecDeque<&'static [u8]>,
+        delay: Duration,
+        fail: bool,
+    }
+
+    impl StaggeredReader {
+        fn boxed(chunks: &[&'static [u8]], delay_ms: u64, fail: bool) -> Box<dyn Read + Send> {
+            Box::new(Self {
+                chunks: chunks.iter().copied().collect(),
+                delay: Duration::from_millis(delay_ms),
+                fail,
+            })
+        }
+    }
+
+    impl Read for StaggeredReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            std::thread::sleep(self.delay);
+
+            let Some(chunk) = self.chunks.pop_front() else {
+                if self.fail {
+                    return Err(std::io::Error::other("fault"));
+                }
+
+                return Ok(0);
+            };
+
+            buf[..chunk.len()].copy_from_slice(chunk);
+
+            Ok(chunk.len())
+        }
+    }
+
+    /// Lines of the given source in the order they appear.
+    fn lines_of<'a>(got: &'a str, prefix: &str) -> Vec<&'a str> {
+        got.lines()
+            .filter_map(|line| line.strip_prefix(prefix))
+            .collect()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            interleave,
+            lines_of,
+            StaggeredReader,
+        };
+
+        #[test]
+        fn line_integrity() {
+            let sources = vec![
+                (
+                    "a: ".to_owned(),
+                    StaggeredReader::boxed(&[b"fir", b"st\nsec", b"ond\nla", b"st"], 3, false),
+                ),
+                (
+                    "b: ".to_owned(),
+                    StaggeredReader::boxed(&[b"one\ntw", b"o\n", b"thr", b"ee\n"], 5, false),
+                ),
+                (
+                    "c: ".to_owned(),
+                    StaggeredReader::boxed(&[b"x", b"y", b"z\n"], 7, false),
+                ),
+            ];
+
+            let mut sink = Vec::new();
+            interleave(sources, &mut sink).unwrap();
+
+            let got = String::from_utf8_lossy(&sink);
+
+            assert_eq!(7, got.lines().count());
+            assert_eq!(vec!["first", "second", "last"], lines_of(&got, "a: "));
+            assert_eq!(vec!["one", "two", "three"], lines_of(&got, "b: "));
+            assert_eq!(vec!["xyz"], lines_of(&got, "c: "));
+        }
+
+        #[test]
+        fn failing_source() {
+            let sources = vec![
+                (
+                    "a: ".to_owned(),
+                    StaggeredReader::boxed(&[b"first\nincom", b"plete"], 1, true),
+                ),
+                (
+                    "b: ".to_owned(),
+                    StaggeredReader::boxed(&[b"one\n", b"two\n"], 5, false),
+                ),
+            ];
+
+            let mut sink = Vec::new();
+            let err = interleave(sources, &mut sink).unwrap_err();
+
+            assert_eq!("fault", err.to_string());
+
+            let got = String::from_utf8_lossy(&sink);
+
+            assert_eq!(vec!["first", "incomplete"], lines_of(&got, "a: "));
+            assert_eq!(vec!["one", "two"], lines_of(&got, "b: "));
+        }
+    }
+}
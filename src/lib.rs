@@ -7,57 +7,612 @@
 //! Crate for a writer that can prefix text that contains multiple
 //! lines or incomplete lines.
 
-use std::io::Write;
+use std::{
+    borrow::Cow,
+    io::Write,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use crate::{
+    alert::Alert,
+    context::PrefixContext,
+    durability::Syncer,
+    rate_limit::RateLimit,
+    records::Records,
+    resources::ResourceMonitor,
+    retry::Sleep,
+    sampling::Sampler,
+    sanitize::Sanitizer,
+    shared::{
+        PrefixIter,
+        Shared,
+    },
+    sort::Sort,
+    verbosity::KeyVerbosity,
+};
+
+mod alert;
+#[cfg(feature = "tokio")]
+mod async_writer;
+mod binary;
+mod caller;
+#[cfg(any(test, feature = "test-util"))]
+mod checked;
+#[cfg(feature = "tokio-util")]
+mod codec;
+mod config;
+mod context;
+mod continuation;
+mod copy;
+mod directive;
+mod durability;
+#[cfg(feature = "encoding")]
+mod encoding;
+mod error;
+mod ext;
+mod fallback;
+mod fixed;
+mod follow;
+#[cfg(feature = "highlight")]
+mod highlight;
+mod histogram;
+mod indent;
+mod interleave;
+mod line_format;
+#[cfg(all(unix, feature = "flock"))]
+mod locked_file;
+mod logfmt;
+mod macros;
+mod offset_map;
+mod paragraph;
+mod parts;
+mod per_key_files;
+mod pool;
+mod prefix;
+mod prefix_width;
+mod presets;
+#[cfg(feature = "pty")]
+mod pty;
+mod rate_limit;
+mod raw;
+mod record_separator;
+mod records;
+mod render;
+mod resources;
+mod retry;
+mod sampling;
+mod sanitize;
+mod scope;
+mod sharded;
+mod shared;
+mod shutdown;
+mod side_by_side;
+mod sort;
+#[cfg(feature = "tracing")]
+mod span_indent;
+mod stats;
+#[cfg(feature = "futures")]
+mod stream;
+#[cfg(feature = "tokio")]
+mod task_prefix;
+#[cfg(feature = "is-terminal")]
+mod terminal;
+mod timestamp;
+mod verbosity;
+mod width;
+mod wrap;
+
+#[cfg(feature = "tokio")]
+pub use async_writer::{
+    prefix_copy_async,
+    AsyncPrefixWriter,
+};
+pub use binary::BinaryDetection;
+pub use caller::CallerFormat;
+#[cfg(any(test, feature = "test-util"))]
+pub use checked::{
+    CheckedWriter,
+    Violation,
+};
+#[cfg(feature = "tokio-util")]
+pub use codec::PrefixEncoder;
+pub use config::ConfigError;
+pub use context::LineContext;
+pub use copy::{
+    copy_bufread,
+    copy_bufread_bytes,
+};
+pub use durability::{
+    Durability,
+    SyncableWrite,
+};
+#[cfg(feature = "encoding")]
+pub use encoding::Encoding;
+pub use error::{
+    InvalidPrefix,
+    PrefixWriteError,
+    WriteStage,
+};
+pub use ext::WriteExt;
+pub use fallback::FallbackWriter;
+pub use fixed::{
+    FixedPrefixWriter,
+    Overflow,
+};
+pub use follow::{
+    follow,
+    Follower,
+};
+#[cfg(feature = "highlight")]
+pub use highlight::{
+    Highlighter,
+    JsonHighlighter,
+    PathHighlighter,
+};
+pub use indent::IndentPlacement;
+pub use interleave::interleave;
+#[cfg(all(unix, feature = "flock"))]
+pub use locked_file::LockedFileSink;
+pub use logfmt::FieldSource;
+pub use parts::PrefixState;
+pub use per_key_files::{
+    KeyWriter,
+    PerKeyFiles,
+};
+pub use pool::{
+    BufferPool,
+    PooledPrefixWriter,
+};
+#[cfg(feature = "pty")]
+pub use portable_pty;
+pub use prefix::{
+    Chain,
+    Elapsed,
+    LineNumber,
+    Prefix,
+};
+pub use prefix_width::PrefixEllipsis;
+#[cfg(feature = "pty")]
+pub use pty::{
+    run_prefixed_pty,
+    run_prefixed_pty_with_size,
+};
+pub use raw::RawGuard;
+pub use records::RecordDetection;
+pub use render::render;
+pub use resources::ResourceUsage;
+pub use retry::RetryPolicy;
+pub use sampling::{
+    Sampling,
+    SamplingStats,
+};
+pub use sanitize::Sanitization;
+pub use scope::PrefixGuard;
+pub use sharded::{
+    ShardHandle,
+    ShardedPrefixWriter,
+};
+#[cfg(all(unix, feature = "signal-hook"))]
+pub use shutdown::install_flush_on_signal;
+pub use shutdown::{
+    flush_all_registered,
+    RegisteredWriter,
+};
+pub use side_by_side::{
+    SideBySideWriter,
+    SideHandle,
+};
+#[cfg(feature = "tracing")]
+pub use span_indent::SpanDepthLayer;
+pub use stats::{
+    Stats,
+    WriterStats,
+};
+#[cfg(feature = "futures")]
+pub use stream::{
+    prefix_stream,
+    PrefixStream,
+};
+#[cfg(feature = "tokio")]
+pub use task_prefix::{
+    with_task_prefix,
+    TaskPrefixedWriter,
+};
+#[cfg(feature = "is-terminal")]
+pub use terminal::PrefixMode;
+pub use time;
+pub use timestamp::{
+    ClockConfig,
+    Timestamp,
+    TimestampFormatError,
+};
+pub use verbosity::{
+    Classifier,
+    Level,
+    Verbosity,
+    VerbosityError,
+    VerbositySpec,
+    VerbosityStats,
+};
+pub use width::Unit;
 
 /// Scans lines and prefixes lines with a given prefix. Will work even
 /// when a write contains multiple lines or incomplete lines between
 /// writes. It will not prefix empty lines.
-#[derive(Debug)]
+///
+/// Flushing writes an incomplete line with its prefix but without a
+/// newline. The incomplete line is then done, the next write starts a
+/// fresh line with a new prefix.
+///
+/// Errors of the writer are returned as they are, including
+/// [`std::io::ErrorKind::WriteZero`] when the writer does not accept any
+/// more bytes. A line that could not be written is not dropped, it is
+/// part of the next write or flush again.
+///
+/// Every line is rendered completely before any of it is written, so when
+/// a closure like the one of [`PrefixWriter::with_map_line`] panics, the
+/// writer never received a part of that line. The lines of the same write
+/// before it are written, an incomplete line of earlier writes is kept.
+#[allow(clippy::struct_excessive_bools)]
 pub struct PrefixWriter<W: Write> {
     prefix: String,
     writer: W,
 
+    message_mode: bool,
+    message_terminator: bool,
+    input_delimiter: u8,
+    output_terminator: Cow<'static, str>,
+    #[cfg(feature = "encoding")]
+    line_encoder: Option<Encoding>,
+    marker_prefix: Option<String>,
+    line_suffix: Option<String>,
+    prefix_directive: Option<String>,
+    record_separator: Option<Vec<u8>>,
+    inplace_remainder: bool,
+    carriage_return_prefix: bool,
+    zebra: Option<(String, String)>,
+    prefix_first_n: Option<u64>,
+    width_unit: Unit,
+    indent_placement: IndentPlacement,
+    normalize_indent: Option<usize>,
+    max_prefix_width: Option<(usize, PrefixEllipsis)>,
+    prefix_column: Option<usize>,
+    wrap_width: Option<usize>,
+    prefix_overflow_wrap: Option<usize>,
+    wrap_continuation: Option<char>,
+    terminal: Option<bool>,
+    passthrough: bool,
+    running_count: Option<usize>,
+    word_count_prefix: bool,
+    intra_write_index: Option<String>,
+    #[cfg(feature = "tracing")]
+    span_indent: Option<String>,
+    silence_broken_pipe: bool,
+    logfmt: Option<Vec<(String, FieldSource)>>,
+    timestamp_clock: Shared<ClockConfig>,
+    timestamp_prefix: bool,
+    #[cfg(feature = "highlight")]
+    colors: bool,
+    mute_summary: bool,
+    markdown_blockquote: bool,
+    prefix_empty_lines: bool,
+    squeeze_empty_lines: bool,
+    trailing_newline: bool,
+    delta_time_prefix: bool,
+    binary_detection: Option<BinaryDetection>,
+    sanitizer: Option<Sanitizer>,
+    join_continuations: bool,
+    collapse_continuation_indent: bool,
+    max_joined_length: usize,
+    paragraph_reflow: Option<usize>,
+    paragraph_prefixes: Option<(String, String)>,
+
     remainder: Option<String>,
+    spare_remainder: Option<String>,
+    spare_rendered: Option<String>,
+    raw_remainder: Vec<u8>,
+    remainder_displayed: bool,
+    held_continuation: Option<(String, String)>,
+    paragraph: Option<String>,
+    paragraph_lines: Vec<(String, usize)>,
+    unfinished_line: Option<UnfinishedLine>,
+    after_blank_line: bool,
+    last_line_empty: bool,
+    raw_line_open: bool,
+    lines_written: u64,
+    write_index: u64,
+    last_prefixed_at: Option<Instant>,
+    broken_pipe: bool,
+    muted: bool,
+    muted_lines: u64,
+    dropped_lines: u64,
+    write_errors: u64,
+    last_write: Option<Instant>,
+    input_offset: u64,
+    output_offset: u64,
+    offset_map: Option<Vec<(u64, u64)>>,
+    length_histogram: Option<(Vec<usize>, Vec<u64>)>,
+    prefix_iter: Option<PrefixIter>,
+    prefix_context: Option<Shared<PrefixContext>>,
+    on_raw_line: Option<RawLineCallback>,
+    alert: Option<Alert>,
+    map_line: Option<Shared<MapLine>>,
+    #[cfg(feature = "highlight")]
+    highlighter: Option<Shared<Box<dyn Highlighter + Send>>>,
+    sort: Option<Sort>,
+    blank_line_sink: Option<Box<dyn Write + Send>>,
+    rate_limit: Option<RateLimit>,
+    sampler: Option<Sampler>,
+    verbosity: Option<KeyVerbosity>,
+    records: Option<Records>,
+    resource_monitor: Option<ResourceMonitor>,
+    syncer: Option<Syncer<W>>,
+    retry_policy: Option<RetryPolicy>,
+    sleep: Sleep,
+    clock: Shared<Clock>,
+}
+
+/// Carriage return followed by the ANSI sequence erasing to the end of
+/// the line.
+const ERASE_LINE: &str = "\r\x1b[K";
+
+/// Placeholder in the format of [`PrefixWriter::with_intra_write_index`]
+/// replaced by the index.
+const INDEX_PLACEHOLDER: &str = "{index}";
+
+/// ANSI sequence resetting all colors and styles.
+const RESET_STYLE: &str = "\x1b[0m";
+
+/// Rendered line of which only the start was written, see
+/// [`PrefixWriter::write_rendered_bytes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct UnfinishedLine {
+    rendered: Vec<u8>,
+    written: usize,
+    payload_len: usize,
+    terminator_len: usize,
+
+    /// Number of the line the errors of writing it are about.
+    line: u64,
+}
+
+/// Source of the current time.
+type Clock = Box<dyn Fn() -> Instant + Send>;
+
+/// Callback receiving the original content and terminator of a line.
+type RawLineCallback = Box<dyn FnMut(&str, &str) + Send>;
+
+/// Transformation of the content of a line.
+type MapLine = Box<dyn Fn(&str) -> String + Send>;
+
+impl<W: Write + std::fmt::Debug> std::fmt::Debug for PrefixWriter<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrefixWriter")
+            .field("prefix", &self.prefix)
+            .field("writer", &self.writer)
+            .field("message_mode", &self.message_mode)
+            .field("message_terminator", &self.message_terminator)
+            .field("remainder", &self.remainder)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<W: Write> Write for PrefixWriter<W> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let input = if let Some(ref remainder) = self.remainder {
-            format!("{}{}", remainder, String::from_utf8_lossy(buf)).into()
+        if self.passthrough {
+            return self.writer.write(buf);
+        }
+
+        self.write_checked(buf).map_err(Into::into)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.passthrough {
+            return self.writer.flush();
+        }
+
+        if self.broken_pipe {
+            return Ok(());
+        }
+
+        self.write_unfinished()?;
+
+        if self.muted {
+            return self.flush_writer();
+        }
+
+        self.write_sampling_note(&self.terminator())?;
+
+        let has_remainder = self.remainder.is_some();
+        let terminator = if self.trailing_newline {
+            self.terminator()
         } else {
-            String::from_utf8_lossy(buf)
+            "".into()
         };
+        self.write_remainder(&terminator)?;
 
-        let input_ends_with_newline = input.ends_with('\n');
+        if has_remainder {
+            self.remainder_displayed = self.inplace_remainder;
+        }
 
-        let mut lines = input.lines().peekable();
+        self.write_sorted()?;
+        self.write_record_group()?;
+        self.flush_writer()?;
+        self.sync_if_due()?;
 
-        while let Some(line) = lines.next() {
-            if lines.peek().is_none() && !input_ends_with_newline {
-                self.remainder = Some(line.to_owned());
-                break;
-            }
+        Ok(())
+    }
+}
 
-            if !line.is_empty() {
-                self.writer.write_all(self.prefix.as_bytes())?;
-            }
+/// Remove a terminator a transformation left at the end of the line, so
+/// it does not end up twice.
+fn strip_terminator(mut line: String) -> String {
+    if line.ends_with('\n') {
+        line.pop();
 
-            self.writer.write_all(line.as_bytes())?;
-            self.writer.write_all(&[b'\n'])?;
+        if line.ends_with('\r') {
+            line.pop();
         }
+    }
 
-        Ok(buf.len())
+    line
+}
+
+/// Split the input into the part that only contains complete lines and
+/// the incomplete line after the last delimiter.
+fn split_incomplete(buf: &[u8], delimiter: u8) -> (&[u8], &[u8]) {
+    match buf.iter().rposition(|byte| *byte == delimiter) {
+        Some(index) => buf.split_at(index + 1),
+        None => (&[], buf),
     }
+}
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        if let Some(ref remainder) = self.remainder {
-            if !remainder.is_empty() {
-                self.writer.write_all(self.prefix.as_bytes())?;
-                self.writer.write_all(remainder.as_bytes())?;
-            }
+/// Get the content of a complete line, joined with the remainder of
+/// previous writes, and the terminator of the line. With the default
+/// delimiter the terminator is either `\n` or `\r\n`, otherwise it is
+/// the delimiter.
+fn line_content<'a>(
+    remainder: Option<&str>,
+    line: &'a [u8],
+    delimiter: u8,
+) -> (Cow<'a, str>, &'a str) {
+    let (line, terminator) = match line.strip_suffix(&[delimiter]) {
+        Some(content) => (content, &line[content.len()..]),
+        None => (line, &[][..]),
+    };
+
+    let content = match remainder {
+        Some(remainder) => format!("{}{}", remainder, String::from_utf8_lossy(line)).into(),
+        None => String::from_utf8_lossy(line),
+    };
+
+    if delimiter != b'\n' {
+        return (content, std::str::from_utf8(terminator).unwrap_or_default());
+    }
+
+    if !content.ends_with('\r') {
+        return (content, "\n");
+    }
+
+    let content = match content {
+        Cow::Borrowed(content) => content[..content.len() - 1].into(),
+        Cow::Owned(mut content) => {
+            content.pop();
+            content.into()
         }
+    };
+
+    (content, "\r\n")
+}
+
+/// Whether the content of a line counts as empty and must not be
+/// prefixed. Besides no content at all this is a lone `\r`, the first
+/// half of a `\r\n` terminator that was split by a flush or a write.
+pub(crate) fn is_empty_content(content: &[u8]) -> bool {
+    matches!(content, b"" | b"\r")
+}
+
+/// Insert the prefix after every carriage return in the line except for
+/// a trailing one, which is the start of a `\r\n` terminator.
+fn prefix_carriage_returns<'a>(line: Cow<'a, str>, prefix: &str) -> Cow<'a, str> {
+    let body = line.strip_suffix('\r').unwrap_or(&line);
+
+    if !body.contains('\r') {
+        return line;
+    }
+
+    let mut prefixed = body.replace('\r', &format!("\r{prefix}"));
+    if body.len() < line.len() {
+        prefixed.push('\r');
+    }
+
+    prefixed.into()
+}
+
+/// Length of the start of a UTF-8 encoded character at the end of the
+/// bytes that is missing its last bytes.
+#[cfg(any(feature = "futures", feature = "tokio"))]
+pub(crate) fn incomplete_char_len(bytes: &[u8]) -> usize {
+    for len in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - len];
+
+        let char_len = match byte {
+            0x80..=0xbf => continue,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => return 0,
+        };
+
+        return if char_len > len { len } else { 0 };
+    }
+
+    0
+}
+
+/// Number of words in the line, separated by whitespace.
+fn word_count(line: &str) -> usize {
+    #[cfg(feature = "unicode")]
+    return line.split_whitespace().count();
+
+    #[cfg(not(feature = "unicode"))]
+    return line.split_ascii_whitespace().count();
+}
+
+/// Render a single line with the prefix and the terminator. Empty lines
+/// are not prefixed.
+pub(crate) fn render_line(prefix: &str, line: &str, terminator: &str, rendered: &mut String) {
+    if !is_empty_content(line.as_bytes()) {
+        rendered.push_str(prefix);
+    }
+
+    rendered.push_str(line);
+    rendered.push_str(terminator);
+}
 
-        self.writer.flush()
+/// Prefix every line of the slice the way a [`PrefixWriter`] does, for
+/// lines that are already split. Empty lines stay empty. The lines must
+/// not contain their terminator.
+///
+/// ```
+/// assert_eq!(
+///     vec!["> first", "", "> second"],
+///     prefix_writer::prefix_all("> ", &["first", "", "second"])
+/// );
+/// ```
+#[must_use]
+pub fn prefix_all(prefix: &str, lines: &[&str]) -> Vec<String> {
+    lines
+        .iter()
+        .map(|line| {
+            let mut rendered = String::new();
+            render_line(prefix, line, "", &mut rendered);
+            rendered
+        })
+        .collect()
+}
+
+/// Configuration for a [`PrefixWriter`] that does not have a writer yet.
+/// Has all the options of a [`PrefixWriter`] and becomes one with
+/// [`PrefixWriterBuilder::build`].
+pub type PrefixWriterBuilder = PrefixWriter<std::io::Sink>;
+
+impl PrefixWriterBuilder {
+    /// Create a new [`PrefixWriterBuilder`] using the prefix for
+    /// prefixing lines.
+    #[must_use]
+    pub fn builder(prefix: String) -> Self {
+        Self::new(prefix, std::io::sink())
+    }
+
+    /// Build a [`PrefixWriter`] with the configuration of the builder
+    /// writing the prefixed lines to the writer.
+    pub fn build<W: Write>(self, writer: W) -> PrefixWriter<W> {
+        self.map_writer(|_| writer)
     }
 }
 
@@ -66,147 +621,3362 @@ impl<W: Write> PrefixWriter<W> {
     /// Create a new [`PrefixWriter`] using the prefix for prefixing
     /// lines and the writer for writing the output of the prefixed
     /// lines.
+    ///
+    /// A prefix containing newlines is a banner: every line of the prefix
+    /// but the last one is written as a line of its own before every
+    /// prefixed line, the last line of the prefix prefixes the line. A
+    /// prefix of only `"\n"` therefore writes an empty line before every
+    /// line. Prefix widths and columns apply to the last line and empty
+    /// lines only get the banner when they are prefixed. Use
+    /// [`PrefixWriter::try_new`] to reject such prefixes instead.
     pub fn new(prefix: String, writer: W) -> Self {
         Self {
             prefix,
             writer,
 
+            message_mode: false,
+            message_terminator: true,
+            input_delimiter: b'\n',
+            output_terminator: "\n".into(),
+            #[cfg(feature = "encoding")]
+            line_encoder: None,
+            marker_prefix: None,
+            line_suffix: None,
+            prefix_directive: None,
+            record_separator: None,
+            inplace_remainder: false,
+            carriage_return_prefix: false,
+            zebra: None,
+            prefix_first_n: None,
+            width_unit: Unit::default(),
+            indent_placement: IndentPlacement::default(),
+            normalize_indent: None,
+            max_prefix_width: None,
+            prefix_column: None,
+            wrap_width: None,
+            prefix_overflow_wrap: None,
+            wrap_continuation: None,
+            terminal: None,
+            passthrough: false,
+            running_count: None,
+            word_count_prefix: false,
+            intra_write_index: None,
+            #[cfg(feature = "tracing")]
+            span_indent: None,
+            silence_broken_pipe: false,
+            logfmt: None,
+            timestamp_clock: Shared::new(ClockConfig::default()),
+            timestamp_prefix: false,
+            #[cfg(feature = "highlight")]
+            colors: false,
+            mute_summary: false,
+            markdown_blockquote: false,
+            prefix_empty_lines: false,
+            squeeze_empty_lines: false,
+            trailing_newline: false,
+            delta_time_prefix: false,
+            binary_detection: None,
+            sanitizer: None,
+            join_continuations: false,
+            collapse_continuation_indent: false,
+            max_joined_length: 64 * 1024,
+            paragraph_reflow: None,
+            paragraph_prefixes: None,
+
             remainder: None,
+            spare_remainder: None,
+            spare_rendered: None,
+            raw_remainder: Vec::new(),
+            remainder_displayed: false,
+            held_continuation: None,
+            paragraph: None,
+            paragraph_lines: Vec::new(),
+            unfinished_line: None,
+            after_blank_line: true,
+            last_line_empty: false,
+            raw_line_open: false,
+            lines_written: 0,
+            write_index: 0,
+            last_prefixed_at: None,
+            broken_pipe: false,
+            muted: false,
+            muted_lines: 0,
+            dropped_lines: 0,
+            write_errors: 0,
+            last_write: None,
+            input_offset: 0,
+            output_offset: 0,
+            offset_map: None,
+            length_histogram: None,
+            prefix_iter: None,
+            prefix_context: None,
+            on_raw_line: None,
+            alert: None,
+            map_line: None,
+            #[cfg(feature = "highlight")]
+            highlighter: None,
+            sort: None,
+            blank_line_sink: None,
+            rate_limit: None,
+            sampler: None,
+            verbosity: None,
+            records: None,
+            resource_monitor: None,
+            syncer: None,
+            retry_policy: None,
+            sleep: Box::new(std::thread::sleep),
+            clock: Shared::new(Box::new(Instant::now)),
+        }
+    }
+
+    /// Like [`PrefixWriter::new`] but rejects prefixes containing line
+    /// terminators, which would split every prefixed line, or other
+    /// control characters like NUL or ESC, which corrupt the output or
+    /// start escape sequences. Tabs are allowed. Styled prefixes, for
+    /// example those of [`PrefixWriter::docker_style`], have to be created
+    /// with [`PrefixWriter::new`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InvalidPrefix`] with the first rejected character in
+    /// the prefix.
+    pub fn try_new(prefix: String, writer: W) -> Result<Self, InvalidPrefix> {
+        if let Some((index, character)) = prefix.char_indices().find(|(_, character)| {
+            *character != '\t'
+                && (character.is_control() || matches!(character, '\u{2028}' | '\u{2029}'))
+        }) {
+            return Err(InvalidPrefix::new(character, index));
         }
+
+        Ok(Self::new(prefix, writer))
     }
 
     /// Set a new prefix for [`PrefixWriter`].
+    #[must_use]
     pub fn with_prefix(self, prefix: String) -> Self {
         Self { prefix, ..self }
     }
 
-    /// Set a new writer for [`PrefixWriter`].
-    pub fn with_writer(self, writer: W) -> Self {
-        Self { writer, ..self }
+    /// Append `extra` to the prefix, for example when entering a nested
+    /// scope. Undo with [`PrefixWriter::pop_prefix`].
+    pub fn push_prefix(&mut self, extra: &str) {
+        self.prefix.push_str(extra);
     }
-}
 
-#[cfg(test)]
-mod test {
-    use const_format::concatcp;
-    use pretty_assertions::assert_eq;
-    use rand::Rng;
-    use std::io::Write;
+    /// Remove `extra` from the end of the prefix, undoing
+    /// [`PrefixWriter::push_prefix`]. Returns whether the prefix ended with
+    /// `extra`, the prefix is left unchanged otherwise.
+    pub fn pop_prefix(&mut self, extra: &str) -> bool {
+        let Some(len) = self.prefix.strip_suffix(extra).map(str::len) else {
+            return false;
+        };
 
-    use super::PrefixWriter;
+        self.prefix.truncate(len);
+        true
+    }
 
-    const PREFIX: &str = "prefix: ";
+    /// Drop every line completed from now on until
+    /// [`PrefixWriter::unmute`] is called. Incomplete lines are still
+    /// collected and not written by flushing, so a line completed after
+    /// unmuting is written as a whole, and a line completed while muted
+    /// is dropped as a whole.
+    pub fn mute(&mut self) {
+        self.muted = true;
+    }
 
-    fn give_random_input() -> Vec<u8> {
-        let mut rng = rand::thread_rng();
-        let lines = rng.gen_range(0..10);
+    /// Write lines again after [`PrefixWriter::mute`]. With
+    /// [`PrefixWriter::with_mute_summary`] a prefixed `(N lines muted)`
+    /// line reports the dropped lines first.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the writer while writing the summary, which
+    /// is written by the next call to this then.
+    pub fn unmute(&mut self) -> std::io::Result<()> {
+        self.muted = false;
 
-        let mut buffer = String::new();
-        for _line in 0..lines {
-            let newline = rng.gen_bool(0.33);
+        let muted_lines = std::mem::take(&mut self.muted_lines);
+        if !self.mute_summary || muted_lines == 0 {
+            return Ok(());
+        }
 
-            buffer.push_str("ABC");
-            newline.then(|| buffer.push('\n'));
+        let notice = format!("({muted_lines} lines muted)");
+        let terminator = self.terminator();
+
+        let mut rendered = self.start_rendered();
+        render_line(&self.prefix, &notice, &terminator, &mut rendered);
+
+        let result = self.write_rendered(rendered.as_bytes(), notice.len(), terminator.len());
+        if result.is_err() {
+            self.muted_lines = muted_lines;
         }
-        buffer.push('e');
 
-        buffer.as_bytes().to_vec()
+        result.map_err(Into::into)
     }
 
-    mod tests {
-        use super::{
-            assert_eq,
-            concatcp,
-            give_random_input,
-            PrefixWriter,
-            Write,
-            PREFIX,
-        };
+    /// Whether lines are dropped because of [`PrefixWriter::mute`].
+    #[must_use]
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
 
-        fn run(input: &str, prefix: &str, expected: &str) {
-            let mut buffer = Vec::new();
-            let mut writer = PrefixWriter::new(prefix.to_owned(), &mut buffer);
+    /// Take the prefix for every emitted line from `iter`, in order.
+    /// Every emitted line consumes one prefix, even empty lines that are
+    /// not prefixed, so the prefixes stay paired with the lines. Once
+    /// `iter` is exhausted the default prefix is used again.
+    #[must_use]
+    pub fn with_prefix_iter(self, iter: impl Iterator<Item = String> + Send + 'static) -> Self {
+        Self {
+            prefix_iter: Some(PrefixIter::new(iter)),
+            ..self
+        }
+    }
 
-            writer.write_all(input.as_bytes()).unwrap();
-            writer.flush().unwrap();
+    /// Transform the content of every line before it is prefixed, and
+    /// encoded with [`PrefixWriter::with_line_encoder`]. A terminator at
+    /// the end of the transformed content is removed, the configured
+    /// terminator is added as usual.
+    #[must_use]
+    pub fn with_map_line(self, map_line: impl Fn(&str) -> String + Send + 'static) -> Self {
+        Self {
+            map_line: Some(Shared::new(Box::new(map_line))),
+            ..self
+        }
+    }
 
-            let got = String::from_utf8_lossy(&buffer);
+    /// Hand every prefixed line to the writer with exactly one call to
+    /// [`Write::write`] instead of [`Write::write_all`], for writers
+    /// where every write becomes one message, like datagram sockets.
+    /// Incomplete lines are only written once they are complete or on
+    /// flush. As a message can not be split, a short write is an error of
+    /// kind [`std::io::ErrorKind::Other`] and a write of zero bytes an
+    /// error of kind [`std::io::ErrorKind::WriteZero`].
+    #[must_use]
+    pub fn with_message_mode(self, message_mode: bool) -> Self {
+        Self {
+            message_mode,
+            ..self
+        }
+    }
 
-            assert_eq!(expected, got);
+    /// Whether messages in message mode include the newline terminating
+    /// the line. Defaults to `true`.
+    #[must_use]
+    pub fn with_message_terminator(self, message_terminator: bool) -> Self {
+        Self {
+            message_terminator,
+            ..self
         }
+    }
 
-        #[test]
-        fn empty() {
-            const INPUT: &str = "";
-            const EXPECTED: &str = INPUT;
+    /// Split the input into lines at the delimiter instead of `\n`, for
+    /// example `b'\0'` for NUL separated input. The delimiter is not
+    /// part of the written lines, which end with the output terminator.
+    /// Only with the default delimiter a `\r` before it is removed as
+    /// well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the delimiter is not ASCII, as splitting at other bytes
+    /// would split characters.
+    #[must_use]
+    pub fn with_input_delimiter(self, input_delimiter: u8) -> Self {
+        assert!(
+            input_delimiter.is_ascii(),
+            "input delimiter has to be ASCII"
+        );
 
-            run(INPUT, PREFIX, EXPECTED);
+        Self {
+            input_delimiter,
+            ..self
         }
+    }
 
-        #[test]
-        fn empty_lines() {
-            const INPUT: &str = "\n\n\n\n";
-            const EXPECTED: &str = INPUT;
+    /// Set the terminator written after every line instead of `\n`,
+    /// independent of how the input is split into lines.
+    #[must_use]
+    pub fn with_output_terminator(self, output_terminator: String) -> Self {
+        Self {
+            output_terminator: output_terminator.into(),
+            ..self
+        }
+    }
 
-            run(INPUT, PREFIX, EXPECTED);
+    /// Encode the content of every line with the encoding before it is
+    /// prefixed, so it survives transports that are not binary safe.
+    #[cfg(feature = "encoding")]
+    #[must_use]
+    pub fn with_line_encoder(self, encoding: Encoding) -> Self {
+        Self {
+            line_encoder: Some(encoding),
+            ..self
         }
+    }
 
-        #[test]
-        fn no_newline() {
-            const INPUT: &str = "first";
-            const EXPECTED: &str = concatcp!(PREFIX, INPUT);
+    /// Set the prefix for lines written with
+    /// [`PrefixWriter::write_marker`]. Defaults to the prefix followed by
+    /// `"== "`.
+    #[must_use]
+    pub fn with_marker_prefix(self, marker_prefix: String) -> Self {
+        Self {
+            marker_prefix: Some(marker_prefix),
+            ..self
+        }
+    }
 
-            run(INPUT, PREFIX, EXPECTED);
+    /// Overwrite the incomplete line written by a flush with whatever is
+    /// written next, for status lines that update in place. The next line
+    /// starts with a carriage return and an erase to the end of the line
+    /// instead of being appended.
+    #[must_use]
+    pub fn with_inplace_remainder(self, inplace_remainder: bool) -> Self {
+        Self {
+            inplace_remainder,
+            ..self
         }
+    }
 
-        #[test]
-        fn single_line() {
-            const INPUT: &str = "first\n";
-            const EXPECTED: &str = concatcp!(PREFIX, INPUT);
+    /// Prefix again after every carriage return within a line, so output
+    /// of programs that redraw a progress line with `\r` keeps the prefix
+    /// on every redraw.
+    #[must_use]
+    pub fn with_carriage_return_prefix(self, carriage_return_prefix: bool) -> Self {
+        Self {
+            carriage_return_prefix,
+            ..self
+        }
+    }
 
-            run(INPUT, PREFIX, EXPECTED);
+    /// Stripe the output by wrapping every line, prefix and content, in
+    /// the ANSI background codes alternating between lines, like
+    /// `"48;5;236"` and `"48;5;238"`. The first line uses `even_code`.
+    /// Empty lines are not wrapped but still count for the alternation.
+    #[must_use]
+    pub fn with_zebra(self, even_code: String, odd_code: String) -> Self {
+        Self {
+            zebra: Some((even_code, odd_code)),
+            ..self
         }
+    }
 
-        #[test]
-        fn two_line() {
-            const INPUT: &str = "first\nsecond\n";
-            const EXPECTED: &str = concatcp!(PREFIX, "first\n", PREFIX, "second\n");
+    /// Only prefix the first `n` lines, empty lines included, and write
+    /// all following lines without a prefix, like a header.
+    #[must_use]
+    pub fn with_prefix_first_n(self, n: usize) -> Self {
+        Self {
+            prefix_first_n: Some(u64::try_from(n).unwrap_or(u64::MAX)),
+            ..self
+        }
+    }
 
-            run(INPUT, PREFIX, EXPECTED);
+    /// Call `f` with the original content and terminator of every line
+    /// after it was written, before any transformation. The terminator is
+    /// `"\n"` or `"\r\n"` as it appeared in the input, or empty for an
+    /// incomplete line written by a flush, so the input can be
+    /// reconstructed exactly. The writer is borrowed while `f` runs, so
+    /// `f` can not write to or flush it, see [`RegisteredWriter`] for
+    /// writers shared with the shutdown registry.
+    #[must_use]
+    pub fn with_on_raw_line(self, f: impl FnMut(&str, &str) + Send + 'static) -> Self {
+        Self {
+            on_raw_line: Some(Box::new(f)),
+            ..self
         }
+    }
 
-        #[test]
-        fn two_line_remainder() {
-            const INPUT: &str = "first\nsecond";
-            const EXPECTED: &str = concatcp!(PREFIX, "first\n", PREFIX, "second");
+    /// Write blank lines with their terminator to the sink instead of
+    /// the writer, so the writer only receives prefixed lines.
+    #[must_use]
+    pub fn with_blank_line_sink(self, sink: impl Write + Send + 'static) -> Self {
+        Self {
+            blank_line_sink: Some(Box::new(sink)),
+            ..self
+        }
+    }
 
-            run(INPUT, PREFIX, EXPECTED);
+    /// Write at most `max_lines_per_sec` lines per second and drop the
+    /// lines exceeding the limit. The next written line is preceded by a
+    /// prefixed `(N lines suppressed)` notice. Seconds are measured with
+    /// the clock set by [`PrefixWriter::with_clock`].
+    #[must_use]
+    pub fn with_rate_limit(self, max_lines_per_sec: u32) -> Self {
+        Self {
+            rate_limit: Some(RateLimit::new(max_lines_per_sec)),
+            ..self
         }
+    }
 
-        #[test]
-        fn two_line_empty_lines() {
-            const INPUT: &str = "first\n\n\n\nsecond\n";
-            const EXPECTED: &str = concatcp!(PREFIX, "first\n", "\n\n\n", PREFIX, "second\n");
+    /// Set the clock used for time based options instead of
+    /// [`Instant::now`], for example to control time in tests.
+    #[must_use]
+    pub fn with_clock(self, clock: impl Fn() -> Instant + Send + 'static) -> Self {
+        Self {
+            clock: Shared::new(Box::new(clock)),
+            ..self
+        }
+    }
 
-            run(INPUT, PREFIX, EXPECTED);
+    /// Start the prefix of every line with the number of lines written so
+    /// far including the line, right-aligned to the width, like `   1 `
+    /// for a width of 4. Empty lines are counted as well.
+    #[must_use]
+    pub fn with_running_count(self, width: usize) -> Self {
+        Self {
+            running_count: Some(width),
+            ..self
         }
+    }
 
-        #[test]
-        fn fuzztest() {
-            for _ in 0..10_000 {
-                let input = give_random_input();
+    /// Start the prefix of every line with the number of words in the
+    /// line, like `[3] ` for `the quick fox`. Words are separated by
+    /// whitespace, by Unicode whitespace with the `unicode` feature and
+    /// by ASCII whitespace without it.
+    #[must_use]
+    pub fn with_word_count_prefix(self, word_count_prefix: bool) -> Self {
+        Self {
+            word_count_prefix,
+            ..self
+        }
+    }
 
-                let mut buffer = Vec::new();
-                let mut writer = PrefixWriter::new(PREFIX.to_owned(), &mut buffer);
+    /// Start the prefix of every line with its index within the current
+    /// write, the format with `{index}` replaced by the index, like
+    /// `"{index}/ "`. The index starts at 1 at every call of
+    /// [`Write::write`] and counts the prefixed lines of that call, so
+    /// lines of one message can be told apart. Lines written by a flush
+    /// continue the index of the last write.
+    #[must_use]
+    pub fn with_intra_write_index(self, format: String) -> Self {
+        Self {
+            intra_write_index: Some(format),
+            ..self
+        }
+    }
 
-                writer.write_all(&input).unwrap();
-                writer.flush().unwrap();
+    /// Discard all output once writing to or flushing the writer failed
+    /// with [`std::io::ErrorKind::BrokenPipe`], for example because the
+    /// output is piped into `head`, instead of returning the error. All
+    /// further writes and flushes succeed without writing anything, see
+    /// [`PrefixWriter::broken_pipe`]. Other errors are returned as usual.
+    #[must_use]
+    pub fn with_silence_broken_pipe(self, silence_broken_pipe: bool) -> Self {
+        Self {
+            silence_broken_pipe,
+            ..self
+        }
+    }
 
-                let got = String::from_utf8_lossy(&buffer);
+    /// Whether lines are prefixed and processed at all, `false` when the
+    /// writer passes everything through as it is because of the prefix
+    /// mode of the `is-terminal` feature.
+    #[must_use]
+    pub fn prefixing_enabled(&self) -> bool {
+        !self.passthrough
+    }
 
-                for line in got.lines() {
-                    assert!(line.starts_with(PREFIX))
-                }
+    /// Whether the output is discarded because the writer failed with a
+    /// broken pipe, see [`PrefixWriter::with_silence_broken_pipe`].
+    #[must_use]
+    pub fn broken_pipe(&self) -> bool {
+        self.broken_pipe
+    }
+
+    /// Report how many lines were dropped while muted with a line when
+    /// unmuting, see [`PrefixWriter::unmute`].
+    #[must_use]
+    pub fn with_mute_summary(self, mute_summary: bool) -> Self {
+        Self {
+            mute_summary,
+            ..self
+        }
+    }
+
+    /// Prefix empty lines as well, with the prefix without trailing
+    /// whitespace, so a `"> "` prefix renders a contiguous Markdown
+    /// blockquote with `>` for empty lines instead of splitting it into
+    /// several blockquotes. The prefix of empty lines is built like that
+    /// of any other line before its trailing whitespace is removed.
+    #[must_use]
+    pub fn with_markdown_blockquote(self, markdown_blockquote: bool) -> Self {
+        Self {
+            markdown_blockquote,
+            ..self
+        }
+    }
+
+    /// Prefix empty lines as well, with the same prefix as any other
+    /// line, like `> ` for quoted email replies. By default empty lines
+    /// stay empty. [`PrefixWriter::with_markdown_blockquote`] takes
+    /// precedence.
+    #[must_use]
+    pub fn with_prefix_empty_lines(self, prefix_empty_lines: bool) -> Self {
+        Self {
+            prefix_empty_lines,
+            ..self
+        }
+    }
+
+    /// Keep at most one empty line in a row and drop the empty lines
+    /// that follow it, like `cat -s`. Dropped lines are counted in
+    /// [`PrefixWriter::stats`].
+    #[must_use]
+    pub fn with_squeeze_empty_lines(self, squeeze_empty_lines: bool) -> Self {
+        Self {
+            squeeze_empty_lines,
+            ..self
+        }
+    }
+
+    /// Terminate an incomplete line when flushing, so the output always
+    /// ends with a terminator. Content written after the flush starts a
+    /// new line.
+    #[must_use]
+    pub fn with_trailing_newline(self, trailing_newline: bool) -> Self {
+        Self {
+            trailing_newline,
+            ..self
+        }
+    }
+
+    /// Start the prefix of every line with the time elapsed since the
+    /// previous prefixed line, like `+0.250s `, to spot slow steps. The
+    /// first line shows `+0.000s`. Time is measured with the clock set by
+    /// [`PrefixWriter::with_clock`].
+    #[must_use]
+    pub fn with_delta_time_prefix(self, delta_time_prefix: bool) -> Self {
+        Self {
+            delta_time_prefix,
+            ..self
+        }
+    }
+
+    /// Set the unit in which all width based options measure text.
+    /// Defaults to [`Unit::Chars`].
+    #[must_use]
+    pub fn with_width_unit(self, width_unit: Unit) -> Self {
+        Self { width_unit, ..self }
+    }
+
+    /// Set a new writer for [`PrefixWriter`].
+    #[must_use]
+    pub fn with_writer(self, writer: W) -> Self {
+        Self { writer, ..self }
+    }
+
+    /// Like [`Write::write`] but returns a [`PrefixWriteError`] with the
+    /// context of the line that failed to be written. When lines were
+    /// written before the failure, the bytes of those lines are reported
+    /// as written and the error is returned by the next call instead. The
+    /// same goes for a line of which only the start was written, the next
+    /// call writes the rest of it before anything else.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the writer.
+    pub fn write_checked(&mut self, buf: &[u8]) -> Result<usize, PrefixWriteError> {
+        if self.passthrough {
+            return self.writer.write(buf).map_err(|err| {
+                PrefixWriteError::new(err, self.lines_written + 1, 0, WriteStage::Payload)
+            });
+        }
+
+        if self.broken_pipe {
+            return Ok(buf.len());
+        }
+
+        // The rest of a partly written line is written and lines that
+        // failed to be synced before are synced first.
+        self.write_unfinished()?;
+        self.sync_if_due()?;
+
+        self.write_index = 0;
+
+        let delimiter = self.input_delimiter;
+        let (complete, incomplete) = split_incomplete(buf, delimiter);
+        let terminator = self.terminator();
+
+        let mut consumed = 0;
+        for line in complete.split_inclusive(|byte| *byte == delimiter) {
+            // The remainder is only dropped once the line was written, so
+            // it survives errors and panics.
+            let (content, original_terminator) =
+                line_content(self.remainder.as_deref(), line, delimiter);
+            let content = self.summarize_binary(line).map_or(content, Into::into);
+
+            let is_last = consumed + line.len() == complete.len() && incomplete.is_empty();
+
+            if let Err(err) = self.emit_line(&content, original_terminator, &terminator, is_last) {
+                if consumed > 0 {
+                    return Ok(consumed);
+                }
+
+                return Err(err);
+            }
+
+            let remainder = self.remainder.take();
+            self.raw_remainder.clear();
+            consumed += line.len();
+            self.input_offset += (remainder.as_ref().map_or(0, String::len) + line.len()) as u64;
+            self.recycle_remainder(remainder);
+
+            if self.unfinished_line.is_some() || self.sync_if_due().is_err() {
+                return Ok(consumed);
+            }
+        }
+
+        if !incomplete.is_empty() {
+            self.remainder
+                .get_or_insert_with(|| {
+                    self.spare_remainder
+                        .as_mut()
+                        .map(std::mem::take)
+                        .unwrap_or_default()
+                })
+                .push_str(&String::from_utf8_lossy(incomplete));
+
+            if self.binary_detection.is_some() {
+                self.raw_remainder.extend_from_slice(incomplete);
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    /// Write the whole string and return how many lines it wrote to the
+    /// writer. An incomplete line at the end is buffered and not counted,
+    /// a line that is completed by the string but was started by earlier
+    /// writes is counted. Lines that are dropped, like muted or sampled
+    /// out lines, and continued lines held for joining are not counted.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the writer.
+    pub fn write_str(&mut self, s: &str) -> std::io::Result<usize> {
+        let lines_written = self.lines_written;
+        self.write_all(s.as_bytes())?;
+
+        Ok(usize::try_from(self.lines_written - lines_written).unwrap_or(usize::MAX))
+    }
+
+    /// Write an out-of-band marker line, like lifecycle messages of a
+    /// supervisor, that stands out from the prefixed lines. A pending
+    /// incomplete line is completed first. Markers use the marker prefix
+    /// and are not transformed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the writer.
+    pub fn write_marker(&mut self, text: &str) -> std::io::Result<()> {
+        let terminator = self.terminator();
+        self.write_remainder(&terminator)?;
+
+        let marker_prefix = match self.marker_prefix {
+            Some(ref marker_prefix) => marker_prefix.clone(),
+            None => format!("{}== ", self.prefix),
+        };
+
+        for line in text.lines() {
+            let mut rendered = self.start_rendered();
+            render_line(&marker_prefix, line, &terminator, &mut rendered);
+
+            self.write_rendered(rendered.as_bytes(), line.len(), terminator.len())?;
+        }
+
+        Ok(())
+    }
+
+    /// Shrink the buffer holding the incomplete line to its current
+    /// length. Useful for long-lived writers that had to buffer a huge
+    /// incomplete line once. Flushing releases the buffer completely.
+    pub fn shrink_to_fit(&mut self) {
+        if let Some(ref mut remainder) = self.remainder {
+            remainder.shrink_to_fit();
+        }
+
+        self.raw_remainder.shrink_to_fit();
+    }
+
+    /// Transform the writer, for example to wrap it in a
+    /// [`std::io::BufWriter`], keeping the configuration and the state.
+    /// An incomplete line is not flushed to the old writer but carried
+    /// over, so it is written to the new writer once it is complete. The
+    /// durability is not carried over, the new writer might not be
+    /// syncable.
+    pub fn map_writer<W2: Write>(self, f: impl FnOnce(W) -> W2) -> PrefixWriter<W2> {
+        PrefixWriter {
+            prefix: self.prefix,
+            writer: f(self.writer),
+
+            message_mode: self.message_mode,
+            message_terminator: self.message_terminator,
+            input_delimiter: self.input_delimiter,
+            output_terminator: self.output_terminator,
+            #[cfg(feature = "encoding")]
+            line_encoder: self.line_encoder,
+            marker_prefix: self.marker_prefix,
+            line_suffix: self.line_suffix,
+            prefix_directive: self.prefix_directive,
+            record_separator: self.record_separator,
+            inplace_remainder: self.inplace_remainder,
+            carriage_return_prefix: self.carriage_return_prefix,
+            zebra: self.zebra,
+            prefix_first_n: self.prefix_first_n,
+            width_unit: self.width_unit,
+            indent_placement: self.indent_placement,
+            normalize_indent: self.normalize_indent,
+            max_prefix_width: self.max_prefix_width,
+            prefix_column: self.prefix_column,
+            wrap_width: self.wrap_width,
+            prefix_overflow_wrap: self.prefix_overflow_wrap,
+            wrap_continuation: self.wrap_continuation,
+            terminal: self.terminal,
+            passthrough: self.passthrough,
+            running_count: self.running_count,
+            word_count_prefix: self.word_count_prefix,
+            intra_write_index: self.intra_write_index,
+            #[cfg(feature = "tracing")]
+            span_indent: self.span_indent,
+            silence_broken_pipe: self.silence_broken_pipe,
+            logfmt: self.logfmt,
+            timestamp_clock: self.timestamp_clock,
+            timestamp_prefix: self.timestamp_prefix,
+            #[cfg(feature = "highlight")]
+            colors: self.colors,
+            mute_summary: self.mute_summary,
+            markdown_blockquote: self.markdown_blockquote,
+            prefix_empty_lines: self.prefix_empty_lines,
+            squeeze_empty_lines: self.squeeze_empty_lines,
+            trailing_newline: self.trailing_newline,
+            delta_time_prefix: self.delta_time_prefix,
+            binary_detection: self.binary_detection,
+            sanitizer: self.sanitizer,
+            join_continuations: self.join_continuations,
+            collapse_continuation_indent: self.collapse_continuation_indent,
+            max_joined_length: self.max_joined_length,
+            paragraph_reflow: self.paragraph_reflow,
+            paragraph_prefixes: self.paragraph_prefixes,
+
+            remainder: self.remainder,
+            spare_remainder: self.spare_remainder,
+            spare_rendered: self.spare_rendered,
+            raw_remainder: self.raw_remainder,
+            remainder_displayed: self.remainder_displayed,
+            held_continuation: self.held_continuation,
+            paragraph: self.paragraph,
+            paragraph_lines: self.paragraph_lines,
+            unfinished_line: self.unfinished_line,
+            after_blank_line: self.after_blank_line,
+            last_line_empty: self.last_line_empty,
+            raw_line_open: self.raw_line_open,
+            lines_written: self.lines_written,
+            write_index: self.write_index,
+            last_prefixed_at: self.last_prefixed_at,
+            broken_pipe: self.broken_pipe,
+            muted: self.muted,
+            muted_lines: self.muted_lines,
+            dropped_lines: self.dropped_lines,
+            write_errors: self.write_errors,
+            last_write: self.last_write,
+            input_offset: self.input_offset,
+            output_offset: self.output_offset,
+            offset_map: self.offset_map,
+            length_histogram: self.length_histogram,
+            prefix_iter: self.prefix_iter,
+            prefix_context: self.prefix_context,
+            on_raw_line: self.on_raw_line,
+            alert: self.alert,
+            map_line: self.map_line,
+            #[cfg(feature = "highlight")]
+            highlighter: self.highlighter,
+            sort: self.sort,
+            blank_line_sink: self.blank_line_sink,
+            rate_limit: self.rate_limit,
+            sampler: self.sampler,
+            verbosity: self.verbosity,
+            records: self.records,
+            resource_monitor: self.resource_monitor,
+            syncer: None,
+            retry_policy: self.retry_policy,
+            sleep: self.sleep,
+            clock: self.clock,
+        }
+    }
+
+    /// Compute what [`Write::write`] would emit for `buf` given the
+    /// current state, without writing anything or changing the writer.
+    /// Incomplete lines are not part of the preview as they would be kept
+    /// as the new remainder. Prefixes of [`PrefixWriter::with_prefix_iter`]
+    /// are only looked at, the next writes still use them. Closures that
+    /// build the output, like the prefix context, the highlighter or a
+    /// [`ClockConfig::Custom`] clock, are called for the previewed lines
+    /// as well, the raw line callback is not. Previewed lines are not
+    /// counted in [`Verbosity::stats`].
+    #[must_use]
+    pub fn preview(&self, buf: &[u8]) -> String {
+        let mut scratch = self.scratch();
+
+        // Writing to a `Vec` does not fail.
+        let _ = scratch.write_all(buf);
+
+        String::from_utf8_lossy(scratch.get_ref()).into_owned()
+    }
+
+    /// Copy of the writer writing to a new buffer for
+    /// [`PrefixWriter::preview`], sharing the closures and the prefix
+    /// iterator with the writer.
+    fn scratch(&self) -> PrefixWriter<Vec<u8>> {
+        PrefixWriter {
+            prefix: self.prefix.clone(),
+            writer: Vec::new(),
+
+            message_mode: self.message_mode,
+            message_terminator: self.message_terminator,
+            input_delimiter: self.input_delimiter,
+            output_terminator: self.output_terminator.clone(),
+            #[cfg(feature = "encoding")]
+            line_encoder: self.line_encoder,
+            marker_prefix: self.marker_prefix.clone(),
+            line_suffix: self.line_suffix.clone(),
+            prefix_directive: self.prefix_directive.clone(),
+            record_separator: self.record_separator.clone(),
+            inplace_remainder: self.inplace_remainder,
+            carriage_return_prefix: self.carriage_return_prefix,
+            zebra: self.zebra.clone(),
+            prefix_first_n: self.prefix_first_n,
+            width_unit: self.width_unit,
+            indent_placement: self.indent_placement,
+            normalize_indent: self.normalize_indent,
+            max_prefix_width: self.max_prefix_width,
+            prefix_column: self.prefix_column,
+            wrap_width: self.wrap_width,
+            prefix_overflow_wrap: self.prefix_overflow_wrap,
+            wrap_continuation: self.wrap_continuation,
+            terminal: self.terminal,
+            passthrough: self.passthrough,
+            running_count: self.running_count,
+            word_count_prefix: self.word_count_prefix,
+            intra_write_index: self.intra_write_index.clone(),
+            #[cfg(feature = "tracing")]
+            span_indent: self.span_indent.clone(),
+            silence_broken_pipe: self.silence_broken_pipe,
+            logfmt: self.logfmt.clone(),
+            timestamp_clock: self.timestamp_clock.clone(),
+            timestamp_prefix: self.timestamp_prefix,
+            #[cfg(feature = "highlight")]
+            colors: self.colors,
+            mute_summary: self.mute_summary,
+            markdown_blockquote: self.markdown_blockquote,
+            prefix_empty_lines: self.prefix_empty_lines,
+            squeeze_empty_lines: self.squeeze_empty_lines,
+            trailing_newline: self.trailing_newline,
+            delta_time_prefix: self.delta_time_prefix,
+            binary_detection: self.binary_detection,
+            sanitizer: self.sanitizer.clone(),
+            join_continuations: self.join_continuations,
+            collapse_continuation_indent: self.collapse_continuation_indent,
+            max_joined_length: self.max_joined_length,
+            paragraph_reflow: self.paragraph_reflow,
+            paragraph_prefixes: self.paragraph_prefixes.clone(),
+
+            remainder: self.remainder.clone(),
+            spare_remainder: None,
+            spare_rendered: None,
+            raw_remainder: self.raw_remainder.clone(),
+            remainder_displayed: self.remainder_displayed,
+            held_continuation: self.held_continuation.clone(),
+            paragraph: self.paragraph.clone(),
+            paragraph_lines: self.paragraph_lines.clone(),
+            unfinished_line: self.unfinished_line.clone(),
+            after_blank_line: self.after_blank_line,
+            last_line_empty: self.last_line_empty,
+            raw_line_open: self.raw_line_open,
+            lines_written: self.lines_written,
+            write_index: self.write_index,
+            last_prefixed_at: self.last_prefixed_at,
+            broken_pipe: self.broken_pipe,
+            muted: self.muted,
+            muted_lines: self.muted_lines,
+            dropped_lines: self.dropped_lines,
+            write_errors: self.write_errors,
+            last_write: self.last_write,
+            input_offset: self.input_offset,
+            output_offset: self.output_offset,
+            offset_map: self.offset_map.clone(),
+            length_histogram: self.length_histogram.clone(),
+            prefix_iter: self.prefix_iter.as_ref().map(PrefixIter::preview),
+            prefix_context: self.prefix_context.clone(),
+            on_raw_line: None,
+            alert: self.alert.clone(),
+            map_line: self.map_line.clone(),
+            #[cfg(feature = "highlight")]
+            highlighter: self.highlighter.clone(),
+            sort: self.sort.clone(),
+            blank_line_sink: self
+                .blank_line_sink
+                .as_ref()
+                .map(|_| Box::new(std::io::sink()) as Box<dyn Write + Send>),
+            rate_limit: self.rate_limit.clone(),
+            sampler: self.sampler.clone(),
+            verbosity: self.verbosity.as_ref().map(KeyVerbosity::uncounted),
+            records: self.records.clone(),
+            resource_monitor: self.resource_monitor.clone(),
+            syncer: None,
+            retry_policy: None,
+            sleep: Box::new(std::thread::sleep),
+            clock: self.clock.clone(),
+        }
+    }
+
+    /// Describe the effective configuration for logging or debugging,
+    /// like `prefix "web: ", terminator "\n", message mode`. Lists the
+    /// prefix, the line terminator and every option that is enabled.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        let mut options = vec![
+            format!("prefix {:?}", self.prefix),
+            format!("terminator {:?}", self.terminator()),
+        ];
+
+        if self.input_delimiter != b'\n' {
+            options.push(format!(
+                "input delimiter {:?}",
+                char::from(self.input_delimiter)
+            ));
+        }
+        if self.message_mode {
+            options.push("message mode".to_owned());
+        }
+        #[cfg(feature = "encoding")]
+        if let Some(encoding) = self.line_encoder {
+            options.push(format!("{encoding:?} encoded lines"));
+        }
+        if let Some(ref marker_prefix) = self.marker_prefix {
+            options.push(format!("marker prefix {marker_prefix:?}"));
+        }
+        if let Some(ref line_suffix) = self.line_suffix {
+            options.push(format!("line suffix {line_suffix:?}"));
+        }
+        if let Some(ref marker) = self.prefix_directive {
+            options.push(format!("prefix directive {marker:?}"));
+        }
+        if let Some(ref separator) = self.record_separator {
+            options.push(format!(
+                "record separator {:?}",
+                String::from_utf8_lossy(separator)
+            ));
+        }
+        if self.inplace_remainder {
+            options.push("in-place remainder".to_owned());
+        }
+        if self.carriage_return_prefix {
+            options.push("carriage return prefix".to_owned());
+        }
+        if self.zebra.is_some() {
+            options.push("zebra".to_owned());
+        }
+        if let Some(first_n) = self.prefix_first_n {
+            options.push(format!("prefix first {first_n} lines"));
+        }
+        if self.passthrough {
+            options.push("passthrough".to_owned());
+        }
+        if let Some(terminal) = self.terminal {
+            options.push(if terminal {
+                "tty aware on a terminal".to_owned()
+            } else {
+                "tty aware without a terminal".to_owned()
+            });
+        }
+        if let Some(ref fields) = self.logfmt {
+            let keys = fields.iter().map(|(key, _)| key.as_str());
+            options.push(format!("logfmt of {}", keys.collect::<Vec<_>>().join(" ")));
+        }
+        if self.markdown_blockquote {
+            options.push("markdown blockquote".to_owned());
+        }
+        if self.delta_time_prefix {
+            options.push("delta time prefix".to_owned());
+        }
+        if self.mute_summary {
+            options.push("mute summary".to_owned());
+        }
+        if self.silence_broken_pipe {
+            options.push("silenced broken pipe".to_owned());
+        }
+        if let Some(width) = self.running_count {
+            options.push(format!("running count of width {width}"));
+        }
+        if self.word_count_prefix {
+            options.push("word count prefix".to_owned());
+        }
+        if let Some(ref format) = self.intra_write_index {
+            options.push(format!("intra write index {format:?}"));
+        }
+        #[cfg(feature = "tracing")]
+        if let Some(ref indent_unit) = self.span_indent {
+            options.push(format!("span indent {indent_unit:?}"));
+        }
+        self.describe_layout(&mut options);
+        self.describe_line_handling(&mut options);
+        self.describe_behaviors(&mut options);
+
+        options.join(", ")
+    }
+
+    /// Describe the enabled options that change how prefixes are measured
+    /// and lines are indented, for [`PrefixWriter::describe`].
+    fn describe_layout(&self, options: &mut Vec<String>) {
+        if self.width_unit != Unit::default() {
+            options.push(format!("widths in {:?}", self.width_unit));
+        }
+        if let Some((width, ellipsis)) = self.max_prefix_width {
+            options.push(format!(
+                "prefixes shortened to {width} with {ellipsis:?} ellipsis"
+            ));
+        }
+        if let Some(column) = self.prefix_column {
+            options.push(format!("prefixes padded to column {column}"));
+        }
+        if self.indent_placement != IndentPlacement::default() {
+            options.push(format!("indent placement {:?}", self.indent_placement));
+        }
+        if let Some(tab_width) = self.normalize_indent {
+            options.push(format!("indentation normalized with tab width {tab_width}"));
+        }
+    }
+
+    /// Describe the enabled options that change which lines are written
+    /// and how they are split, for [`PrefixWriter::describe`].
+    fn describe_line_handling(&self, options: &mut Vec<String>) {
+        if self.prefix_empty_lines {
+            options.push("prefixed empty lines".to_owned());
+        }
+        if self.squeeze_empty_lines {
+            options.push("squeezed empty lines".to_owned());
+        }
+        if self.trailing_newline {
+            options.push("trailing newline".to_owned());
+        }
+        if self.join_continuations {
+            options.push(if self.collapse_continuation_indent {
+                format!(
+                    "joined continuations up to {} bytes without indent",
+                    self.max_joined_length
+                )
+            } else {
+                format!(
+                    "joined continuations up to {} bytes",
+                    self.max_joined_length
+                )
+            });
+        }
+        if self.binary_detection.is_some() {
+            options.push("binary detection".to_owned());
+        }
+        if let Some(ref sanitizer) = self.sanitizer {
+            let sanitization = sanitizer.sanitization();
+            options.push(match (sanitization.visible, sanitization.colors) {
+                (false, false) => "sanitization".to_owned(),
+                (true, false) => "visible sanitization".to_owned(),
+                (false, true) => "sanitization keeping colors".to_owned(),
+                (true, true) => "visible sanitization keeping colors".to_owned(),
+            });
+        }
+        if let Some(ref sampler) = self.sampler {
+            options.push(sampler.describe());
+        }
+        if let Some(ref verbosity) = self.verbosity {
+            options.push(format!("verbosity of key `{}`", verbosity.key()));
+        }
+        if let Some(ref records) = self.records {
+            options.push(if records.is_atomic() {
+                "atomic records".to_owned()
+            } else {
+                "records".to_owned()
+            });
+        }
+        if let Some(ref sort) = self.sort {
+            options.push(if sort.is_reverse() {
+                "reversed".to_owned()
+            } else {
+                "sorted".to_owned()
+            });
+        }
+        if let Some(width) = self.paragraph_reflow {
+            options.push(format!("paragraphs reflowed to {width}"));
+        }
+        if let Some((ref first_of_paragraph, ref continuation)) = self.paragraph_prefixes {
+            options.push(format!(
+                "paragraph prefixes {first_of_paragraph:?} and {continuation:?}"
+            ));
+        }
+        if let Some(width) = self.wrap_width {
+            options.push(format!("wrap at {width}"));
+        }
+        if let Some(width) = self.prefix_overflow_wrap {
+            options.push(format!("prefix on its own line beyond {width}"));
+        }
+        if let Some(continuation) = self.wrap_continuation {
+            options.push(format!("wrap continuation {continuation:?}"));
+        }
+    }
+
+    /// Describe the enabled options that hook into writing, like
+    /// callbacks and policies, for [`PrefixWriter::describe`].
+    fn describe_behaviors(&self, options: &mut Vec<String>) {
+        if self.prefix_iter.is_some() {
+            options.push("prefix iterator".to_owned());
+        }
+        if self.on_raw_line.is_some() {
+            options.push("raw line callback".to_owned());
+        }
+        if let Some(ref alert) = self.alert {
+            options.push(if alert.fired() {
+                "alert fired".to_owned()
+            } else {
+                "alert".to_owned()
+            });
+        }
+        if self.map_line.is_some() {
+            options.push("line transformation".to_owned());
+        }
+        if self.prefix_context.is_some() {
+            options.push("prefix context".to_owned());
+        }
+        #[cfg(feature = "highlight")]
+        if self.highlighter.is_some() {
+            options.push(if self.colors {
+                "highlighting".to_owned()
+            } else {
+                "highlighting without colors".to_owned()
+            });
+        }
+        if self.blank_line_sink.is_some() {
+            options.push("blank line sink".to_owned());
+        }
+        if self.offset_map.is_some() {
+            options.push("offset map".to_owned());
+        }
+        if let Some((ref boundaries, _)) = self.length_histogram {
+            options.push(format!("length histogram at {boundaries:?}"));
+        }
+        let timestamp_clock = self.timestamp_clock.lock();
+        if !matches!(*timestamp_clock, ClockConfig::Utc) {
+            options.push(format!("timestamp clock {:?}", *timestamp_clock));
+        }
+        if self.timestamp_prefix {
+            options.push("timestamp prefix".to_owned());
+        }
+        if let Some(ref syncer) = self.syncer {
+            options.push(format!("durability {:?}", syncer.durability()));
+        }
+        if let Some(retry_policy) = self.retry_policy {
+            options.push(format!(
+                "retry policy of {} attempts",
+                retry_policy.max_attempts()
+            ));
+        }
+        if let Some(ref resource_monitor) = self.resource_monitor {
+            options.push(format!(
+                "resource usage every {:?}",
+                resource_monitor.interval()
+            ));
+        }
+        if let Some(ref rate_limit) = self.rate_limit {
+            options.push(format!(
+                "rate limit {} lines per second",
+                rate_limit.max_lines()
+            ));
+        }
+    }
+
+    /// Apply the configured transformations to the content of a line.
+    fn transform_line<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let line: Cow<'a, str> = match self.map_line {
+            Some(ref map_line) => strip_terminator(map_line.lock()(line)).into(),
+            None => line.into(),
+        };
+
+        let line = match self.normalize_indent {
+            Some(tab_width) => match indent::normalize_indent(&line, tab_width) {
+                Cow::Borrowed(_) => line,
+                Cow::Owned(normalized) => normalized.into(),
+            },
+            None => line,
+        };
+
+        #[cfg(feature = "encoding")]
+        if let Some(encoding) = self.line_encoder {
+            return encoding.encode(&line).into();
+        }
+
+        line
+    }
+
+    /// Write the incomplete line of previous writes, if there is one,
+    /// and a held continued line with the terminator. The remainder is
+    /// kept when writing fails.
+    fn write_remainder(&mut self, terminator: &str) -> Result<(), PrefixWriteError> {
+        if let Some(remainder) = self.remainder.clone() {
+            let content = self
+                .summarize_binary(&[])
+                .unwrap_or_else(|| remainder.clone());
+            self.emit_line(&content, "", terminator, true)?;
+
+            let written = self.remainder.take();
+            self.raw_remainder.clear();
+            self.recycle_remainder(written);
+            self.input_offset += remainder.len() as u64;
+        }
+
+        self.release_continuation(terminator)?;
+        self.write_paragraph()?;
+        self.write_record_group()
+    }
+
+    /// Sanitize the line, join it with continued lines and assign it to a
+    /// record, then transform, prefix and write it with the terminator.
+    /// `original_terminator` is the terminator the line had in the input,
+    /// `is_last` whether it is the last line of the write.
+    fn emit_line(
+        &mut self,
+        content: &str,
+        original_terminator: &str,
+        terminator: &str,
+        is_last: bool,
+    ) -> Result<(), PrefixWriteError> {
+        let Some(ref current) = self.sanitizer else {
+            return self.emit_sanitized_line(content, original_terminator, terminator, is_last);
+        };
+
+        // The sanitizer only advances once the line is written, so a line
+        // that is written again is sanitized the same way.
+        let mut advanced = current.clone();
+        let sanitized = advanced.sanitize(content, !original_terminator.is_empty());
+
+        self.emit_sanitized_line(&sanitized, original_terminator, terminator, is_last)?;
+        self.sanitizer = Some(advanced);
+
+        Ok(())
+    }
+
+    /// Emit the line like [`PrefixWriter::emit_line`] does after it was
+    /// sanitized.
+    fn emit_sanitized_line(
+        &mut self,
+        content: &str,
+        original_terminator: &str,
+        terminator: &str,
+        is_last: bool,
+    ) -> Result<(), PrefixWriteError> {
+        if self.take_prefix_directive(content) {
+            return Ok(());
+        }
+
+        let joined;
+        let content = if self.join_continuations {
+            let Some(line) = self.join_continuation(content, original_terminator, terminator)?
+            else {
+                return Ok(());
+            };
+
+            joined = line;
+            &joined
+        } else {
+            content
+        };
+
+        self.detect_record(content)?;
+
+        if self.paragraph_reflow.is_some() {
+            return self.reflow_line(content, original_terminator, terminator, is_last);
+        }
+
+        self.emit_logical_line(content, original_terminator, terminator, is_last)
+    }
+
+    /// Transform, prefix and write the content of a single line with the
+    /// terminator, see [`PrefixWriter::emit_line`].
+    pub(crate) fn emit_logical_line(
+        &mut self,
+        content: &str,
+        original_terminator: &str,
+        terminator: &str,
+        is_last: bool,
+    ) -> Result<(), PrefixWriteError> {
+        if !self.admit_line(content, original_terminator, terminator)? {
+            return Ok(());
+        }
+
+        let empty = is_empty_content(content.as_bytes());
+        if empty && self.take_empty_line(content, original_terminator, terminator)? {
+            return Ok(());
+        }
+
+        let transformed = self.transform_line(content);
+        let mut rendered = self.take_rendered();
+        let offsets = self.line_offsets(rendered.len());
+
+        if let Some(ref fields) = self.logfmt {
+            let record = logfmt::render(
+                fields,
+                &transformed,
+                self.lines_written + 1,
+                &mut self.timestamp_clock.lock(),
+            );
+
+            rendered.push_str(&record);
+            rendered.push_str(terminator);
+
+            self.write_rendered(rendered.as_bytes(), record.len(), terminator.len())?;
+            self.recycle_rendered(rendered);
+            self.record_offsets(offsets);
+            self.line_written(content, original_terminator);
+
+            return Ok(());
+        }
+        // Lines that are wrapped are not highlighted, wrapping would cut
+        // through the escape sequences.
+        #[cfg(feature = "highlight")]
+        let transformed = match self.highlight_unwrapped(&transformed) {
+            Some(highlighted) => highlighted.into(),
+            None => transformed,
+        };
+
+        let carriage_return_prefix = self.carriage_return_prefix;
+        let indent_placement = self.indent_placement;
+        let (wrap_width, wrap_continuation, width_unit) =
+            (self.wrap_width, self.wrap_continuation, self.width_unit);
+        let prefix_overflow_wrap = self.prefix_overflow_wrap;
+
+        let style = self.zebra_style().filter(|_| !empty);
+
+        let prefixed = self.prefix_next_line() && (!empty || self.prefixes_empty_lines());
+        let suffix = self.line_suffix.clone().filter(|_| prefixed && !empty);
+        let blockquote = empty && self.markdown_blockquote;
+        let prefix = if !prefixed {
+            "".into()
+        } else if blockquote {
+            self.next_prefix(content, is_last)
+                .trim_end()
+                .to_owned()
+                .into()
+        } else {
+            self.next_prefix(content, is_last)
+        };
+
+        let transformed = if carriage_return_prefix {
+            prefix_carriage_returns(transformed, &prefix)
+        } else {
+            transformed
+        };
+
+        let transformed = match wrap_width {
+            Some(width) => wrap::wrap(
+                &transformed,
+                width_unit,
+                width,
+                wrap_continuation,
+                &format!("{terminator}{prefix}"),
+            )
+            .into_owned()
+            .into(),
+            None => transformed,
+        };
+
+        if let Some(ref style) = style {
+            rendered.push_str(style);
+        }
+
+        let (indentation, transformed_content) = indent_placement.split(&transformed);
+        rendered.push_str(indentation);
+
+        let prefix = wrap::overflow_prefix(
+            prefix,
+            transformed_content,
+            width_unit,
+            prefix_overflow_wrap,
+            terminator,
+        );
+
+        // render_line leaves empty lines unprefixed.
+        if empty {
+            rendered.push_str(&prefix);
+        }
+        render_line(&prefix, transformed_content, "", &mut rendered);
+        if let Some(ref suffix) = suffix {
+            rendered.push_str(suffix);
+        }
+        let payload_end = rendered.len();
+
+        if style.is_some() {
+            rendered.push_str(RESET_STYLE);
+        }
+
+        rendered.push_str(terminator);
+
+        self.write_rendered(
+            rendered.as_bytes(),
+            transformed.len(),
+            rendered.len() - payload_end,
+        )?;
+        self.recycle_rendered(rendered);
+        self.record_offsets(offsets);
+
+        self.line_written(content, original_terminator);
+
+        Ok(())
+    }
+
+    /// Whether the line is to be written now, and not dropped because the
+    /// writer is muted or the line is below the verbosity level, buffered
+    /// for sorting or left out by the sampling or the rate limit.
+    fn admit_line(
+        &mut self,
+        content: &str,
+        original_terminator: &str,
+        terminator: &str,
+    ) -> Result<bool, PrefixWriteError> {
+        if self.muted {
+            self.muted_lines += 1;
+            self.dropped_lines += 1;
+            return Ok(false);
+        }
+
+        if !self.verbose_enough(content) {
+            self.dropped_lines += 1;
+            return Ok(false);
+        }
+
+        if let Some(ref mut sort) = self.sort {
+            sort.push(content, original_terminator);
+            return Ok(false);
+        }
+
+        let empty = is_empty_content(content.as_bytes());
+        if self.squeeze_empty_lines && empty && self.last_line_empty {
+            self.dropped_lines += 1;
+            return Ok(false);
+        }
+
+        if !self.sample(content, terminator)? {
+            self.dropped_lines += 1;
+            return Ok(false);
+        }
+
+        if let Some(ref mut rate_limit) = self.rate_limit {
+            let Some(suppressed) = rate_limit.admit(self.clock.lock()()) else {
+                self.dropped_lines += 1;
+                return Ok(false);
+            };
+
+            if suppressed > 0 {
+                self.write_suppressed_notice(suppressed, terminator)?;
+            }
+        }
+
+        self.alert(content)?;
+
+        Ok(true)
+    }
+
+    /// Write the notice about lines dropped by the rate limit.
+    fn write_suppressed_notice(
+        &mut self,
+        suppressed: u64,
+        terminator: &str,
+    ) -> Result<(), PrefixWriteError> {
+        let notice = format!("({suppressed} lines suppressed)");
+
+        let mut rendered = self.start_rendered();
+        render_line(&self.prefix, &notice, terminator, &mut rendered);
+
+        let result = self.write_rendered(rendered.as_bytes(), notice.len(), terminator.len());
+
+        if result.is_err() {
+            if let Some(ref mut rate_limit) = self.rate_limit {
+                rate_limit.restore_suppressed(suppressed);
+            }
+        }
+
+        result
+    }
+
+    /// Hand a line that was written to the raw line callback, remember
+    /// whether it was blank and count its length.
+    fn line_written(&mut self, content: &str, original_terminator: &str) {
+        self.after_blank_line = content.trim().is_empty();
+        self.last_line_empty = is_empty_content(content.as_bytes());
+        self.count_length(content.len());
+
+        if let Some(ref mut on_raw_line) = self.on_raw_line {
+            on_raw_line(content, original_terminator);
+        }
+    }
+
+    /// Whether empty lines are prefixed like the other lines.
+    fn prefixes_empty_lines(&self) -> bool {
+        self.markdown_blockquote || self.prefix_empty_lines
+    }
+
+    /// Whether the next line is still within the lines that are prefixed.
+    fn prefix_next_line(&self) -> bool {
+        self.terminal != Some(false)
+            && self
+                .prefix_first_n
+                .is_none_or(|first_n| self.lines_written < first_n)
+    }
+
+    /// Start rendering the next line, which erases the incomplete line
+    /// written by the last flush when it is to be overwritten and ends a
+    /// line that raw output left open.
+    pub(crate) fn start_rendered(&self) -> String {
+        let mut rendered = String::new();
+        self.push_line_start(&mut rendered);
+
+        rendered
+    }
+
+    /// Push what has to be written before the next line.
+    pub(crate) fn push_line_start(&self, rendered: &mut String) {
+        if self.raw_line_open {
+            rendered.push('\n');
+        }
+
+        if self.remainder_displayed {
+            rendered.push_str(ERASE_LINE);
+        }
+    }
+
+    /// Escape sequence starting the background of the next line when
+    /// zebra striping is enabled.
+    fn zebra_style(&self) -> Option<String> {
+        let (even, odd) = self.zebra.as_ref()?;
+
+        let code = if self.lines_written.is_multiple_of(2) {
+            even
+        } else {
+            odd
+        };
+
+        Some(format!("\x1b[{code}m"))
+    }
+
+    /// Terminator that is written after every complete line.
+    fn terminator(&self) -> Cow<'static, str> {
+        if self.message_mode && !self.message_terminator {
+            "".into()
+        } else {
+            self.output_terminator.clone()
+        }
+    }
+
+    /// Write a rendered line to the writer. In message mode the line has
+    /// to be written with a single call. Errors carry the part of the
+    /// line that failed, which is why the length of the payload and the
+    /// terminator at the end of the line are needed.
+    pub(crate) fn write_rendered(
+        &mut self,
+        rendered: &[u8],
+        payload_len: usize,
+        terminator_len: usize,
+    ) -> Result<(), PrefixWriteError> {
+        if self.broken_pipe {
+            return Ok(());
+        }
+
+        match self.records {
+            Some(ref records) if records.is_atomic() => {
+                if records.is_full() {
+                    self.write_record_group()?;
+                }
+
+                if let Some(ref mut records) = self.records {
+                    records.collect(rendered);
+                }
+            }
+
+            _ => self.write_rendered_bytes(rendered, payload_len, terminator_len)?,
+        }
+
+        self.lines_written += 1;
+        self.remainder_displayed = false;
+        self.raw_line_open = false;
+
+        Ok(())
+    }
+
+    /// Write rendered lines to the writer, see
+    /// [`PrefixWriter::write_rendered`]. The rest of a line that was only
+    /// partly written before is written first. When only the start of the
+    /// lines can be written outside of message mode, the rest is kept
+    /// and written by the next write or flush, which also returns the
+    /// error, so nothing is written twice.
+    pub(crate) fn write_rendered_bytes(
+        &mut self,
+        rendered: &[u8],
+        payload_len: usize,
+        terminator_len: usize,
+    ) -> Result<(), PrefixWriteError> {
+        if self.broken_pipe {
+            return Ok(());
+        }
+
+        self.write_unfinished()?;
+
+        let mut unfinished = UnfinishedLine {
+            rendered: Vec::new(),
+            written: 0,
+            payload_len,
+            terminator_len,
+            line: self.lines_written + 1,
+        };
+
+        match self.write_rendered_from(rendered, &mut unfinished) {
+            Err(_) if !self.message_mode && unfinished.written > 0 => {
+                unfinished.rendered = rendered.to_vec();
+                self.unfinished_line = Some(unfinished);
+
+                Ok(())
+            }
+
+            result => result,
+        }
+    }
+
+    /// Write the rest of the line that was only partly written, see
+    /// [`PrefixWriter::write_rendered_bytes`].
+    pub(crate) fn write_unfinished(&mut self) -> Result<(), PrefixWriteError> {
+        let Some(mut unfinished) = self.unfinished_line.take() else {
+            return Ok(());
+        };
+
+        let rendered = std::mem::take(&mut unfinished.rendered);
+        let result = self.write_rendered_from(&rendered, &mut unfinished);
+
+        if result.is_err() && !self.broken_pipe {
+            unfinished.rendered = rendered;
+            self.unfinished_line = Some(unfinished);
+        }
+
+        result
+    }
+
+    /// Write the rendered line from the first byte that was not written
+    /// yet, counting the written bytes in `progress`.
+    fn write_rendered_from(
+        &mut self,
+        rendered: &[u8],
+        progress: &mut UnfinishedLine,
+    ) -> Result<(), PrefixWriteError> {
+        let mut failed = 0;
+
+        // An empty message is still a message.
+        let mut pending = self.message_mode;
+
+        while pending || progress.written < rendered.len() {
+            pending = false;
+            let written = progress.written;
+
+            let result = match self.writer.write(&rendered[written..]) {
+                Ok(0) if !rendered.is_empty() => Err(std::io::Error::new(
+                    std::io::ErrorKind::WriteZero,
+                    "failed to write whole line",
+                )),
+
+                Ok(count) if self.message_mode && count < rendered.len() => {
+                    progress.written = count;
+                    self.output_offset += count as u64;
+
+                    Err(std::io::Error::other(format!(
+                        "short write of message, wrote {count} of {} bytes",
+                        rendered.len()
+                    )))
+                }
+
+                Ok(count) => {
+                    progress.written += count;
+                    self.output_offset += count as u64;
+                    self.last_write = Some(self.clock.lock()());
+                    failed = 0;
+                    Ok(())
+                }
+
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {
+                    pending = self.message_mode;
+                    Ok(())
+                }
+
+                Err(err) => {
+                    failed += 1;
+
+                    match self
+                        .retry_policy
+                        .and_then(|retry_policy| retry_policy.backoff(&err, failed))
+                    {
+                        Some(backoff) => {
+                            (self.sleep)(backoff);
+                            pending = self.message_mode;
+                            Ok(())
+                        }
+
+                        None => Err(err),
+                    }
+                }
+            };
+
+            if let Err(err) = result {
+                if self.silence_broken_pipe && err.kind() == std::io::ErrorKind::BrokenPipe {
+                    self.broken_pipe = true;
+                    self.remainder = None;
+                    self.raw_remainder.clear();
+                    return Ok(());
+                }
+
+                self.write_errors += 1;
+
+                let written = progress.written;
+                let terminator_start = rendered.len() - progress.terminator_len;
+                let payload_start = terminator_start - progress.payload_len;
+
+                let stage = if written < payload_start {
+                    WriteStage::Prefix
+                } else if written < terminator_start {
+                    WriteStage::Payload
+                } else {
+                    WriteStage::Terminator
+                };
+
+                return Err(PrefixWriteError::new(err, progress.line, written, stage));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flush the writer, discarding all further output instead of
+    /// returning the error if it failed with a silenced broken pipe.
+    fn flush_writer(&mut self) -> std::io::Result<()> {
+        match self.writer.flush() {
+            Err(err)
+                if self.silence_broken_pipe && err.kind() == std::io::ErrorKind::BrokenPipe =>
+            {
+                self.broken_pipe = true;
+                self.remainder = None;
+                self.raw_remainder.clear();
+                self.unfinished_line = None;
+
+                Ok(())
+            }
+
+            result => result,
+        }
+    }
+
+    /// Take the prefix of an empty line that is not prefixed from the
+    /// prefix iterator and write the line to the blank line sink, if
+    /// there is one. Returns whether the line was written to the sink.
+    fn take_empty_line(
+        &mut self,
+        content: &str,
+        original_terminator: &str,
+        terminator: &str,
+    ) -> Result<bool, PrefixWriteError> {
+        let prefixed = self.blank_line_sink.is_none()
+            && self.prefixes_empty_lines()
+            && self.prefix_next_line();
+
+        if !prefixed {
+            self.skip_iter_prefix();
+        }
+
+        let Some(ref mut blank_line_sink) = self.blank_line_sink else {
+            return Ok(false);
+        };
+
+        blank_line_sink
+            .write_all(format!("{content}{terminator}").as_bytes())
+            .map_err(|err| {
+                PrefixWriteError::new(err, self.lines_written + 1, 0, WriteStage::Payload)
+            })?;
+
+        self.line_written(content, original_terminator);
+
+        Ok(true)
+    }
+
+    /// Take the prefix of an empty line, which is not prefixed, from the
+    /// prefix iterator, so the prefixes stay paired with the lines.
+    fn skip_iter_prefix(&mut self) {
+        let continuation = self
+            .records
+            .as_ref()
+            .and_then(Records::continuation_prefix)
+            .is_some();
+
+        if self.prefix_context.is_some() || continuation {
+            return;
+        }
+
+        if self.prefix_iter.as_mut().and_then(Iterator::next).is_none() {
+            self.prefix_iter = None;
+        }
+    }
+
+    /// Get the prefix for the next emitted line.
+    pub(crate) fn next_prefix(&mut self, content: &str, is_last: bool) -> Cow<'_, str> {
+        let (max_prefix_width, prefix_column) = (self.max_prefix_width, self.prefix_column);
+        let prefix = self.render_prefix(content, is_last);
+
+        if max_prefix_width.is_none() && prefix_column.is_none() {
+            return prefix;
+        }
+
+        // The lines of a banner are written as they are, only its last
+        // line prefixes the line.
+        let (banner, line_prefix) = prefix
+            .rfind('\n')
+            .map_or(("", &*prefix), |index| prefix.split_at(index + 1));
+
+        let mut line_prefix = Cow::Borrowed(line_prefix);
+        if let Some((width, ellipsis)) = max_prefix_width {
+            line_prefix = prefix_width::shorten(&line_prefix, width, ellipsis).into();
+        }
+        if let Some(column) = prefix_column {
+            line_prefix = prefix_width::pad_to_column(&line_prefix, column).into();
+        }
+
+        format!("{banner}{line_prefix}").into()
+    }
+
+    /// Build the prefix of the next line from the prefix options, before
+    /// it is shortened.
+    fn render_prefix(&mut self, content: &str, is_last: bool) -> Cow<'_, str> {
+        self.write_index += 1;
+
+        if let Some(ref prefix_context) = self.prefix_context {
+            let mut prefix = String::new();
+            prefix_context.lock().render(
+                &LineContext {
+                    number: self.lines_written + 1,
+                    index_in_write: self.write_index,
+                    byte_offset: self.input_offset,
+                    content,
+                    is_last,
+                },
+                &mut prefix,
+            );
+
+            return prefix.into();
+        }
+
+        let continuation_prefix = self.records.as_ref().and_then(Records::continuation_prefix);
+
+        let prefix: Cow<'_, str> = if let Some(prefix) = continuation_prefix {
+            prefix.into()
+        } else if let Some(prefix) = self.prefix_iter.as_mut().and_then(Iterator::next) {
+            prefix.into()
+        } else {
+            self.prefix_iter = None;
+
+            match self.paragraph_prefixes {
+                Some((ref first_of_paragraph, _)) if self.after_blank_line => {
+                    first_of_paragraph.as_str().into()
+                }
+                Some((_, ref continuation)) => continuation.as_str().into(),
+                None => self.prefix.as_str().into(),
+            }
+        };
+
+        #[cfg(feature = "tracing")]
+        let prefix = match self.span_indent {
+            Some(ref indent_unit) => {
+                format!("{prefix}{}", indent_unit.repeat(span_indent::depth())).into()
+            }
+            None => prefix,
+        };
+
+        let prefix = match self.resource_monitor {
+            Some(ref mut resource_monitor) => {
+                format!("{} {prefix}", resource_monitor.render(self.clock.lock()())).into()
+            }
+            None => prefix,
+        };
+
+        let prefix = if self.delta_time_prefix {
+            let now = self.clock.lock()();
+            let delta = self
+                .last_prefixed_at
+                .map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+            self.last_prefixed_at = Some(now);
+
+            format!("+{:.3}s {prefix}", delta.as_secs_f64()).into()
+        } else {
+            prefix
+        };
+
+        let prefix = if self.timestamp_prefix {
+            format!("{} {prefix}", self.timestamp_clock.lock().now()).into()
+        } else {
+            prefix
+        };
+
+        let prefix = if self.word_count_prefix {
+            format!("[{}] {prefix}", word_count(content)).into()
+        } else {
+            prefix
+        };
+
+        let prefix = match self.intra_write_index {
+            Some(ref format) => {
+                let index = format.replace(INDEX_PLACEHOLDER, &self.write_index.to_string());
+                format!("{index}{prefix}").into()
+            }
+            None => prefix,
+        };
+
+        match self.running_count {
+            Some(width) => format!("{:>width$} {prefix}", self.lines_written + 1).into(),
+            None => prefix,
+        }
+    }
+
+    /// Width of the text in the unit set by
+    /// [`PrefixWriter::with_width_unit`], the way width based options
+    /// measure it.
+    #[must_use]
+    pub fn width(&self, text: &str) -> usize {
+        self.width_unit.measure(text)
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get a mutable reference to the underlying writer. Writing
+    /// directly to it bypasses the prefixing.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use const_format::concatcp;
+    use pretty_assertions::assert_eq;
+    use rand::Rng;
+    use std::{
+        io::Write,
+        sync::{
+            Arc,
+            Mutex,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    use super::{
+        prefix_all,
+        CheckedWriter,
+        Classifier,
+        PrefixEllipsis,
+        PrefixWriteError,
+        PrefixWriter,
+        Sampling,
+        Sanitization,
+        Unit,
+        Verbosity,
+        VerbositySpec,
+        VerbosityStats,
+        WriteStage,
+    };
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Records every call to [`Write::write`] separately. Writes at most
+    /// `limit` bytes per call.
+    #[derive(Debug, Default)]
+    struct ChunkWriter {
+        chunks: Vec<String>,
+        limit: Option<usize>,
+    }
+
+    impl Write for ChunkWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let written = self.limit.map_or(buf.len(), |limit| buf.len().min(limit));
+            self.chunks
+                .push(String::from_utf8_lossy(&buf[..written]).into_owned());
+
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Fails every write with `Ok(0)` while `full` is set.
+    #[derive(Debug, Default)]
+    struct FullWriter {
+        full: bool,
+        data: Vec<u8>,
+    }
+
+    impl Write for FullWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.full {
+                return Ok(0);
+            }
+
+            self.data.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Accepts every write and fails every flush with a broken pipe.
+    #[derive(Debug, Default)]
+    struct BrokenFlushWriter {
+        data: Vec<u8>,
+    }
+
+    impl Write for BrokenFlushWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.data.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    /// Accepts `limit` bytes in total, then fails every write.
+    #[derive(Debug)]
+    struct FaultyWriter {
+        limit: usize,
+        data: Vec<u8>,
+    }
+
+    impl Write for FaultyWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let available = self.limit - self.data.len();
+            if available == 0 {
+                return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "fault"));
+            }
+
+            self.data.write(&buf[..buf.len().min(available)])
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Writer that can still be read after it was moved into the writer
+    /// under test.
+    #[derive(Debug, Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn give_random_input() -> Vec<u8> {
+        let mut rng = rand::thread_rng();
+        let lines = rng.gen_range(0..10);
+
+        let mut buffer = String::new();
+        for _line in 0..lines {
+            let newline = rng.gen_bool(0.33);
+
+            buffer.push_str("ABC");
+            newline.then(|| buffer.push('\n'));
+        }
+        buffer.push('e');
+
+        buffer.as_bytes().to_vec()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            concatcp,
+            give_random_input,
+            prefix_all,
+            Arc,
+            BrokenFlushWriter,
+            CheckedWriter,
+            ChunkWriter,
+            Classifier,
+            Duration,
+            FaultyWriter,
+            FullWriter,
+            Instant,
+            Mutex,
+            PrefixEllipsis,
+            PrefixWriteError,
+            PrefixWriter,
+            Sampling,
+            Sanitization,
+            SharedBuffer,
+            Unit,
+            Verbosity,
+            VerbositySpec,
+            VerbosityStats,
+            Write,
+            WriteStage,
+            PREFIX,
+        };
+
+        fn run(input: &str, prefix: &str, expected: &str) {
+            let mut buffer = Vec::new();
+            let mut writer = PrefixWriter::new(prefix.to_owned(), &mut buffer);
+
+            writer.write_all(input.as_bytes()).unwrap();
+            writer.flush().unwrap();
+
+            let got = String::from_utf8_lossy(&buffer);
+
+            assert_eq!(expected, got);
+        }
+
+        #[test]
+        fn empty() {
+            const INPUT: &str = "";
+            const EXPECTED: &str = INPUT;
+
+            run(INPUT, PREFIX, EXPECTED);
+        }
+
+        #[test]
+        fn empty_lines() {
+            const INPUT: &str = "\n\n\n\n";
+            const EXPECTED: &str = INPUT;
+
+            run(INPUT, PREFIX, EXPECTED);
+        }
+
+        #[test]
+        fn no_newline() {
+            const INPUT: &str = "first";
+            const EXPECTED: &str = concatcp!(PREFIX, INPUT);
+
+            run(INPUT, PREFIX, EXPECTED);
+        }
+
+        #[test]
+        fn single_line() {
+            const INPUT: &str = "first\n";
+            const EXPECTED: &str = concatcp!(PREFIX, INPUT);
+
+            run(INPUT, PREFIX, EXPECTED);
+        }
+
+        #[test]
+        fn two_line() {
+            const INPUT: &str = "first\nsecond\n";
+            const EXPECTED: &str = concatcp!(PREFIX, "first\n", PREFIX, "second\n");
+
+            run(INPUT, PREFIX, EXPECTED);
+        }
+
+        #[test]
+        fn two_line_remainder() {
+            const INPUT: &str = "first\nsecond";
+            const EXPECTED: &str = concatcp!(PREFIX, "first\n", PREFIX, "second");
+
+            run(INPUT, PREFIX, EXPECTED);
+        }
+
+        #[test]
+        fn two_line_empty_lines() {
+            const INPUT: &str = "first\n\n\n\nsecond\n";
+            const EXPECTED: &str = concatcp!(PREFIX, "first\n", "\n\n\n", PREFIX, "second\n");
+
+            run(INPUT, PREFIX, EXPECTED);
+        }
+
+        #[test]
+        fn write_after_flush() {
+            const EXPECTED: &str = concatcp!(PREFIX, "foo", PREFIX, "bar\n", PREFIX, "baz");
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            writer.write_all(b"foo").unwrap();
+            writer.flush().unwrap();
+            writer.write_all(b"bar\n").unwrap();
+            writer.flush().unwrap();
+            writer.write_all(b"baz").unwrap();
+            writer.flush().unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        fn inplace_remainder() {
+            const EXPECTED: &str = concatcp!(
+                PREFIX, "10%", "\r\x1b[K", PREFIX, "20%", "\r\x1b[K", PREFIX, "done\n", PREFIX,
+                "next\n"
+            );
+
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_inplace_remainder(true);
+
+            writer.write_all(b"10%").unwrap();
+            writer.flush().unwrap();
+            writer.write_all(b"20%").unwrap();
+            writer.flush().unwrap();
+            writer.flush().unwrap();
+            writer.write_all(b"done\nnext\n").unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        fn carriage_return_prefix() {
+            const EXPECTED: &str =
+                concatcp!(PREFIX, "10%\r", PREFIX, "20%\r", PREFIX, "done\n", PREFIX, "end\r");
+
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_carriage_return_prefix(true);
+
+            writer.write_all(b"10%\r20%\rdone\r\nend\r").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        fn zebra() {
+            const EXPECTED: &str = concatcp!(
+                "\x1b[40m",
+                PREFIX,
+                "first\x1b[0m\n",
+                "\x1b[47m",
+                PREFIX,
+                "second\x1b[0m\n",
+                "\n",
+                "\x1b[47m",
+                PREFIX,
+                "third\x1b[0m\n",
+            );
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_zebra("40".to_owned(), "47".to_owned());
+
+            writer.write_all(b"first\nsec").unwrap();
+            writer.write_all(b"ond\n\nthird\n").unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        fn prefix_first_n() {
+            const EXPECTED: &str = concatcp!(PREFIX, "header\nfirst\nsecond\nthird");
+
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_prefix_first_n(1);
+
+            writer.write_all(b"header\nfirst\nsec").unwrap();
+            writer.write_all(b"ond\nthird").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        fn blank_line_sink() {
+            let blank_lines = SharedBuffer::default();
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_blank_line_sink(blank_lines.clone());
+
+            writer.write_all(b"first\n\nsec").unwrap();
+            writer.write_all(b"ond\r\n\r\n\n").unwrap();
+
+            assert_eq!(
+                concatcp!(PREFIX, "first\n", PREFIX, "second\n"),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+            assert_eq!(
+                "\n\n\n",
+                String::from_utf8_lossy(&blank_lines.0.lock().unwrap())
+            );
+        }
+
+        #[test]
+        fn rate_limit() {
+            const EXPECTED: &str = concatcp!(
+                PREFIX,
+                "1\n",
+                PREFIX,
+                "2\n",
+                PREFIX,
+                "(3 lines suppressed)\n",
+                PREFIX,
+                "6\n",
+                PREFIX,
+                "7\n",
+                PREFIX,
+                "8\n",
+            );
+
+            let start = Instant::now();
+            let now = Arc::new(Mutex::new(start));
+
+            let clock = Arc::clone(&now);
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_rate_limit(2)
+                .with_clock(move || *clock.lock().unwrap());
+
+            writer.write_all(b"1\n2\n3\n4\n").unwrap();
+
+            *now.lock().unwrap() = start + Duration::from_millis(900);
+            writer.write_all(b"5\n").unwrap();
+
+            *now.lock().unwrap() = start + Duration::from_secs(1);
+            writer.write_all(b"6\n7\n").unwrap();
+
+            *now.lock().unwrap() = start + Duration::from_millis(2500);
+            writer.write_all(b"8\n").unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        fn banner_prefix() {
+            const BANNER: &str = "-- web --\n> ";
+            const EXPECTED: &str =
+                concatcp!(BANNER, "first\n\n", BANNER, "second\n", BANNER, "third");
+
+            let mut writer = PrefixWriter::new(BANNER.to_owned(), Vec::new());
+
+            writer.write_all(b"first\n\nsec").unwrap();
+            writer.write_all(b"ond\nthird").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        fn banner_prefix_padding() {
+            const BANNER: &str = "-- web --\n> ";
+
+            let run = |mut writer: PrefixWriter<Vec<u8>>| {
+                writer.write_all(b"first\nsecond\n").unwrap();
+                String::from_utf8_lossy(writer.get_ref()).into_owned()
+            };
+
+            assert_eq!(
+                "-- web --\n>   first\n-- web --\n>   second\n",
+                run(PrefixWriter::new(BANNER.to_owned(), Vec::new()).with_prefix_column(4))
+            );
+            assert_eq!(
+                "-- web --\nlo…first\n-- web --\nlo…second\n",
+                run(
+                    PrefixWriter::new("-- web --\nlong: ".to_owned(), Vec::new())
+                        .with_max_prefix_width(3, PrefixEllipsis::Trailing)
+                )
+            );
+        }
+
+        #[test]
+        fn banner_prefix_empty_lines() {
+            const BANNER: &str = "-- web --\n> ";
+            const INPUT: &[u8] = b"first\n\n\nsecond\n";
+
+            let run = |mut writer: PrefixWriter<Vec<u8>>| {
+                writer.write_all(INPUT).unwrap();
+                String::from_utf8_lossy(writer.get_ref()).into_owned()
+            };
+            let writer = || PrefixWriter::new(BANNER.to_owned(), Vec::new());
+
+            assert_eq!(
+                concatcp!(BANNER, "first\n\n\n", BANNER, "second\n"),
+                run(writer())
+            );
+            assert_eq!(
+                concatcp!(BANNER, "first\n", BANNER, "\n", BANNER, "\n", BANNER, "second\n"),
+                run(writer().with_prefix_empty_lines(true))
+            );
+            assert_eq!(
+                concatcp!(BANNER, "first\n\n", BANNER, "second\n"),
+                run(writer().with_squeeze_empty_lines(true))
+            );
+            assert_eq!(
+                concatcp!(BANNER, "first\n", BANNER, "second\n"),
+                run(writer().with_blank_line_sink(std::io::sink()))
+            );
+            assert_eq!(
+                "-- web --\n> first\n-- web --\n>\n-- web --\n>\n-- web --\n> second\n",
+                run(writer().with_markdown_blockquote(true))
+            );
+        }
+
+        #[test]
+        fn try_new() {
+            for prefix in ["", "prefix: ", "\t[web] ", "日本: ", "[ü] | "] {
+                assert!(PrefixWriter::try_new(prefix.to_owned(), Vec::new()).is_ok());
+            }
+
+            for (prefix, character, index) in [
+                ("tag:\n", '\n', 4),
+                ("ü\r\n", '\r', 2),
+                ("\x1b[31mred\x1b[0m ", '\x1b', 0),
+                ("nul\0: ", '\0', 3),
+                ("bell\x07", '\x07', 4),
+                ("del\x7f", '\x7f', 3),
+                ("next\u{85}line", '\u{85}', 4),
+                ("ü\u{2028}", '\u{2028}', 2),
+            ] {
+                let err = PrefixWriter::try_new(prefix.to_owned(), Vec::new()).unwrap_err();
+
+                assert_eq!(character, err.character());
+                assert_eq!(index, err.index());
+            }
+
+            let err = PrefixWriter::try_new("tag:\n".to_owned(), Vec::new()).unwrap_err();
+            assert_eq!(
+                "invalid character '\\n' at index 4 of the prefix",
+                err.to_string()
+            );
+        }
+
+        #[test]
+        fn prefix_all_like_writer() {
+            const LINES: &[&str] = &["first", "", "  indented", "last"];
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+            for line in LINES {
+                writeln!(writer, "{line}").unwrap();
+            }
+
+            let expected = String::from_utf8_lossy(writer.get_ref()).into_owned();
+
+            assert_eq!(
+                expected.lines().collect::<Vec<_>>(),
+                prefix_all(PREFIX, LINES)
+            );
+        }
+
+        #[test]
+        fn panicking_closure() {
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_map_line(|line| {
+                    assert_ne!("boom", line, "closure panicked");
+                    line.to_uppercase()
+                });
+
+            writer.write_all(b"first\nbo").unwrap();
+
+            let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                writer.write_all(b"om\nafter\n").unwrap();
+            }));
+            assert!(panicked.is_err());
+            assert_eq!("prefix: FIRST\n", String::from_utf8_lossy(writer.get_ref()));
+
+            // The incomplete line of the earlier write is kept.
+            writer.flush().unwrap();
+            assert_eq!(
+                "prefix: FIRST\nprefix: BO",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn terminator_only_prefix() {
+            for prefix in ["\n", "\r\n", "\n\n"] {
+                let err = PrefixWriter::try_new(prefix.to_owned(), Vec::new()).unwrap_err();
+
+                assert_eq!(prefix.chars().next(), Some(err.character()));
+                assert_eq!(0, err.index());
+            }
+
+            let mut writer = PrefixWriter::new("\n".to_owned(), Vec::new());
+            writer.write_all(b"first\nsecond\n").unwrap();
+
+            assert_eq!(
+                "\nfirst\n\nsecond\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn push_pop_prefix() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            writer.write_all(b"outer\n").unwrap();
+            writer.push_prefix("[a] ");
+            writer.write_all(b"scope a\n").unwrap();
+            writer.push_prefix("[b] ");
+            writer.write_all(b"scope b\n").unwrap();
+
+            assert!(!writer.pop_prefix("[a] "));
+            assert!(writer.pop_prefix("[b] "));
+            writer.write_all(b"back in a\n").unwrap();
+            assert!(writer.pop_prefix("[a] "));
+            writer.write_all(b"outer again\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "prefix: outer\n",
+                    "prefix: [a] scope a\n",
+                    "prefix: [a] [b] scope b\n",
+                    "prefix: [a] back in a\n",
+                    "prefix: outer again\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn silence_broken_pipe() {
+            let sink = FaultyWriter {
+                limit: 29,
+                data: Vec::new(),
+            };
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), sink).with_silence_broken_pipe(true);
+
+            writer.write_all(b"first\nsecond\n").unwrap();
+            assert!(!writer.broken_pipe());
+
+            writer.write_all(b"third\nfourth\nincomplete").unwrap();
+            writer.flush().unwrap();
+            assert!(writer.broken_pipe());
+
+            assert_eq!(
+                "prefix: first\nprefix: second\n",
+                String::from_utf8_lossy(&writer.get_ref().data)
+            );
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), FullWriter::default())
+                .with_silence_broken_pipe(true);
+            writer.get_mut().full = true;
+
+            assert_eq!(
+                std::io::ErrorKind::WriteZero,
+                writer.write_all(b"first\n").unwrap_err().kind()
+            );
+            assert!(!writer.broken_pipe());
+        }
+
+        #[test]
+        fn silence_broken_pipe_flush() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), BrokenFlushWriter::default());
+
+            writer.write_all(b"first\n").unwrap();
+            assert_eq!(
+                std::io::ErrorKind::BrokenPipe,
+                writer.flush().unwrap_err().kind()
+            );
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), BrokenFlushWriter::default())
+                .with_silence_broken_pipe(true);
+
+            writer.write_all(b"first\nsecond").unwrap();
+            writer.flush().unwrap();
+            assert!(writer.broken_pipe());
+
+            writer.write_all(b"\nthird\n").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                "prefix: first\nprefix: second",
+                String::from_utf8_lossy(&writer.get_ref().data)
+            );
+        }
+
+        #[test]
+        fn mute() {
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_mute_summary(true);
+
+            writer.write_all(b"first\ntorn ").unwrap();
+            writer.mute();
+            writer.write_all(b"line\nsecond\nthird\nwho").unwrap();
+            writer.flush().unwrap();
+            assert!(writer.is_muted());
+
+            writer.unmute().unwrap();
+            writer.write_all(b"le line\nfourth\n").unwrap();
+            writer.mute();
+            writer.unmute().unwrap();
+
+            assert_eq!(
+                concat!(
+                    "prefix: first\n",
+                    "prefix: (3 lines muted)\n",
+                    "prefix: whole line\n",
+                    "prefix: fourth\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn map_line_terminator() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_map_line(|line| format!("{}\n", line.to_uppercase()));
+
+            writer.write_all(b"first\nsecond\r\n").unwrap();
+            writer.write_all(b"third").unwrap();
+            writer.flush().unwrap();
+
+            let mut writer = writer.with_map_line(|line| format!("{line}\r\n"));
+            writer.write_all(b"\nfourth\n").unwrap();
+
+            assert_eq!(
+                "prefix: FIRST\nprefix: SECOND\nprefix: THIRD\nprefix: fourth\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn delta_time_prefix() {
+            let start = Instant::now();
+            let now = Arc::new(Mutex::new(start));
+
+            let clock = Arc::clone(&now);
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_delta_time_prefix(true)
+                .with_clock(move || *clock.lock().unwrap());
+
+            writer.write_all(b"first\n").unwrap();
+
+            *now.lock().unwrap() = start + Duration::from_millis(250);
+            writer.write_all(b"second\n\n").unwrap();
+
+            *now.lock().unwrap() = start + Duration::from_millis(1750);
+            writer.write_all(b"third\nfourth\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "+0.000s prefix: first\n",
+                    "+0.250s prefix: second\n",
+                    "\n",
+                    "+1.500s prefix: third\n",
+                    "+0.000s prefix: fourth\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn markdown_blockquote() {
+            let mut writer =
+                PrefixWriter::new("> ".to_owned(), Vec::new()).with_markdown_blockquote(true);
+
+            writer
+                .write_all(b"First paragraph\nstill first.\n\nSecond paragraph.\n\n\nThird.\n")
+                .unwrap();
+
+            writer.push_prefix("> ");
+            writer.write_all(b"Nested\n\nquote.\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "> First paragraph\n",
+                    "> still first.\n",
+                    ">\n",
+                    "> Second paragraph.\n",
+                    ">\n",
+                    ">\n",
+                    "> Third.\n",
+                    "> > Nested\n",
+                    "> >\n",
+                    "> > quote.\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn prefix_empty_lines() {
+            let mut writer =
+                PrefixWriter::new("> ".to_owned(), Vec::new()).with_prefix_empty_lines(true);
+
+            writer.write_all(b"a\n\n\nb\n").unwrap();
+            writer.write_all(b"\n\r\n").unwrap();
+            writer.write_all(b"c\n\n").unwrap();
+            writer.write_all(b"d").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                "> a\n> \n> \n> b\n> \n> \n> c\n> \n> d",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn prefix_empty_lines_layout() {
+            let prefixes = || ["1:", "2:", "3:"].map(str::to_owned).into_iter();
+
+            let mut writer = PrefixWriter::new("> ".to_owned(), Vec::new())
+                .with_prefix_empty_lines(true)
+                .with_prefix_iter(prefixes())
+                .with_prefix_column(4);
+            writer.write_all(b"a\n\nb\n").unwrap();
+
+            assert_eq!(
+                "1:  a\n2:  \n3:  b\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+
+            let mut writer = PrefixWriter::new("> ".to_owned(), Vec::new())
+                .with_markdown_blockquote(true)
+                .with_prefix_iter(prefixes())
+                .with_prefix_column(4);
+            writer.write_all(b"a\n\nb\n").unwrap();
+
+            assert_eq!(
+                "1:  a\n2:\n3:  b\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn prefix_empty_lines_default() {
+            let mut writer = PrefixWriter::new("> ".to_owned(), Vec::new());
+            writer.write_all(b"a\n\n\nb\n").unwrap();
+
+            assert_eq!("> a\n\n\n> b\n", String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        fn squeeze_empty_lines() {
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_squeeze_empty_lines(true);
+
+            writer.write_all(b"\n\nfirst\n\n\r\n\n").unwrap();
+            writer.write_all(b"\nsecond\n\nthird\n").unwrap();
+
+            assert_eq!(
+                concatcp!(
+                    "\n",
+                    PREFIX,
+                    "first\n\n",
+                    PREFIX,
+                    "second\n\n",
+                    PREFIX,
+                    "third\n"
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+            assert_eq!(4, writer.stats().get(PREFIX).unwrap().lines_dropped);
+        }
+
+        #[test]
+        fn trailing_newline() {
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_trailing_newline(true);
+
+            writer.write_all(b"first\nsec").unwrap();
+            writer.flush().unwrap();
+            writer.write_all(b"ond\n").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                concatcp!(PREFIX, "first\n", PREFIX, "sec\n", PREFIX, "ond\n"),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn input_delimiter_output_terminator() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_input_delimiter(b'\0')
+                .with_output_terminator("\n".to_owned());
+
+            assert_eq!(1, writer.write_str("first\0multi\nline\r").unwrap());
+            writer.write_all(b"\0\0last").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                "prefix: first\nprefix: multi\nline\r\n\nprefix: last",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_output_terminator("\r\n".to_owned());
+            writer.write_all(b"first\r\nsecond\n").unwrap();
+
+            assert_eq!(
+                "prefix: first\r\nprefix: second\r\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn message_mode() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), ChunkWriter::default())
+                .with_message_mode(true);
+
+            writer.write_all(b"fir").unwrap();
+            writer.write_all(b"st\nsec").unwrap();
+            writer.write_all(b"ond\n\nthi").unwrap();
+            writer.write_all(b"rd").unwrap();
+            writer.flush().unwrap();
+
+            let expected = [
+                concatcp!(PREFIX, "first\n"),
+                concatcp!(PREFIX, "second\n"),
+                "\n",
+                concatcp!(PREFIX, "third"),
+            ];
+
+            assert_eq!(expected.as_slice(), writer.get_ref().chunks);
+        }
+
+        #[test]
+        fn message_mode_without_terminator() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), ChunkWriter::default())
+                .with_message_mode(true)
+                .with_message_terminator(false);
+
+            writer.write_all(b"first\nsec").unwrap();
+            writer.write_all(b"ond\n").unwrap();
+
+            let expected = [concatcp!(PREFIX, "first"), concatcp!(PREFIX, "second")];
+
+            assert_eq!(expected.as_slice(), writer.get_ref().chunks);
+        }
+
+        #[test]
+        fn message_mode_short_write() {
+            let sink = ChunkWriter {
+                limit: Some(4),
+                ..ChunkWriter::default()
+            };
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), sink).with_message_mode(true);
+
+            let err = writer.write_all(b"first\n").unwrap_err();
+
+            assert_eq!(std::io::ErrorKind::Other, err.kind());
+        }
+
+        #[test]
+        #[cfg(feature = "encoding")]
+        fn line_encoder() {
+            const INPUT: &str = "first\n\nsecond";
+            const EXPECTED_BASE64: &str = concatcp!(PREFIX, "Zmlyc3Q=\n\n", PREFIX, "c2Vjb25k");
+            const EXPECTED_HEX: &str = concatcp!(PREFIX, "6669727374\n\n", PREFIX, "7365636f6e64");
+
+            for (encoding, expected) in [
+                (crate::Encoding::Base64, EXPECTED_BASE64),
+                (crate::Encoding::Hex, EXPECTED_HEX),
+            ] {
+                let mut writer =
+                    PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_line_encoder(encoding);
+
+                writer.write_all(INPUT.as_bytes()).unwrap();
+                writer.flush().unwrap();
+
+                assert_eq!(expected, String::from_utf8_lossy(writer.get_ref()));
+            }
+        }
+
+        #[test]
+        fn write_zero() {
+            const EXPECTED: &str = concatcp!(PREFIX, "partial\n", PREFIX, "next\n", PREFIX, "last");
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), FullWriter::default());
+
+            writer.write_all(b"par").unwrap();
+
+            writer.get_mut().full = true;
+            let err = writer.write_all(b"tial\nnext\n").unwrap_err();
+            assert_eq!(std::io::ErrorKind::WriteZero, err.kind());
+
+            writer.get_mut().full = false;
+            writer.write_all(b"tial\nnext\n").unwrap();
+            writer.write_all(b"last").unwrap();
+
+            writer.get_mut().full = true;
+            let err = writer.flush().unwrap_err();
+            assert_eq!(std::io::ErrorKind::WriteZero, err.kind());
+
+            writer.get_mut().full = false;
+            writer.flush().unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(&writer.get_ref().data));
+        }
+
+        #[test]
+        fn write_str() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            assert_eq!(2, writer.write_str("a\nb\nc").unwrap());
+            assert_eq!(
+                concatcp!(PREFIX, "a\n", PREFIX, "b\n"),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+
+            assert_eq!(1, writer.write_str("\n").unwrap());
+            assert_eq!(0, writer.write_str("").unwrap());
+            assert_eq!(
+                concatcp!(PREFIX, "a\n", PREFIX, "b\n", PREFIX, "c\n"),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+
+            writer.mute();
+            assert_eq!(0, writer.write_str("muted\n").unwrap());
+        }
+
+        #[test]
+        fn write_str_dropped() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_join_continuations(true)
+                .with_sampling(Sampling::every(2));
+
+            assert_eq!(1, writer.write_str("a\nb\n").unwrap());
+            assert_eq!(0, writer.write_str("c \\\n").unwrap());
+            assert_eq!(1, writer.write_str("d\n").unwrap());
+            assert_eq!(0, writer.write_str("e\n").unwrap());
+            assert_eq!(
+                concatcp!(PREFIX, "a\n", PREFIX, "c d\n"),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn write_marker() {
+            const EXPECTED: &str = concatcp!(
+                PREFIX,
+                "partial\n",
+                PREFIX,
+                "== web started (pid 4242)\n",
+                PREFIX,
+                " next\n",
+                "-- web exited with status 1\n",
+            );
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            writer.write_all(b"partial").unwrap();
+            writer.write_marker("web started (pid 4242)").unwrap();
+            writer.write_all(b" next\n").unwrap();
+
+            let mut writer = writer.with_marker_prefix("-- ".to_owned());
+            writer.write_marker("web exited with status 1").unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        #[cfg(feature = "encoding")]
+        fn write_marker_not_encoded() {
+            const EXPECTED: &str = concatcp!(PREFIX, "Zmlyc3Q=\n", PREFIX, "== marker\n");
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_line_encoder(crate::Encoding::Base64);
+
+            writer.write_all(b"first").unwrap();
+            writer.write_marker("marker").unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        fn shrink_to_fit() {
+            let huge = "a".repeat(1024 * 1024);
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), std::io::sink());
+
+            writer.write_all(b"first").unwrap();
+            for chunk in huge.as_bytes().chunks(1000) {
+                writer.write_all(chunk).unwrap();
+            }
+            assert!(writer.remainder.as_ref().unwrap().capacity() > huge.len() + 5);
+
+            writer.shrink_to_fit();
+            assert_eq!(
+                huge.len() + 5,
+                writer.remainder.as_ref().unwrap().capacity()
+            );
+
+            writer.flush().unwrap();
+            writer.shrink_to_fit();
+            assert_eq!(0, writer.remainder.as_ref().map_or(0, String::capacity));
+        }
+
+        #[test]
+        fn on_raw_line() {
+            let lines = Arc::new(Mutex::new(Vec::new()));
+
+            let recorded = Arc::clone(&lines);
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_on_raw_line(
+                move |content, terminator| {
+                    recorded
+                        .lock()
+                        .unwrap()
+                        .push(format!("{content}{terminator}"));
+                },
+            );
+
+            writer.write_all(b"first\r\nsecond\n\nthi").unwrap();
+            writer.write_all(b"rd\r").unwrap();
+            writer.write_all(b"\nlast").unwrap();
+            writer.flush().unwrap();
+
+            let expected = ["first\r\n", "second\n", "\n", "third\r\n", "last"];
+
+            assert_eq!(expected.as_slice(), *lines.lock().unwrap());
+            assert_eq!(
+                concatcp!(
+                    PREFIX,
+                    "first\n",
+                    PREFIX,
+                    "second\n\n",
+                    PREFIX,
+                    "third\n",
+                    PREFIX,
+                    "last"
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn write_checked() {
+            // Errors after the start of a line was written are returned by
+            // the next call.
+            const INPUT: &[&[u8]] = &[b"first\n", b"second\n", b""];
+
+            let cases = [
+                (3, 1, 3, WriteStage::Prefix),
+                (PREFIX.len() + 2, 1, PREFIX.len() + 2, WriteStage::Payload),
+                (
+                    PREFIX.len() + 5,
+                    1,
+                    PREFIX.len() + 5,
+                    WriteStage::Terminator,
+                ),
+                (PREFIX.len() + 6, 2, 0, WriteStage::Prefix),
+                (
+                    PREFIX.len() * 2 + 7,
+                    2,
+                    PREFIX.len() + 1,
+                    WriteStage::Payload,
+                ),
+            ];
+
+            for (limit, line, emitted, stage) in cases {
+                let sink = FaultyWriter {
+                    limit,
+                    data: Vec::new(),
+                };
+                let mut writer = PrefixWriter::new(PREFIX.to_owned(), sink);
+
+                let err = INPUT
+                    .iter()
+                    .find_map(|input| writer.write_checked(input).err())
+                    .unwrap();
+
+                assert_eq!(line, err.line());
+                assert_eq!(emitted, err.emitted());
+                assert_eq!(stage, err.stage());
+                assert_eq!(std::io::ErrorKind::BrokenPipe, err.kind());
+                assert_eq!(
+                    "fault",
+                    std::error::Error::source(&err).unwrap().to_string()
+                );
+            }
+        }
+
+        #[test]
+        fn write_error_downcast() {
+            let sink = FaultyWriter {
+                limit: PREFIX.len() + 2,
+                data: Vec::new(),
+            };
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), sink);
+
+            writer.write_all(b"first\n").unwrap();
+
+            let err = writer.flush().unwrap_err();
+            assert_eq!(std::io::ErrorKind::BrokenPipe, err.kind());
+
+            let err = err
+                .get_ref()
+                .unwrap()
+                .downcast_ref::<PrefixWriteError>()
+                .unwrap();
+            assert_eq!(1, err.line());
+            assert_eq!(PREFIX.len() + 2, err.emitted());
+            assert_eq!(WriteStage::Payload, err.stage());
+        }
+
+        #[test]
+        fn map_writer() {
+            const EXPECTED: &str =
+                concatcp!(PREFIX, "first\n", PREFIX, "second\n", PREFIX, "third");
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+            writer.write_all(b"first\nsec").unwrap();
+
+            let mut writer = writer.map_writer(std::io::BufWriter::new);
+            writer.write_all(b"ond\nthird").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                EXPECTED,
+                String::from_utf8_lossy(writer.get_ref().get_ref())
+            );
+        }
+
+        #[test]
+        fn prefix_iter() {
+            const INPUT: &str = "first\nsecond\nthird\nfourth";
+            const EXPECTED: &str =
+                concatcp!("1: first\n", "2: second\n", "3: third\n", PREFIX, "fourth");
+
+            let metadata = vec!["1: ".to_owned(), "2: ".to_owned(), "3: ".to_owned()];
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_prefix_iter(metadata.into_iter());
+
+            writer.write_all(INPUT.as_bytes()).unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        fn empty_content() {
+            const INPUTS: &[&str] = &["\n\r\nfirst\r", "\n\r", "\n\r"];
+            const EXPECTED: &str = concatcp!("\n\n", PREFIX, "first\n\n\r");
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            for input in INPUTS {
+                writer.write_all(input.as_bytes()).unwrap();
+            }
+            writer.flush().unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        fn empty_content_prefix_iter() {
+            const EXPECTED: &str = concatcp!("\n2: first\n\r\n", PREFIX, "second\n");
+
+            let metadata = vec!["1: ".to_owned(), "2: ".to_owned()];
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_prefix_iter(metadata.into_iter());
+
+            writer.write_all(b"\nfirst\r\n\r").unwrap();
+            writer.flush().unwrap();
+            writer.write_all(b"\r\nsecond\n").unwrap();
+
+            assert_eq!(EXPECTED, String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[test]
+        fn preview() {
+            const INPUTS: &[&str] = &["first\nsec", "ond\n\nthird", "", "\n", "fourth"];
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            for input in INPUTS {
+                let preview = writer.preview(input.as_bytes());
+
+                let before = writer.get_ref().len();
+                writer.write_all(input.as_bytes()).unwrap();
+                let got = String::from_utf8_lossy(&writer.get_ref()[before..]);
+
+                assert_eq!(preview, got);
+            }
+        }
+
+        #[test]
+        fn preview_options() {
+            const INPUTS: &[&str] = &["first\nsec", "ond\n\nthird and more", "\n", "fourth\n"];
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_map_line(str::to_uppercase)
+                .with_zebra("1".to_owned(), "2".to_owned())
+                .with_prefix_first_n(3)
+                .with_prefix_column(10)
+                .with_wrap_width(10);
+
+            for input in INPUTS {
+                let preview = writer.preview(input.as_bytes());
+
+                let before = writer.get_ref().len();
+                writer.write_all(input.as_bytes()).unwrap();
+                let got = String::from_utf8_lossy(&writer.get_ref()[before..]);
+
+                assert_eq!(preview, got);
+            }
+
+            assert!(String::from_utf8_lossy(writer.get_ref()).contains("prefix:   SECOND"));
+        }
+
+        #[test]
+        fn preview_state() {
+            const INPUTS: &[&str] = &[
+                "first\nerror: sec",
+                "ond\n\x1b[3",
+                "1mthird\n",
+                "debug fourth\nfifth\n",
+                "sixth\n",
+            ];
+
+            let verbosity =
+                Verbosity::new(VerbositySpec::parse("").unwrap(), Classifier::default());
+            let writer = || PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+            let writers = [
+                writer().with_prefix_iter(["1: ", "2: ", "3: "].map(str::to_owned).into_iter()),
+                writer().with_sort(None::<fn(&str, &str) -> std::cmp::Ordering>),
+                writer().with_sampling(Sampling::every(2)),
+                writer().with_verbosity(&verbosity, "web"),
+                writer().with_alert_on(|line| line.starts_with("error"), b"\x07".to_vec()),
+                writer().with_sanitization(Sanitization::new()),
+            ];
+
+            for mut writer in writers {
+                for input in INPUTS {
+                    let preview = writer.preview(input.as_bytes());
+                    assert_eq!(preview, writer.preview(input.as_bytes()));
+
+                    let before = writer.get_ref().len();
+                    writer.write_all(input.as_bytes()).unwrap();
+                    let got = String::from_utf8_lossy(&writer.get_ref()[before..]);
+
+                    assert_eq!(preview, got);
+                }
+            }
+
+            assert_eq!(
+                VerbosityStats {
+                    kept: 5,
+                    dropped: 1,
+                },
+                verbosity.stats("web")
+            );
+        }
+
+        #[test]
+        fn running_count() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_running_count(4);
+
+            writer.write_all(b"first\nsec").unwrap();
+            writer.write_all(b"ond\n\nthird").unwrap();
+            writer.flush().unwrap();
+            writer.write_all(b" continued\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "   1 prefix: first\n",
+                    "   2 prefix: second\n",
+                    "\n",
+                    "   4 prefix: third",
+                    "   5 prefix:  continued\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn word_count_prefix() {
+            let mut writer =
+                PrefixWriter::new(String::new(), Vec::new()).with_word_count_prefix(true);
+
+            writer
+                .write_all(b"the quick fox\n  spaced\tout  words \n\nend\n")
+                .unwrap();
+
+            assert_eq!(
+                "[3] the quick fox\n[3]   spaced\tout  words \n\n[1] end\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[cfg(feature = "unicode")]
+        #[test]
+        fn word_count_unicode() {
+            let mut writer =
+                PrefixWriter::new(String::new(), Vec::new()).with_word_count_prefix(true);
+
+            writer.write_all("grüße\u{3000}日本\n".as_bytes()).unwrap();
+
+            assert_eq!(
+                "[2] grüße\u{3000}日本\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn intra_write_index() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_intra_write_index("{index}/ ".to_owned());
+
+            writer.write_all(b"first\nsecond\n\nthi").unwrap();
+            writer.write_all(b"rd\nfourth\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "1/ prefix: first\n",
+                    "2/ prefix: second\n",
+                    "\n",
+                    "1/ prefix: third\n",
+                    "2/ prefix: fourth\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn describe() {
+            assert_eq!(
+                r#"prefix "prefix: ", terminator "\n""#,
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).describe()
+            );
+
+            let writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_message_mode(true)
+                .with_message_terminator(false)
+                .with_marker_prefix("> ".to_owned())
+                .with_prefix_first_n(3)
+                .with_width_unit(Unit::Bytes)
+                .with_rate_limit(10);
+
+            assert_eq!(
+                concat!(
+                    r#"prefix "prefix: ", terminator "", message mode, marker prefix "> ", "#,
+                    "prefix first 3 lines, widths in Bytes, rate limit 10 lines per second",
+                ),
+                writer.describe()
+            );
+        }
+
+        #[test]
+        fn fuzztest() {
+            for _ in 0..10_000 {
+                let input = give_random_input();
+
+                let mut checker = CheckedWriter::new(PREFIX.to_owned(), Vec::new());
+                let mut writer = PrefixWriter::new(PREFIX.to_owned(), &mut checker);
+
+                writer.write_all(&input).unwrap();
+                writer.flush().unwrap();
+
+                checker.finish();
             }
         }
     }
@@ -0,0 +1,103 @@
+//! Formatting lines with a template around their content.
+
+use std::io::Write;
+
+use crate::PrefixWriter;
+
+/// Split the template at the first `{}` into the text before and after
+/// the content, unescaping `{{` and `}}`. Without a placeholder the
+/// content follows the whole template.
+fn parse(template: &str) -> (String, String) {
+    let (mut before, mut after) = (String::new(), String::new());
+    let mut placeholder_seen = false;
+
+    let mut characters = template.chars().peekable();
+    while let Some(character) = characters.next() {
+        let out = if placeholder_seen {
+            &mut after
+        } else {
+            &mut before
+        };
+
+        match (character, characters.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                characters.next();
+                out.push(character);
+            }
+            ('{', Some('}')) if !placeholder_seen => {
+                characters.next();
+                placeholder_seen = true;
+            }
+            _ => out.push(character),
+        }
+    }
+
+    (before, after)
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Write every line through the template, with the first `{}`
+    /// replaced by the content of the line, like `LOG: {} (end)`. `{{`
+    /// and `}}` are a literal `{` and `}`. The text before the
+    /// placeholder becomes the prefix, the text after it ends every line
+    /// before the terminator. Empty lines stay empty.
+    #[must_use]
+    pub fn with_line_format(self, template: &str) -> Self {
+        let (prefix, suffix) = parse(template);
+
+        Self {
+            prefix,
+            line_suffix: Some(suffix).filter(|suffix| !suffix.is_empty()),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::parse;
+    use crate::PrefixWriter;
+
+    mod tests {
+        use super::{
+            assert_eq,
+            parse,
+            PrefixWriter,
+            Write,
+        };
+
+        #[test]
+        fn parse_template() {
+            assert_eq!(
+                ("LOG: ".to_owned(), " (end)".to_owned()),
+                parse("LOG: {} (end)")
+            );
+            assert_eq!(
+                ("{literal} ".to_owned(), " {}".to_owned()),
+                parse("{{literal}} {} {}")
+            );
+            assert_eq!(
+                ("no placeholder ".to_owned(), String::new()),
+                parse("no placeholder ")
+            );
+        }
+
+        #[test]
+        fn wrap_content() {
+            let mut writer =
+                PrefixWriter::new(String::new(), Vec::new()).with_line_format("LOG: {} (end)");
+
+            writer.write_all(b"first\n\nsecond\r\nthi").unwrap();
+            writer.write_all(b"rd").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                "LOG: first (end)\n\nLOG: second (end)\nLOG: third (end)",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
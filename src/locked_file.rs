@@ -0,0 +1,89 @@
+//! Appending to a file shared by several processes without splicing
+//! lines.
+
+use std::{
+    fs::{
+        File,
+        OpenOptions,
+    },
+    io::Write,
+    path::Path,
+};
+
+use crate::{
+    PrefixWriter,
+    SyncableWrite,
+};
+
+/// Writes to a file while holding an exclusive advisory lock on it, so
+/// several processes can append to the same file without their lines
+/// interleaving. [`PrefixWriter`] hands every line to its writer in one
+/// write, so every line is written under one lock. Only processes that
+/// lock the file as well are kept out, the lock is advisory.
+#[derive(Debug)]
+pub struct LockedFileSink {
+    file: File,
+}
+
+impl LockedFileSink {
+    /// Create a new [`LockedFileSink`] writing to the file. The file
+    /// should be opened for appending, otherwise processes overwrite the
+    /// lines of each other.
+    #[must_use]
+    pub fn new(file: File) -> Self {
+        Self { file }
+    }
+
+    /// Open the file at the path for appending, creating it if it does
+    /// not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of opening the file.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(Self::new)
+    }
+
+    /// Get back the file.
+    #[must_use]
+    pub fn into_inner(self) -> File {
+        self.file
+    }
+}
+
+impl Write for LockedFileSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.lock()?;
+        let result = self.file.write_all(buf);
+        self.file.unlock()?;
+
+        result.map(|()| buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl SyncableWrite for LockedFileSink {
+    fn sync_data(&mut self) -> std::io::Result<()> {
+        self.file.sync_data()
+    }
+}
+
+impl PrefixWriter<LockedFileSink> {
+    /// Create a new [`PrefixWriter`] appending to the file at the path
+    /// through a [`LockedFileSink`], for several processes sharing one
+    /// log file.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of opening the file.
+    pub fn shared_file(path: impl AsRef<Path>, prefix: String) -> std::io::Result<Self> {
+        LockedFileSink::open(path).map(|sink| Self::new(prefix, sink))
+    }
+}
@@ -0,0 +1,176 @@
+//! Writing every line as a logfmt record of key-value pairs.
+
+use std::io::Write;
+
+use crate::{
+    ClockConfig,
+    PrefixWriter,
+};
+
+/// Where the value of a logfmt field comes from, see
+/// [`PrefixWriter::with_logfmt`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FieldSource {
+    /// The same value for every line.
+    Constant(String),
+
+    /// The content of the line.
+    Content,
+
+    /// The time the line is written as RFC 3339, in UTC like
+    /// `2024-05-17T08:03:09.042Z` unless another clock is set with
+    /// [`PrefixWriter::with_timestamp_clock`].
+    Timestamp,
+
+    /// The number of the line, starting at 1.
+    LineNumber,
+}
+
+/// Render a line as logfmt record without the terminator.
+pub(crate) fn render(
+    fields: &[(String, FieldSource)],
+    content: &str,
+    line_number: u64,
+    clock: &mut ClockConfig,
+) -> String {
+    fields
+        .iter()
+        .map(|(key, source)| {
+            let value = match source {
+                FieldSource::Constant(value) => quote(value),
+                FieldSource::Content => quote(content),
+                FieldSource::Timestamp => clock.now(),
+                FieldSource::LineNumber => line_number.to_string(),
+            };
+
+            format!("{key}={value}")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quote the value if it is empty or contains spaces, quotes, equal signs
+/// or control characters, escaping quotes, backslashes and control
+/// characters.
+fn quote(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|character| matches!(character, ' ' | '"' | '=' | '\\') || character.is_control());
+
+    if !needs_quotes {
+        return value.to_owned();
+    }
+
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+
+    for character in value.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            character if character.is_control() => {
+                quoted.extend(character.escape_unicode());
+            }
+            character => quoted.push(character),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Write every line as a logfmt record of the fields in order, like
+    /// `level=info msg="content of the line"`, instead of prefixing it.
+    /// Values are quoted when they contain spaces or other characters
+    /// that would break the record. Empty lines are written as records
+    /// as well.
+    #[must_use]
+    pub fn with_logfmt(self, fields: Vec<(String, FieldSource)>) -> Self {
+        Self {
+            logfmt: Some(fields),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::{
+        quote,
+        FieldSource,
+    };
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            quote,
+            FieldSource,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn records() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_logfmt(vec![
+                ("level".to_owned(), FieldSource::Constant("info".to_owned())),
+                ("line".to_owned(), FieldSource::LineNumber),
+                ("msg".to_owned(), FieldSource::Content),
+            ]);
+
+            writer
+                .write_all(b"started\nlistening on port=80\n\nsaid \"hi\"\n")
+                .unwrap();
+
+            assert_eq!(
+                concat!(
+                    "level=info line=1 msg=started\n",
+                    "level=info line=2 msg=\"listening on port=80\"\n",
+                    "level=info line=3 msg=\"\"\n",
+                    "level=info line=4 msg=\"said \\\"hi\\\"\"\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn timestamp() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_logfmt(vec![
+                ("ts".to_owned(), FieldSource::Timestamp),
+                ("msg".to_owned(), FieldSource::Content),
+            ]);
+
+            writer.write_all(b"line\n").unwrap();
+
+            let output = String::from_utf8_lossy(writer.get_ref());
+            let (timestamp, rest) = output.strip_prefix("ts=").unwrap().split_once(' ').unwrap();
+
+            assert_eq!("msg=line\n", rest);
+            assert_eq!(24, timestamp.len());
+            assert!(timestamp.ends_with('Z'));
+        }
+
+        #[test]
+        fn quoting() {
+            assert_eq!("plain", quote("plain"));
+            assert_eq!("ünïcode", quote("ünïcode"));
+            assert_eq!("\"\"", quote(""));
+            assert_eq!("\"a b\"", quote("a b"));
+            assert_eq!("\"a\\\\b\"", quote("a\\b"));
+            assert_eq!("\"tab\\there\"", quote("tab\there"));
+            assert_eq!("\"bell\\u{7}\"", quote("bell\x07"));
+        }
+    }
+}
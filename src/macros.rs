@@ -0,0 +1,163 @@
+//! Macros writing through a [`crate::PrefixWriter`].
+
+/// Like [`dbg!`] but writes through the writer, usually a
+/// [`crate::PrefixWriter`], instead of to stderr, so every line of the
+/// pretty printed value gets prefixed. Evaluates the expressions, writes
+/// the file, line and column with every expression and its value and
+/// returns the values, moving them like [`dbg!`] does. Errors of the
+/// writer are ignored.
+///
+/// ```
+/// use prefix_writer::{
+///     pdbg,
+///     PrefixWriter,
+/// };
+///
+/// let mut writer = PrefixWriter::new("debug: ".to_owned(), Vec::new());
+///
+/// let doubled = pdbg!(writer, 2 * 21);
+///
+/// assert_eq!(42, doubled);
+/// assert!(String::from_utf8_lossy(writer.get_ref()).ends_with("] 2 * 21 = 42\n"));
+/// ```
+#[macro_export]
+macro_rules! pdbg {
+    ($writer:expr $(,)?) => {{
+        let _ = ::std::io::Write::write_fmt(
+            &mut $writer,
+            ::std::format_args!(
+                "[{}:{}:{}]\n",
+                ::std::file!(),
+                ::std::line!(),
+                ::std::column!()
+            ),
+        );
+    }};
+    ($writer:expr, $value:expr $(,)?) => {
+        match $value {
+            value => {
+                let _ = ::std::io::Write::write_fmt(
+                    &mut $writer,
+                    ::std::format_args!(
+                        "[{}:{}:{}] {} = {:#?}\n",
+                        ::std::file!(),
+                        ::std::line!(),
+                        ::std::column!(),
+                        ::std::stringify!($value),
+                        &value
+                    ),
+                );
+                value
+            }
+        }
+    };
+    ($writer:expr, $($value:expr),+ $(,)?) => {{
+        let writer = &mut $writer;
+        ($($crate::pdbg!(*writer, $value)),+,)
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "debug: ";
+
+    #[derive(Debug, PartialEq)]
+    struct Outer {
+        name: String,
+        inner: Inner,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Inner {
+        values: Vec<u8>,
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            Inner,
+            Outer,
+            PrefixWriter,
+            PREFIX,
+        };
+
+        #[test]
+        fn returns_value() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            let name = String::from("moved");
+            let moved: String = pdbg!(writer, name);
+            assert_eq!("moved", moved);
+
+            let (first, second) = pdbg!(writer, 1 + 1, "two");
+            assert_eq!((2, "two"), (first, second));
+
+            let reference = &moved;
+            let same: &String = pdbg!(writer, reference);
+            assert!(std::ptr::eq(reference, same));
+        }
+
+        #[test]
+        fn writer_evaluated_once() {
+            let mut writers = [PrefixWriter::new(PREFIX.to_owned(), Vec::new())];
+            let mut evaluated = 0;
+
+            let values = pdbg!(
+                writers[{
+                    evaluated += 1;
+                    0
+                }],
+                1,
+                2,
+                3
+            );
+
+            assert_eq!((1, 2, 3), values);
+            assert_eq!(1, evaluated);
+            assert_eq!(
+                3,
+                String::from_utf8_lossy(writers[0].get_ref())
+                    .lines()
+                    .count()
+            );
+        }
+
+        #[test]
+        fn nested_struct() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            let outer = pdbg!(
+                writer,
+                Outer {
+                    name: "outer".to_owned(),
+                    inner: Inner { values: vec![1, 2] },
+                }
+            );
+            assert_eq!("outer", outer.name);
+
+            let output = String::from_utf8_lossy(writer.get_ref()).into_owned();
+            let (location, value) = output.split_once("] ").unwrap();
+
+            assert!(location.starts_with("debug: [src/macros.rs:"));
+            assert_eq!(
+                concat!(
+                    "Outer { name: \"outer\".to_owned(), inner: Inner { values: vec![1, 2] }, } = \
+                     Outer {\n",
+                    "debug:     name: \"outer\",\n",
+                    "debug:     inner: Inner {\n",
+                    "debug:         values: [\n",
+                    "debug:             1,\n",
+                    "debug:             2,\n",
+                    "debug:         ],\n",
+                    "debug:     },\n",
+                    "debug: }\n",
+                ),
+                value
+            );
+        }
+    }
+}
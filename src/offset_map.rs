@@ -0,0 +1,101 @@
+//! Mapping the offsets of lines in the output back to the input.
+
+use std::io::Write;
+
+use crate::{
+    records::Records,
+    PrefixWriter,
+};
+
+impl<W: Write> PrefixWriter<W> {
+    /// Record for every line the offset of its first byte in the input
+    /// and the offset where it starts in the output, including its
+    /// prefix, see [`PrefixWriter::offset_map`]. This maps positions in
+    /// the prefixed output back to the input, for example for an index.
+    /// Offsets count all bytes ever written to and by the writer. Lines
+    /// that are buffered, like sorted lines, are mapped to the input
+    /// offset the writer was at when they were written.
+    #[must_use]
+    pub fn with_offset_map(self, offset_map: bool) -> Self {
+        Self {
+            offset_map: offset_map.then(Vec::new),
+            ..self
+        }
+    }
+
+    /// Pairs of input and output offsets of all lines written so far, in
+    /// the order they were written, see [`PrefixWriter::with_offset_map`].
+    /// Empty when the offset map is disabled.
+    #[must_use]
+    pub fn offset_map(&self) -> &[(u64, u64)] {
+        self.offset_map.as_deref().unwrap_or_default()
+    }
+
+    /// Offsets of the line about to be written, which starts `line_start`
+    /// bytes into its rendered bytes, or `None` when they are not
+    /// recorded.
+    pub(crate) fn line_offsets(&self, line_start: usize) -> Option<(u64, u64)> {
+        self.offset_map.as_ref()?;
+
+        let pending = self.records.as_ref().map_or(0, Records::pending_len);
+
+        Some((
+            self.input_offset,
+            self.output_offset + (pending + line_start) as u64,
+        ))
+    }
+
+    /// Record the offsets of a line once it was written.
+    pub(crate) fn record_offsets(&mut self, offsets: Option<(u64, u64)>) {
+        if let Some((ref mut offset_map, offsets)) = self.offset_map.as_mut().zip(offsets) {
+            offset_map.push(offsets);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn offsets() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_offset_map(true);
+
+            writer.write_all(b"first\nsec").unwrap();
+            writer.write_all(b"ond\r\n\nthird\n").unwrap();
+
+            assert_eq!([(0, 0), (6, 14), (14, 29), (15, 30)], writer.offset_map());
+
+            let input = b"first\nsecond\r\n\nthird\n";
+            let output = writer.get_ref();
+            for &(input_offset, output_offset) in writer.offset_map() {
+                let input = &input[usize::try_from(input_offset).unwrap()..];
+                let output = &output[usize::try_from(output_offset).unwrap()..];
+
+                let content = output.strip_prefix(PREFIX.as_bytes()).unwrap_or(output);
+                assert_eq!(input[0], content[0]);
+            }
+        }
+
+        #[test]
+        fn disabled() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+            writer.write_all(b"first\n").unwrap();
+
+            assert!(writer.offset_map().is_empty());
+        }
+    }
+}
@@ -0,0 +1,301 @@
+//! Reflowing paragraphs of prose with the prefix on their first line.
+
+use std::io::Write;
+
+use crate::{
+    width::display_width,
+    PrefixWriteError,
+    PrefixWriter,
+    Unit,
+};
+
+/// Fill the words into lines of at most `width` after the prefix of
+/// `prefix_width` on the first line and an indentation of `indent_width`
+/// on the others, all measured in the unit. A word wider than its line
+/// gets a line of its own.
+fn reflow(
+    words: &str,
+    unit: Unit,
+    width: usize,
+    prefix_width: usize,
+    indent_width: usize,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in words.split_whitespace() {
+        let taken = if lines.is_empty() {
+            prefix_width
+        } else {
+            indent_width
+        };
+        let available = width.saturating_sub(taken);
+
+        if !line.is_empty() && unit.measure(&line) + 1 + unit.measure(word) > available {
+            lines.push(std::mem::take(&mut line));
+        }
+
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Reflow paragraphs, runs of lines that are not blank, to lines of at
+    /// most `width` including the prefix. Only the first line of a
+    /// paragraph is prefixed, the following lines are indented with
+    /// spaces as wide as the prefix. A paragraph is written once a blank
+    /// line ends it or the writer is flushed, every line of it ends with
+    /// the terminator. Reflowed lines get no other options applied.
+    #[must_use]
+    pub fn with_paragraph_reflow(self, width: usize) -> Self {
+        Self {
+            paragraph_reflow: Some(width),
+            ..self
+        }
+    }
+
+    /// Prefix the first line of every paragraph, the first line and every
+    /// line after a blank line, with `first_of_paragraph` and the other
+    /// lines with `continuation`, instead of the prefix. A line only
+    /// counts as blank once it was written, so a paragraph can be split
+    /// between writes.
+    #[must_use]
+    pub fn with_paragraph_prefixes(self, first_of_paragraph: String, continuation: String) -> Self {
+        Self {
+            paragraph_prefixes: Some((first_of_paragraph, continuation)),
+            ..self
+        }
+    }
+
+    /// Add the line to the paragraph, or write the paragraph and the
+    /// blank line ending it.
+    pub(crate) fn reflow_line(
+        &mut self,
+        content: &str,
+        original_terminator: &str,
+        terminator: &str,
+        is_last: bool,
+    ) -> Result<(), PrefixWriteError> {
+        if content.trim().is_empty() {
+            self.write_paragraph()?;
+            return self.emit_logical_line(content, original_terminator, terminator, is_last);
+        }
+
+        let paragraph = self.paragraph.get_or_insert_with(String::new);
+        paragraph.push(' ');
+        paragraph.push_str(content);
+
+        Ok(())
+    }
+
+    /// Write the collected paragraph reflowed. The lines that were not
+    /// written yet are kept when writing fails and written first by the
+    /// next try.
+    pub(crate) fn write_paragraph(&mut self) -> Result<(), PrefixWriteError> {
+        if let (Some(width), Some(paragraph)) = (self.paragraph_reflow, self.paragraph.take()) {
+            let prefix = self.next_prefix(&paragraph, true).into_owned();
+            let indent = " ".repeat(display_width(&prefix));
+
+            let unit = self.width_unit;
+            let lines = reflow(
+                &paragraph,
+                unit,
+                width,
+                unit.measure(&prefix),
+                unit.measure(&indent),
+            );
+            for (index, line) in lines.into_iter().enumerate() {
+                let start = if index == 0 { &prefix } else { &indent };
+                self.paragraph_lines
+                    .push((format!("{start}{line}"), line.len()));
+            }
+        }
+
+        let terminator = self.terminator();
+        let lines = std::mem::take(&mut self.paragraph_lines);
+        for (index, (line, payload_len)) in lines.iter().enumerate() {
+            let mut rendered = self.start_rendered();
+            rendered.push_str(line);
+            rendered.push_str(&terminator);
+
+            if let Err(err) =
+                self.write_rendered(rendered.as_bytes(), *payload_len, terminator.len())
+            {
+                self.paragraph_lines = lines;
+                self.paragraph_lines.drain(..index);
+
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::reflow;
+    use crate::{
+        PrefixWriter,
+        Unit,
+    };
+
+    const PREFIX: &str = "note: ";
+
+    /// Fails the write numbered `fail_at`, counting from 0, once.
+    #[derive(Debug, Default)]
+    struct FailingWriter {
+        data: Vec<u8>,
+        writes: usize,
+        fail_at: Option<usize>,
+    }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let write = self.writes;
+            self.writes += 1;
+
+            if self.fail_at == Some(write) {
+                self.fail_at = None;
+                return Err(std::io::Error::other("failing"));
+            }
+
+            self.data.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            reflow,
+            FailingWriter,
+            PrefixWriter,
+            Unit,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn hanging_indent() {
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_paragraph_reflow(24);
+
+            writer
+                .write_all(b"The quick brown fox\njumps over the lazy dog and\nkeeps running.\n")
+                .unwrap();
+            writer.write_all(b"\nSecond paragraph.\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "note: The quick brown\n",
+                    "      fox jumps over the\n",
+                    "      lazy dog and keeps\n",
+                    "      running.\n",
+                    "\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+
+            writer.flush().unwrap();
+
+            assert_eq!(
+                concat!(
+                    "note: The quick brown\n",
+                    "      fox jumps over the\n",
+                    "      lazy dog and keeps\n",
+                    "      running.\n",
+                    "\n",
+                    "note: Second paragraph.\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[cfg(feature = "unicode")]
+        #[test]
+        fn wide_prefix() {
+            let output = |unit| {
+                let mut writer = PrefixWriter::new("注意: ".to_owned(), Vec::new())
+                    .with_width_unit(unit)
+                    .with_paragraph_reflow(20);
+
+                writer.write_all(b"aaa bbb ccc ddd eee\n").unwrap();
+                writer.flush().unwrap();
+
+                String::from_utf8_lossy(writer.get_ref()).into_owned()
+            };
+
+            assert_eq!("注意: aaa bbb ccc\n      ddd eee\n", output(Unit::Columns));
+            assert_eq!("注意: aaa bbb ccc ddd\n      eee\n", output(Unit::Chars));
+        }
+
+        #[test]
+        fn write_error() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), FailingWriter::default())
+                .with_paragraph_reflow(16);
+
+            writer.write_all(b"one two three four five six\n").unwrap();
+
+            writer.get_mut().fail_at = Some(1);
+            writer.flush().unwrap_err();
+            writer.get_mut().fail_at = Some(3);
+            writer.flush().unwrap_err();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                concat!("note: one two\n", "      three four\n", "      five six\n"),
+                String::from_utf8_lossy(&writer.get_ref().data)
+            );
+        }
+
+        #[test]
+        fn long_words() {
+            assert_eq!(
+                vec!["a", "abcdefghij", "b c"],
+                reflow("a abcdefghij b c", Unit::Chars, 6, 2, 2)
+            );
+        }
+
+        #[test]
+        fn paragraph_prefixes() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_paragraph_prefixes("¶ ".to_owned(), "  ".to_owned());
+
+            writer.write_all(b"first paragraph\ncontinues\n").unwrap();
+            writer.write_all(b"\n  \nsecond ").unwrap();
+            writer
+                .write_all(b"paragraph\nand more\n\n\nthird\n")
+                .unwrap();
+
+            assert_eq!(
+                concat!(
+                    "¶ first paragraph\n",
+                    "  continues\n",
+                    "\n",
+                    "¶   \n",
+                    "¶ second paragraph\n",
+                    "  and more\n",
+                    "\n",
+                    "\n",
+                    "¶ third\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
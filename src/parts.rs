@@ -0,0 +1,356 @@
+//! Taking a [`PrefixWriter`] apart and putting it back together, for
+//! example to continue a stream in another process.
+
+use std::{
+    borrow::Cow,
+    io::Write,
+};
+
+#[cfg(feature = "encoding")]
+use crate::Encoding;
+use crate::{
+    rate_limit::RateLimit,
+    sanitize::Sanitizer,
+    FieldSource,
+    IndentPlacement,
+    PrefixEllipsis,
+    PrefixWriter,
+    Sanitization,
+    UnfinishedLine,
+    Unit,
+};
+
+/// Configuration and state of a [`PrefixWriter`] without its writer, see
+/// [`PrefixWriter::into_parts`]. Serializable with the `serde` feature.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrefixState {
+    prefix: String,
+
+    message_mode: bool,
+    message_terminator: bool,
+    input_delimiter: u8,
+    output_terminator: Cow<'static, str>,
+    #[cfg(feature = "encoding")]
+    line_encoder: Option<Encoding>,
+    marker_prefix: Option<String>,
+    line_suffix: Option<String>,
+    prefix_directive: Option<String>,
+    record_separator: Option<Vec<u8>>,
+    inplace_remainder: bool,
+    carriage_return_prefix: bool,
+    passthrough: bool,
+    zebra: Option<(String, String)>,
+    prefix_first_n: Option<u64>,
+    width_unit: Unit,
+    indent_placement: IndentPlacement,
+    normalize_indent: Option<usize>,
+    max_prefix_width: Option<(usize, PrefixEllipsis)>,
+    prefix_column: Option<usize>,
+    wrap_width: Option<usize>,
+    prefix_overflow_wrap: Option<usize>,
+    wrap_continuation: Option<char>,
+    terminal: Option<bool>,
+    running_count: Option<usize>,
+    word_count_prefix: bool,
+    intra_write_index: Option<String>,
+    #[cfg(feature = "tracing")]
+    span_indent: Option<String>,
+    silence_broken_pipe: bool,
+    logfmt: Option<Vec<(String, FieldSource)>>,
+    timestamp_prefix: bool,
+    #[cfg(feature = "highlight")]
+    colors: bool,
+    mute_summary: bool,
+    markdown_blockquote: bool,
+    prefix_empty_lines: bool,
+    squeeze_empty_lines: bool,
+    trailing_newline: bool,
+    delta_time_prefix: bool,
+    join_continuations: bool,
+    collapse_continuation_indent: bool,
+    max_joined_length: usize,
+    paragraph_reflow: Option<usize>,
+    paragraph_prefixes: Option<(String, String)>,
+    rate_limit: Option<u32>,
+    sanitization: Option<Sanitization>,
+
+    remainder: Option<String>,
+    remainder_displayed: bool,
+    held_continuation: Option<(String, String)>,
+    paragraph: Option<String>,
+    paragraph_lines: Vec<(String, usize)>,
+    unfinished_line: Option<UnfinishedLine>,
+    after_blank_line: bool,
+    last_line_empty: bool,
+    raw_line_open: bool,
+    lines_written: u64,
+    input_offset: u64,
+    output_offset: u64,
+    offset_map: Option<Vec<(u64, u64)>>,
+    length_histogram: Option<(Vec<usize>, Vec<u64>)>,
+    broken_pipe: bool,
+    muted: bool,
+    muted_lines: u64,
+    dropped_lines: u64,
+    write_errors: u64,
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Take the writer apart into its state and its writer without
+    /// flushing, so an incomplete line is part of the state and is
+    /// completed once the writer is put back together with
+    /// [`PrefixWriter::from_parts`].
+    ///
+    /// The prefix iterator, the prefix context, the raw line callback, the
+    /// line transformation, the highlighter, the sort order, the
+    /// sampling, the verbosity, the alert, the binary detection, the
+    /// record detection, the blank line sink, the resource usage, the
+    /// durability, the retry policy, the sleep, the clock and the
+    /// timestamp clock can not be part of the state and have to be set
+    /// again. An alert that is set again is armed, even if it fired
+    /// before. Of the rate limit only the limit is
+    /// kept, of the sanitization only its options. Lines buffered for
+    /// sorting or collected for an atomic record are dropped, flush before
+    /// taking the writer apart.
+    pub fn into_parts(self) -> (PrefixState, W) {
+        (self.state(), self.writer)
+    }
+
+    /// The state of the writer as [`PrefixWriter::into_parts`] takes it
+    /// apart, without taking the writer apart.
+    pub(crate) fn state(&self) -> PrefixState {
+        PrefixState {
+            prefix: self.prefix.clone(),
+
+            message_mode: self.message_mode,
+            message_terminator: self.message_terminator,
+            input_delimiter: self.input_delimiter,
+            output_terminator: self.output_terminator.clone(),
+            #[cfg(feature = "encoding")]
+            line_encoder: self.line_encoder,
+            marker_prefix: self.marker_prefix.clone(),
+            line_suffix: self.line_suffix.clone(),
+            prefix_directive: self.prefix_directive.clone(),
+            record_separator: self.record_separator.clone(),
+            inplace_remainder: self.inplace_remainder,
+            carriage_return_prefix: self.carriage_return_prefix,
+            passthrough: self.passthrough,
+            zebra: self.zebra.clone(),
+            prefix_first_n: self.prefix_first_n,
+            width_unit: self.width_unit,
+            indent_placement: self.indent_placement,
+            normalize_indent: self.normalize_indent,
+            max_prefix_width: self.max_prefix_width,
+            prefix_column: self.prefix_column,
+            wrap_width: self.wrap_width,
+            prefix_overflow_wrap: self.prefix_overflow_wrap,
+            wrap_continuation: self.wrap_continuation,
+            terminal: self.terminal,
+            running_count: self.running_count,
+            word_count_prefix: self.word_count_prefix,
+            intra_write_index: self.intra_write_index.clone(),
+            #[cfg(feature = "tracing")]
+            span_indent: self.span_indent.clone(),
+            silence_broken_pipe: self.silence_broken_pipe,
+            logfmt: self.logfmt.clone(),
+            timestamp_prefix: self.timestamp_prefix,
+            #[cfg(feature = "highlight")]
+            colors: self.colors,
+            mute_summary: self.mute_summary,
+            markdown_blockquote: self.markdown_blockquote,
+            prefix_empty_lines: self.prefix_empty_lines,
+            squeeze_empty_lines: self.squeeze_empty_lines,
+            trailing_newline: self.trailing_newline,
+            delta_time_prefix: self.delta_time_prefix,
+            join_continuations: self.join_continuations,
+            collapse_continuation_indent: self.collapse_continuation_indent,
+            max_joined_length: self.max_joined_length,
+            paragraph_reflow: self.paragraph_reflow,
+            paragraph_prefixes: self.paragraph_prefixes.clone(),
+            rate_limit: self.rate_limit.as_ref().map(RateLimit::max_lines),
+            sanitization: self.sanitizer.as_ref().map(Sanitizer::sanitization),
+
+            remainder: self.remainder.clone(),
+            remainder_displayed: self.remainder_displayed,
+            held_continuation: self.held_continuation.clone(),
+            paragraph: self.paragraph.clone(),
+            paragraph_lines: self.paragraph_lines.clone(),
+            unfinished_line: self.unfinished_line.clone(),
+            after_blank_line: self.after_blank_line,
+            last_line_empty: self.last_line_empty,
+            raw_line_open: self.raw_line_open,
+            lines_written: self.lines_written,
+            input_offset: self.input_offset,
+            output_offset: self.output_offset,
+            offset_map: self.offset_map.clone(),
+            length_histogram: self.length_histogram.clone(),
+            broken_pipe: self.broken_pipe,
+            muted: self.muted,
+            muted_lines: self.muted_lines,
+            dropped_lines: self.dropped_lines,
+            write_errors: self.write_errors,
+        }
+    }
+
+    /// Put a writer taken apart with [`PrefixWriter::into_parts`] back
+    /// together, continuing the stream where it was left off.
+    pub fn from_parts(state: PrefixState, writer: W) -> Self {
+        let mut prefix_writer = Self::new(state.prefix, writer);
+
+        prefix_writer.message_mode = state.message_mode;
+        prefix_writer.message_terminator = state.message_terminator;
+        prefix_writer.input_delimiter = state.input_delimiter;
+        prefix_writer.output_terminator = state.output_terminator;
+        #[cfg(feature = "encoding")]
+        {
+            prefix_writer.line_encoder = state.line_encoder;
+        }
+        prefix_writer.marker_prefix = state.marker_prefix;
+        prefix_writer.line_suffix = state.line_suffix;
+        prefix_writer.prefix_directive = state.prefix_directive;
+        prefix_writer.record_separator = state.record_separator;
+        prefix_writer.inplace_remainder = state.inplace_remainder;
+        prefix_writer.carriage_return_prefix = state.carriage_return_prefix;
+        prefix_writer.passthrough = state.passthrough;
+        prefix_writer.zebra = state.zebra;
+        prefix_writer.prefix_first_n = state.prefix_first_n;
+        prefix_writer.width_unit = state.width_unit;
+        prefix_writer.indent_placement = state.indent_placement;
+        prefix_writer.normalize_indent = state.normalize_indent;
+        prefix_writer.max_prefix_width = state.max_prefix_width;
+        prefix_writer.prefix_column = state.prefix_column;
+        prefix_writer.wrap_width = state.wrap_width;
+        prefix_writer.prefix_overflow_wrap = state.prefix_overflow_wrap;
+        prefix_writer.wrap_continuation = state.wrap_continuation;
+        prefix_writer.terminal = state.terminal;
+        prefix_writer.running_count = state.running_count;
+        prefix_writer.word_count_prefix = state.word_count_prefix;
+        prefix_writer.intra_write_index = state.intra_write_index;
+        #[cfg(feature = "tracing")]
+        {
+            prefix_writer.span_indent = state.span_indent;
+        }
+        prefix_writer.silence_broken_pipe = state.silence_broken_pipe;
+        prefix_writer.logfmt = state.logfmt;
+        prefix_writer.timestamp_prefix = state.timestamp_prefix;
+        #[cfg(feature = "highlight")]
+        {
+            prefix_writer.colors = state.colors;
+        }
+        prefix_writer.mute_summary = state.mute_summary;
+        prefix_writer.markdown_blockquote = state.markdown_blockquote;
+        prefix_writer.prefix_empty_lines = state.prefix_empty_lines;
+        prefix_writer.squeeze_empty_lines = state.squeeze_empty_lines;
+        prefix_writer.trailing_newline = state.trailing_newline;
+        prefix_writer.delta_time_prefix = state.delta_time_prefix;
+        prefix_writer.join_continuations = state.join_continuations;
+        prefix_writer.collapse_continuation_indent = state.collapse_continuation_indent;
+        prefix_writer.max_joined_length = state.max_joined_length;
+        prefix_writer.paragraph_reflow = state.paragraph_reflow;
+        prefix_writer.paragraph_prefixes = state.paragraph_prefixes;
+        prefix_writer.rate_limit = state.rate_limit.map(RateLimit::new);
+        prefix_writer.sanitizer = state.sanitization.map(Sanitizer::new);
+
+        prefix_writer.remainder = state.remainder;
+        prefix_writer.remainder_displayed = state.remainder_displayed;
+        prefix_writer.held_continuation = state.held_continuation;
+        prefix_writer.paragraph = state.paragraph;
+        prefix_writer.paragraph_lines = state.paragraph_lines;
+        prefix_writer.unfinished_line = state.unfinished_line;
+        prefix_writer.after_blank_line = state.after_blank_line;
+        prefix_writer.last_line_empty = state.last_line_empty;
+        prefix_writer.raw_line_open = state.raw_line_open;
+        prefix_writer.lines_written = state.lines_written;
+        prefix_writer.input_offset = state.input_offset;
+        prefix_writer.output_offset = state.output_offset;
+        prefix_writer.offset_map = state.offset_map;
+        prefix_writer.length_histogram = state.length_histogram;
+        prefix_writer.broken_pipe = state.broken_pipe;
+        prefix_writer.muted = state.muted;
+        prefix_writer.muted_lines = state.muted_lines;
+        prefix_writer.dropped_lines = state.dropped_lines;
+        prefix_writer.write_errors = state.write_errors;
+
+        prefix_writer
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+    const INPUT: &[u8] = b"first\nsec";
+    const REST: &[u8] = b"ond\nthird\nfourth\n";
+
+    fn configured(writer: Vec<u8>) -> PrefixWriter<Vec<u8>> {
+        PrefixWriter::new(PREFIX.to_owned(), writer)
+            .with_zebra("1".to_owned(), "2".to_owned())
+            .with_prefix_first_n(3)
+    }
+
+    fn uninterrupted() -> Vec<u8> {
+        let mut writer = configured(Vec::new());
+
+        writer.write_all(INPUT).unwrap();
+        writer.write_all(REST).unwrap();
+        writer.flush().unwrap();
+
+        writer.get_ref().clone()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            configured,
+            uninterrupted,
+            PrefixWriter,
+            Write,
+            INPUT,
+            REST,
+        };
+
+        #[test]
+        fn round_trip() {
+            let mut writer = configured(Vec::new());
+            writer.write_all(INPUT).unwrap();
+
+            let (state, buffer) = writer.into_parts();
+
+            let mut writer = PrefixWriter::from_parts(state, buffer);
+            writer.write_all(REST).unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                String::from_utf8_lossy(&uninterrupted()),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn round_trip_serde() {
+            let mut writer = configured(Vec::new());
+            writer.write_all(INPUT).unwrap();
+
+            let (state, buffer) = writer.into_parts();
+            let serialized = serde_json::to_string(&state).unwrap();
+            let deserialized = serde_json::from_str(&serialized).unwrap();
+            assert_eq!(state, deserialized);
+
+            let mut writer = PrefixWriter::from_parts(deserialized, buffer);
+            writer.write_all(REST).unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                String::from_utf8_lossy(&uninterrupted()),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
@@ -0,0 +1,295 @@
+//! Splitting the lines of many keys into one file per key.
+
+use std::{
+    collections::VecDeque,
+    fs::{
+        File,
+        OpenOptions,
+    },
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        Arc,
+        Mutex,
+        MutexGuard,
+        PoisonError,
+    },
+};
+
+/// Placeholder in the file name template replaced by the key.
+const KEY_PLACEHOLDER: &str = "{key}";
+
+/// Writes the lines of every key into its own file in a directory, like
+/// `logs/web.log` and `logs/db.log`. Files are opened for appending when
+/// the first line of their key arrives. Only a limited number of files is
+/// kept open, when another one is needed the least recently used one is
+/// flushed and closed and opened again on its next line.
+///
+/// Every key gets a [`KeyWriter`] from [`PerKeyFiles::key_writer`], which
+/// is usually wrapped in a [`crate::PrefixWriter`], so the options of the
+/// prefixing stay the same for all keys.
+#[derive(Debug, Clone)]
+pub struct PerKeyFiles {
+    directory: PathBuf,
+    template: String,
+    state: Arc<Mutex<State>>,
+}
+
+/// Writes to the file of one key of a [`PerKeyFiles`].
+#[derive(Debug)]
+pub struct KeyWriter {
+    state: Arc<Mutex<State>>,
+    path: PathBuf,
+}
+
+#[derive(Debug)]
+struct State {
+    max_open: usize,
+
+    /// Open files, the least recently used first.
+    open: VecDeque<(PathBuf, File)>,
+}
+
+impl PerKeyFiles {
+    /// Create a new [`PerKeyFiles`] writing into the directory. The file
+    /// name of a key is the template with `{key}` replaced by the key,
+    /// like `{key}.log`. At most 64 files are open at the same time.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of creating the directory.
+    pub fn new(directory: impl AsRef<Path>, template: String) -> std::io::Result<Self> {
+        std::fs::create_dir_all(directory.as_ref())?;
+
+        Ok(Self {
+            directory: directory.as_ref().to_owned(),
+            template,
+            state: Arc::new(Mutex::new(State {
+                max_open: 64,
+                open: VecDeque::new(),
+            })),
+        })
+    }
+
+    /// Keep at most `max_open` files open at the same time, at least one.
+    #[must_use]
+    pub fn with_max_open(self, max_open: usize) -> Self {
+        self.lock().max_open = max_open.max(1);
+
+        self
+    }
+
+    /// Create the writer for the lines of the key. Characters of the key
+    /// that are not allowed or special in file names are replaced with
+    /// `_`, so keys can not point outside the directory. Keys that are
+    /// the same after the replacement share their file.
+    #[must_use]
+    pub fn key_writer(&self, key: &str) -> KeyWriter {
+        let file_name = self.template.replace(KEY_PLACEHOLDER, &sanitize(key));
+
+        KeyWriter {
+            state: Arc::clone(&self.state),
+            path: self.directory.join(file_name),
+        }
+    }
+
+    /// Flush all open files.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of flushing a file.
+    pub fn finish(&self) -> std::io::Result<()> {
+        self.lock()
+            .open
+            .iter_mut()
+            .try_for_each(|(_, file)| file.flush())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl State {
+    /// Get the open file of the path, opening it and closing the least
+    /// recently used file if needed.
+    fn file(&mut self, path: &Path) -> std::io::Result<&mut File> {
+        if let Some(index) = self.open.iter().position(|(open, _)| open == path) {
+            if let Some(used) = self.open.remove(index) {
+                self.open.push_back(used);
+            }
+        } else {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+            while self.open.len() >= self.max_open {
+                if let Some((_, mut closed)) = self.open.pop_front() {
+                    closed.flush()?;
+                }
+            }
+
+            self.open.push_back((path.to_owned(), file));
+        }
+
+        let last = self.open.len() - 1;
+        Ok(&mut self.open[last].1)
+    }
+}
+
+impl Write for KeyWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .file(&self.path)?
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match state.open.iter_mut().find(|(open, _)| *open == self.path) {
+            Some((_, file)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Make the key usable as part of a file name.
+fn sanitize(key: &str) -> String {
+    let mut sanitized = key
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() || matches!(character, '-' | '_' | '.') {
+                character
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    if sanitized.is_empty() || sanitized.starts_with('.') {
+        sanitized.replace_range(..sanitized.len().min(1), "_");
+    }
+
+    sanitized
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::Write,
+        path::PathBuf,
+    };
+
+    use super::PerKeyFiles;
+    use crate::PrefixWriter;
+
+    /// Empty directory for the test, removed again by [`TempDir::drop`].
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "prefix_writer-per_key_files-{name}-{}",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+
+            Self(path)
+        }
+
+        fn read(&self, file_name: &str) -> String {
+            std::fs::read_to_string(self.0.join(file_name)).unwrap()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            PerKeyFiles,
+            PrefixWriter,
+            TempDir,
+            Write,
+        };
+
+        #[test]
+        fn routing() {
+            let directory = TempDir::new("routing");
+            let files = PerKeyFiles::new(&directory.0, "{key}.log".to_owned()).unwrap();
+
+            let mut web = PrefixWriter::new("web: ".to_owned(), files.key_writer("web"));
+            let mut db = PrefixWriter::new("db: ".to_owned(), files.key_writer("db"));
+
+            web.write_all(b"GET /\n").unwrap();
+            db.write_all(b"SELECT 1\n").unwrap();
+            web.write_all(b"GET /about\nPOST").unwrap();
+            web.flush().unwrap();
+            files.finish().unwrap();
+
+            assert_eq!(
+                "web: GET /\nweb: GET /about\nweb: POST",
+                directory.read("web.log")
+            );
+            assert_eq!("db: SELECT 1\n", directory.read("db.log"));
+        }
+
+        #[test]
+        fn sanitization() {
+            let directory = TempDir::new("sanitization");
+            let files = PerKeyFiles::new(&directory.0, "app-{key}.log".to_owned()).unwrap();
+
+            for key in ["../../etc/passwd", "", "web v2.1", "üser"] {
+                writeln!(files.key_writer(key), "{key}").unwrap();
+            }
+            files.finish().unwrap();
+
+            let mut names = std::fs::read_dir(&directory.0)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect::<Vec<_>>();
+            names.sort();
+
+            assert_eq!(
+                vec![
+                    "app-_._.._etc_passwd.log",
+                    "app-_.log",
+                    "app-_ser.log",
+                    "app-web_v2.1.log",
+                ],
+                names
+            );
+            assert_eq!("\n", directory.read("app-_.log"));
+        }
+
+        #[test]
+        fn handle_cap() {
+            let directory = TempDir::new("handle_cap");
+            let files = PerKeyFiles::new(&directory.0, "{key}".to_owned())
+                .unwrap()
+                .with_max_open(2);
+
+            let mut writers = ["a", "b", "c"].map(|key| files.key_writer(key));
+            for round in 0..3 {
+                for writer in &mut writers {
+                    writeln!(writer, "{round}").unwrap();
+                    assert!(files.lock().open.len() <= 2);
+                }
+            }
+            files.finish().unwrap();
+
+            for key in ["a", "b", "c"] {
+                assert_eq!("0\n1\n2\n", directory.read(key));
+            }
+        }
+    }
+}
@@ -0,0 +1,362 @@
+//! Reusing the buffers of many short-lived writers.
+
+use std::{
+    io::Write,
+    ops::{
+        Deref,
+        DerefMut,
+    },
+    sync::{
+        Arc,
+        Mutex,
+        PoisonError,
+    },
+};
+
+use crate::{
+    PrefixWriter,
+    PrefixWriterBuilder,
+};
+
+/// Buffers shared by a [`BufferPool`] and its clones.
+#[derive(Debug)]
+struct Buffers {
+    free: Vec<String>,
+    pooled_bytes: usize,
+    max_bytes: usize,
+}
+
+/// Recycles the buffers of writers created with [`BufferPool::writer`],
+/// or [`BufferPool::build`], for programs that create and drop many
+/// writers, like one per test case. The prefix and the buffers of the
+/// incomplete line and of the rendered lines are taken from the pool and
+/// given back once the writer is dropped, otherwise the writers are the
+/// same as those of [`PrefixWriter::new`] and
+/// [`PrefixWriterBuilder::build`]. Clones share the same pool.
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    buffers: Arc<Mutex<Buffers>>,
+}
+
+/// [`PrefixWriter`] with buffers from a [`BufferPool`] that gives them
+/// back when dropped. Dereferences to the [`PrefixWriter`], options are
+/// set on the builder given to [`BufferPool::build`]. Like the
+/// [`PrefixWriter`] it does not flush when dropped, use
+/// [`PooledPrefixWriter::finish`] to write the incomplete line.
+#[derive(Debug)]
+pub struct PooledPrefixWriter<W: Write> {
+    writer: Option<PrefixWriter<W>>,
+    pool: BufferPool,
+}
+
+impl BufferPool {
+    /// Create a new [`BufferPool`] keeping buffers until they add up to
+    /// `max_bytes` of capacity. Buffers given back beyond that are freed.
+    #[must_use]
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            buffers: Arc::new(Mutex::new(Buffers {
+                free: Vec::new(),
+                pooled_bytes: 0,
+                max_bytes,
+            })),
+        }
+    }
+
+    /// Create a new writer using the prefix for prefixing lines and the
+    /// writer for writing the output, with its buffers from the pool.
+    pub fn writer<W: Write>(&self, prefix: &str, writer: W) -> PooledPrefixWriter<W> {
+        let mut prefix_buffer = self.lock().take();
+        prefix_buffer.push_str(prefix);
+
+        self.pooled(PrefixWriter::new(prefix_buffer, writer))
+    }
+
+    /// Build a writer with the configuration of the builder writing the
+    /// output to the writer, with the buffers of its lines from the pool.
+    /// The prefix of the builder is given to the pool once the writer is
+    /// dropped.
+    pub fn build<W: Write>(
+        &self,
+        builder: PrefixWriterBuilder,
+        writer: W,
+    ) -> PooledPrefixWriter<W> {
+        self.pooled(builder.build(writer))
+    }
+
+    /// Give the writer buffers from the pool.
+    fn pooled<W: Write>(&self, mut prefix_writer: PrefixWriter<W>) -> PooledPrefixWriter<W> {
+        {
+            let mut buffers = self.lock();
+            prefix_writer.spare_remainder = Some(buffers.take());
+            prefix_writer.spare_rendered = Some(buffers.take());
+        }
+
+        PooledPrefixWriter {
+            writer: Some(prefix_writer),
+            pool: self.clone(),
+        }
+    }
+
+    /// Capacity in bytes of the buffers in the pool.
+    #[must_use]
+    pub fn pooled_bytes(&self) -> usize {
+        self.lock().pooled_bytes
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Buffers> {
+        self.buffers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Buffers {
+    /// Take an empty buffer from the pool, or a new one if it is empty.
+    fn take(&mut self) -> String {
+        let buffer = self.free.pop().unwrap_or_default();
+        self.pooled_bytes -= buffer.capacity();
+
+        buffer
+    }
+
+    /// Clear the buffer and keep it unless the pool is full.
+    fn give(&mut self, mut buffer: String) {
+        if buffer.capacity() == 0 || self.pooled_bytes + buffer.capacity() > self.max_bytes {
+            return;
+        }
+
+        buffer.clear();
+        self.pooled_bytes += buffer.capacity();
+        self.free.push(buffer);
+    }
+}
+
+impl<W: Write> PooledPrefixWriter<W> {
+    /// Flush the incomplete line, give the buffers back to the pool and
+    /// return the writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the writer. The buffers are given back
+    /// either way.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        let result = self.flush();
+
+        let mut writer = self.take_writer();
+        self.give_back(&mut writer);
+
+        result.map(|()| writer.writer)
+    }
+
+    fn take_writer(&mut self) -> PrefixWriter<W> {
+        match self.writer.take() {
+            Some(writer) => writer,
+            None => unreachable!("writer is only taken when finishing or dropping"),
+        }
+    }
+
+    /// Give the buffers of the writer back to the pool.
+    fn give_back(&self, writer: &mut PrefixWriter<W>) {
+        let mut buffers = self.pool.lock();
+
+        buffers.give(std::mem::take(&mut writer.prefix));
+
+        if let Some(remainder) = writer.remainder.take() {
+            buffers.give(remainder);
+        }
+
+        if let Some(spare_remainder) = writer.spare_remainder.take() {
+            buffers.give(spare_remainder);
+        }
+
+        if let Some(spare_rendered) = writer.spare_rendered.take() {
+            buffers.give(spare_rendered);
+        }
+    }
+}
+
+impl<W: Write> Deref for PooledPrefixWriter<W> {
+    type Target = PrefixWriter<W>;
+
+    fn deref(&self) -> &Self::Target {
+        match self.writer {
+            Some(ref writer) => writer,
+            None => unreachable!("writer is only taken when finishing or dropping"),
+        }
+    }
+}
+
+impl<W: Write> DerefMut for PooledPrefixWriter<W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self.writer {
+            Some(ref mut writer) => writer,
+            None => unreachable!("writer is only taken when finishing or dropping"),
+        }
+    }
+}
+
+impl<W: Write> Write for PooledPrefixWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.deref_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.deref_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for PooledPrefixWriter<W> {
+    fn drop(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            self.give_back(&mut writer);
+        }
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Keep the buffer of a remainder that was written for the next
+    /// remainder if the writer is from a [`BufferPool`].
+    pub(crate) fn recycle_remainder(&mut self, remainder: Option<String>) {
+        if let (Some(spare_remainder), Some(mut remainder)) =
+            (self.spare_remainder.as_mut(), remainder)
+        {
+            remainder.clear();
+            *spare_remainder = remainder;
+        }
+    }
+
+    /// Start rendering the next line like
+    /// [`PrefixWriter::start_rendered`], in the buffer of the last line
+    /// if the writer is from a [`BufferPool`].
+    pub(crate) fn take_rendered(&mut self) -> String {
+        let Some(mut rendered) = self.spare_rendered.as_mut().map(std::mem::take) else {
+            return self.start_rendered();
+        };
+
+        self.push_line_start(&mut rendered);
+
+        rendered
+    }
+
+    /// Keep the buffer of a line that was written for the next line if
+    /// the writer is from a [`BufferPool`].
+    pub(crate) fn recycle_rendered(&mut self, mut rendered: String) {
+        if let Some(ref mut spare_rendered) = self.spare_rendered {
+            rendered.clear();
+            *spare_rendered = rendered;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::BufferPool;
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+    const WRITES: [&str; 4] = ["first\nsec", "ond\n\nthi", "rd", "\r\nincomplete"];
+
+    /// Write all writes and flush.
+    fn run(writer: &mut impl Write) {
+        for write in WRITES {
+            writer.write_all(write.as_bytes()).unwrap();
+        }
+
+        writer.flush().unwrap();
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            run,
+            BufferPool,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn same_as_unpooled() {
+            let mut unpooled = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+            run(&mut unpooled);
+
+            let pool = BufferPool::new(1024);
+            for _ in 0..3 {
+                let mut pooled = pool.writer(PREFIX, Vec::new());
+                run(&mut pooled);
+
+                assert_eq!(unpooled.get_ref(), &pooled.finish().unwrap());
+            }
+        }
+
+        #[test]
+        fn builder() {
+            let builder = || {
+                PrefixWriter::builder(PREFIX.to_owned())
+                    .with_prefix_empty_lines(true)
+                    .with_prefix_column(10)
+            };
+
+            let mut unpooled = builder().build(Vec::new());
+            run(&mut unpooled);
+
+            let pool = BufferPool::new(1024);
+            for _ in 0..3 {
+                let mut pooled = pool.build(builder(), Vec::new());
+                run(&mut pooled);
+
+                assert_eq!(unpooled.get_ref(), &pooled.finish().unwrap());
+            }
+        }
+
+        #[test]
+        fn rendered_buffer() {
+            let pool = BufferPool::new(1024);
+
+            let mut writer = pool.writer(PREFIX, Vec::new());
+            writer.write_all(b"a rendered line\n").unwrap();
+            let capacity = writer.spare_rendered.as_ref().unwrap().capacity();
+            assert!(capacity >= "prefix: a rendered line\n".len());
+
+            writer.write_all(b"short\n").unwrap();
+            assert_eq!(capacity, writer.spare_rendered.as_ref().unwrap().capacity());
+        }
+
+        #[test]
+        fn no_bleed() {
+            let pool = BufferPool::new(1024);
+
+            let mut first = pool.writer("a very long first prefix: ", Vec::new());
+            first.write_all(b"line\nleft incomplete").unwrap();
+            drop(first);
+            assert!(pool.pooled_bytes() > 0);
+
+            let mut second = pool.writer("b: ", Vec::new());
+            second.write_all(b"x").unwrap();
+            second.write_all(b"y\nz").unwrap();
+
+            assert_eq!(
+                "b: xy\nb: z",
+                String::from_utf8(second.finish().unwrap()).unwrap()
+            );
+        }
+
+        #[test]
+        fn max_bytes() {
+            let pool = BufferPool::new(16);
+
+            let mut writer = pool.writer(PREFIX, Vec::new());
+            writer.write_all(&[b'x'; 100]).unwrap();
+            drop(writer);
+
+            assert!(pool.pooled_bytes() <= 16);
+
+            let empty = BufferPool::new(0);
+            drop(empty.writer(PREFIX, Vec::new()));
+
+            assert_eq!(0, empty.pooled_bytes());
+        }
+    }
+}
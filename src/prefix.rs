@@ -0,0 +1,331 @@
+//! Composing the prefix of every line from prefix sources.
+
+use std::{
+    borrow::Cow,
+    fmt::Write as _,
+    io::Write,
+    time::Instant,
+};
+
+use crate::{
+    shared::Shared,
+    ClockConfig,
+    LineContext,
+    PrefixWriter,
+};
+
+/// Source rendering the prefix of a line, see
+/// [`PrefixWriter::with_prefix_source`]. Implemented for text, for
+/// closures like `|context: &LineContext<'_>, out: &mut String| ..`, for
+/// the components [`LineNumber`], [`Elapsed`], [`ClockConfig`] and
+/// [`crate::Timestamp`] and for
+/// tuples of sources, which render one after the other, so
+/// `("[", Elapsed::seconds(), "] ", "web: ")` is one prefix.
+pub trait Prefix {
+    /// Append the prefix of the line described by the context to `out`.
+    fn render(&mut self, context: &LineContext<'_>, out: &mut String);
+
+    /// Follow this prefix with the next one.
+    #[must_use]
+    fn chain<P: Prefix>(self, next: P) -> Chain<Self, P>
+    where
+        Self: Sized,
+    {
+        Chain(self, next)
+    }
+}
+
+impl Prefix for str {
+    fn render(&mut self, _context: &LineContext<'_>, out: &mut String) {
+        out.push_str(self);
+    }
+}
+
+impl Prefix for &str {
+    fn render(&mut self, _context: &LineContext<'_>, out: &mut String) {
+        out.push_str(self);
+    }
+}
+
+impl Prefix for String {
+    fn render(&mut self, _context: &LineContext<'_>, out: &mut String) {
+        out.push_str(self);
+    }
+}
+
+impl<F: FnMut(&LineContext<'_>, &mut String)> Prefix for F {
+    fn render(&mut self, context: &LineContext<'_>, out: &mut String) {
+        self(context, out);
+    }
+}
+
+/// Two prefixes rendered one after the other, see [`Prefix::chain`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chain<A, B>(pub A, pub B);
+
+impl<A: Prefix, B: Prefix> Prefix for Chain<A, B> {
+    fn render(&mut self, context: &LineContext<'_>, out: &mut String) {
+        self.0.render(context, out);
+        self.1.render(context, out);
+    }
+}
+
+/// Implement [`Prefix`] for tuples of prefixes.
+macro_rules! tuple_prefix {
+    ($($name:ident),+) => {
+        impl<$($name: Prefix),+> Prefix for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn render(&mut self, context: &LineContext<'_>, out: &mut String) {
+                let ($(ref mut $name,)+) = *self;
+                $($name.render(context, out);)+
+            }
+        }
+    };
+}
+
+tuple_prefix!(A);
+tuple_prefix!(A, B);
+tuple_prefix!(A, B, C);
+tuple_prefix!(A, B, C, D);
+tuple_prefix!(A, B, C, D, E);
+tuple_prefix!(A, B, C, D, E, F);
+tuple_prefix!(A, B, C, D, E, F, G);
+tuple_prefix!(A, B, C, D, E, F, G, H);
+
+/// Number of the line in the output, right aligned to a width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LineNumber {
+    width: usize,
+}
+
+impl LineNumber {
+    /// Create a new [`LineNumber`] right aligned to at least `width`
+    /// characters.
+    #[must_use]
+    pub fn new(width: usize) -> Self {
+        Self { width }
+    }
+}
+
+impl Prefix for LineNumber {
+    fn render(&mut self, context: &LineContext<'_>, out: &mut String) {
+        let _ = write!(out, "{:>width$}", context.number, width = self.width);
+    }
+}
+
+/// Time since the source was created, like `1.042`.
+pub struct Elapsed {
+    start: Instant,
+    clock: Box<dyn Fn() -> Instant + Send>,
+}
+
+impl std::fmt::Debug for Elapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Elapsed")
+            .field("start", &self.start)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Elapsed {
+    /// Create a new [`Elapsed`] showing the seconds since now with
+    /// milliseconds.
+    #[must_use]
+    pub fn seconds() -> Self {
+        Self {
+            start: Instant::now(),
+            clock: Box::new(Instant::now),
+        }
+    }
+
+    /// Use the clock instead of the system clock, for example a synthetic
+    /// clock in tests. The time is measured from now on this clock.
+    #[must_use]
+    pub fn with_clock(self, clock: impl Fn() -> Instant + Send + 'static) -> Self {
+        Self {
+            start: clock(),
+            clock: Box::new(clock),
+        }
+    }
+}
+
+impl Prefix for Elapsed {
+    fn render(&mut self, _context: &LineContext<'_>, out: &mut String) {
+        let elapsed = (self.clock)().saturating_duration_since(self.start);
+
+        let _ = write!(out, "{:.3}", elapsed.as_secs_f64());
+    }
+}
+
+impl Prefix for ClockConfig {
+    fn render(&mut self, _context: &LineContext<'_>, out: &mut String) {
+        out.push_str(&self.now());
+    }
+}
+
+/// Closure of [`PrefixWriter::with_prefix_context`] as a [`Prefix`].
+pub(crate) struct ContextFn<F>(pub(crate) F);
+
+impl<F: for<'a> FnMut(&LineContext<'a>) -> Cow<'a, str>> Prefix for ContextFn<F> {
+    fn render(&mut self, context: &LineContext<'_>, out: &mut String) {
+        out.push_str(&(self.0)(context));
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Render the prefix of every line with the prefix source, for
+    /// prefixes composed of parts like `("[", Elapsed::seconds(), "] ",
+    /// "web: ")`. Like [`PrefixWriter::with_prefix_context`] this
+    /// supersedes the simpler prefix options and replaces a closure set
+    /// with it.
+    #[must_use]
+    pub fn with_prefix_source(self, prefix: impl Prefix + Send + 'static) -> Self {
+        Self {
+            prefix_context: Some(Shared::new(Box::new(prefix))),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        fmt::Write as _,
+        io::Write,
+        sync::{
+            Arc,
+            Mutex,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    use super::{
+        Elapsed,
+        LineNumber,
+        Prefix,
+    };
+    use crate::{
+        LineContext,
+        PrefixWriter,
+    };
+
+    /// Prefix counting how often it was rendered.
+    #[derive(Debug, Default)]
+    struct Renders(u64);
+
+    impl Prefix for Renders {
+        fn render(&mut self, context: &LineContext<'_>, out: &mut String) {
+            self.0 += 1;
+            write!(out, "#{}/{}", self.0, context.content.len()).unwrap();
+        }
+    }
+
+    /// Clock advancing by the duration every time it is read.
+    fn ticking_clock(step: Duration) -> impl Fn() -> Instant + Send + 'static {
+        let now = Arc::new(Mutex::new(Instant::now()));
+
+        move || {
+            let mut now = now.lock().unwrap();
+            *now += step;
+            *now
+        }
+    }
+
+    fn run(prefix: impl Prefix + Send + 'static) -> String {
+        let mut writer = PrefixWriter::new(String::new(), Vec::new()).with_prefix_source(prefix);
+
+        writer.write_all(b"first\n\nsec").unwrap();
+        writer.write_all(b"ond\nthird\n").unwrap();
+
+        String::from_utf8_lossy(writer.get_ref()).into_owned()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            run,
+            ticking_clock,
+            Duration,
+            Elapsed,
+            LineContext,
+            LineNumber,
+            Prefix,
+            PrefixWriter,
+            Renders,
+            Write,
+        };
+
+        #[test]
+        fn text() {
+            assert_eq!("web: first\n\nweb: second\nweb: third\n", run("web: "));
+            assert_eq!(
+                "web: first\n\nweb: second\nweb: third\n",
+                run("web: ".to_owned())
+            );
+        }
+
+        #[test]
+        fn composition_order() {
+            assert_eq!(
+                concat!(
+                    "[#1/5 1] web: first\n",
+                    "\n",
+                    "[#2/6 3] web: second\n",
+                    "[#3/5 4] web: third\n",
+                ),
+                run((
+                    "[",
+                    Renders::default(),
+                    " ",
+                    LineNumber::new(1),
+                    "] ",
+                    "web: "
+                ))
+            );
+
+            assert_eq!(
+                "#1/5>1 first\n\n#2/6>3 second\n#3/5>4 third\n",
+                run(Renders::default()
+                    .chain(">")
+                    .chain(LineNumber::new(0))
+                    .chain(" "))
+            );
+        }
+
+        #[test]
+        fn closure() {
+            let prefix = |context: &LineContext<'_>, out: &mut String| {
+                if context.is_last {
+                    out.push_str("last: ");
+                }
+            };
+
+            assert_eq!("first\n\nsecond\nlast: third\n", run(prefix));
+        }
+
+        #[test]
+        fn elapsed() {
+            let elapsed = Elapsed::seconds().with_clock(ticking_clock(Duration::from_millis(250)));
+
+            assert_eq!(
+                "[0.250] web: first\n\n[0.500] web: second\n[0.750] web: third\n",
+                run(("[", elapsed, "] ", "web: "))
+            );
+        }
+
+        #[test]
+        fn replaces_prefix_context() {
+            let mut writer = PrefixWriter::new(String::new(), Vec::new())
+                .with_prefix_context(|_| "context: ".into())
+                .with_prefix_source((LineNumber::new(2), " "));
+
+            writer.write_all(b"first\n").unwrap();
+
+            assert_eq!(" 1 first\n", String::from_utf8_lossy(writer.get_ref()));
+        }
+    }
+}
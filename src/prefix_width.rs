@@ -0,0 +1,333 @@
+//! Shortening prefixes that are wider than a maximum width and padding
+//! them to a column.
+
+use std::io::Write;
+
+use crate::{
+    width::{
+        display_width,
+        DISPLAY_UNIT,
+    },
+    PrefixWriter,
+    Unit,
+};
+
+/// Where a prefix shortened by [`PrefixWriter::with_max_prefix_width`]
+/// is cut and gets its ellipsis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PrefixEllipsis {
+    /// Keep the end of the prefix, like `…/src/main.rs: `.
+    Leading,
+
+    /// Keep the start of the prefix, like `web-7f9c…`.
+    #[default]
+    Trailing,
+
+    /// Keep the start and the end of the prefix, like `web-…-x2k: `.
+    Middle,
+}
+
+/// Split the text into ANSI escape sequences and the parts it may be cut
+/// between, marking the escape sequences.
+fn tokens(text: &str, unit: Unit) -> Vec<(&str, bool)> {
+    let mut tokens = Vec::new();
+
+    let mut rest = text;
+    while !rest.is_empty() {
+        let escape_start = rest.find('\x1b').unwrap_or(rest.len());
+        tokens.extend(
+            unit.segments(&rest[..escape_start])
+                .map(|segment| (segment, false)),
+        );
+        rest = &rest[escape_start..];
+
+        if rest.is_empty() {
+            break;
+        }
+
+        // A CSI sequence ends with its final byte, other escapes are the
+        // escape character and the one after it.
+        let escape_len = if rest[1..].starts_with('[') {
+            rest[2..]
+                .find(|character| matches!(character, '\x40'..='\x7e'))
+                .map_or(rest.len(), |end| end + 3)
+        } else {
+            rest[1..]
+                .chars()
+                .next()
+                .map_or(1, |next| 1 + next.len_utf8())
+        };
+
+        tokens.push((&rest[..escape_len], true));
+        rest = &rest[escape_len..];
+    }
+
+    tokens
+}
+
+/// Pad the prefix with spaces until it reaches the column of a terminal,
+/// where wide characters take up two columns and combining characters
+/// none. Escape sequences do not take up columns.
+pub(crate) fn pad_to_column(prefix: &str, column: usize) -> String {
+    // Measured as a whole, emojis joined by zero width joiners are only
+    // as wide as one emoji.
+    let visible = tokens(prefix, DISPLAY_UNIT)
+        .into_iter()
+        .filter(|(_, escape)| !escape)
+        .map(|(token, _)| token)
+        .collect::<String>();
+    let width = display_width(&visible);
+
+    let mut padded = prefix.to_owned();
+    padded.extend(std::iter::repeat_n(' ', column.saturating_sub(width)));
+
+    padded
+}
+
+/// Shorten the prefix to at most `width` columns of a terminal with an
+/// ellipsis where it is cut. Escape sequences are kept as a whole and do
+/// not count towards the width.
+pub(crate) fn shorten(prefix: &str, width: usize, ellipsis: PrefixEllipsis) -> String {
+    let unit = DISPLAY_UNIT;
+    let tokens = tokens(prefix, unit);
+
+    let visible = tokens
+        .iter()
+        .filter(|(_, escape)| !escape)
+        .map(|(token, _)| unit.measure(token))
+        .collect::<Vec<_>>();
+
+    if visible.iter().sum::<usize>() <= width {
+        return prefix.to_owned();
+    }
+
+    let ellipsis_width = unit.measure("…");
+    let available = width.saturating_sub(ellipsis_width);
+
+    let (head_width, tail_width) = match ellipsis {
+        PrefixEllipsis::Leading => (0, available),
+        PrefixEllipsis::Trailing => (available, 0),
+        PrefixEllipsis::Middle => (available.div_ceil(2), available / 2),
+    };
+
+    let keep_while = |budget: usize, widths: &mut dyn Iterator<Item = &usize>| {
+        let mut used = 0;
+        widths
+            .take_while(|width| {
+                used += *width;
+                used <= budget
+            })
+            .count()
+    };
+    let head = keep_while(head_width, &mut visible.iter());
+    let tail = keep_while(tail_width, &mut visible.iter().rev());
+    let tail_start = visible.len() - tail;
+
+    let mut shortened = String::with_capacity(prefix.len());
+    let mut index = 0;
+    for (token, escape) in tokens {
+        if escape {
+            shortened.push_str(token);
+            continue;
+        }
+
+        if index == head && width >= ellipsis_width {
+            shortened.push('…');
+        }
+        if index < head || index >= tail_start {
+            shortened.push_str(token);
+        }
+
+        index += 1;
+    }
+
+    shortened
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Shorten prefixes wider than `width` to fit, with an ellipsis where
+    /// they are cut, for prefixes built from long paths or names. Applies
+    /// to the complete prefix, including the options that extend it. Like
+    /// [`PrefixWriter::with_prefix_column`] the width is always measured
+    /// in columns of a terminal. Escape sequences styling the prefix are
+    /// never cut and do not count towards the width.
+    #[must_use]
+    pub fn with_max_prefix_width(self, width: usize, ellipsis: PrefixEllipsis) -> Self {
+        Self {
+            max_prefix_width: Some((width, ellipsis)),
+            ..self
+        }
+    }
+
+    /// Pad prefixes narrower than `column` with spaces, so the content of
+    /// all lines starts at the same column even for prefixes of different
+    /// widths, like `web: ` and `数据库: `. Unlike the widths of the
+    /// content the column is always measured in columns of a terminal,
+    /// where wide characters like CJK take up two columns and combining
+    /// characters and zero width joiners none. Without the `unicode`
+    /// feature the characters are counted instead. Prefixes are padded after
+    /// they were shortened by [`PrefixWriter::with_max_prefix_width`].
+    #[must_use]
+    pub fn with_prefix_column(self, column: usize) -> Self {
+        Self {
+            prefix_column: Some(column),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::{
+        shorten,
+        PrefixEllipsis,
+    };
+    use crate::PrefixWriter;
+    #[cfg(feature = "unicode")]
+    use crate::Unit;
+
+    const PREFIX: &str = "web-7f9c8d-x2k: ";
+
+    mod tests {
+        #[cfg(feature = "unicode")]
+        use super::Unit;
+        use super::{
+            assert_eq,
+            shorten,
+            PrefixEllipsis,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn strategies() {
+            let shortened = |width, ellipsis| shorten(PREFIX, width, ellipsis);
+
+            assert_eq!("…-x2k: ", shortened(7, PrefixEllipsis::Leading));
+            assert_eq!("web-7f…", shortened(7, PrefixEllipsis::Trailing));
+            assert_eq!("web…k: ", shortened(7, PrefixEllipsis::Middle));
+            assert_eq!("we… ", shortened(4, PrefixEllipsis::Middle));
+            assert_eq!("", shortened(0, PrefixEllipsis::Middle));
+        }
+
+        #[test]
+        fn at_limit() {
+            for ellipsis in [
+                PrefixEllipsis::Leading,
+                PrefixEllipsis::Trailing,
+                PrefixEllipsis::Middle,
+            ] {
+                assert_eq!(PREFIX, shorten(PREFIX, 16, ellipsis));
+                assert_eq!(15, shorten(PREFIX, 15, ellipsis).chars().count());
+            }
+        }
+
+        #[cfg(feature = "unicode")]
+        #[test]
+        fn multi_byte() {
+            assert_eq!("日本…", shorten("日本日本: ", 6, PrefixEllipsis::Trailing));
+            assert_eq!("日…", shorten("日本日本: ", 4, PrefixEllipsis::Trailing));
+            assert_eq!("…: ", shorten("日本日本: ", 4, PrefixEllipsis::Leading));
+            assert_eq!(
+                "cafe\u{301}-…",
+                shorten("cafe\u{301}-cafe\u{301}: ", 6, PrefixEllipsis::Trailing)
+            );
+        }
+
+        #[cfg(not(feature = "unicode"))]
+        #[test]
+        fn multi_byte_chars() {
+            assert_eq!(
+                "日本日…",
+                shorten("日本日本: ", 4, PrefixEllipsis::Trailing)
+            );
+            assert_eq!("…本: ", shorten("日本日本: ", 4, PrefixEllipsis::Leading));
+        }
+
+        #[test]
+        fn escapes() {
+            assert_eq!(
+                "\x1b[1mweb-7f…\x1b[0m",
+                shorten("\x1b[1mweb-7f9c8d\x1b[0m: ", 7, PrefixEllipsis::Trailing)
+            );
+            assert_eq!(
+                "\x1b[1m…\x1b[0m: ",
+                shorten("\x1b[1mweb-7f9c8d\x1b[0m: ", 3, PrefixEllipsis::Leading)
+            );
+        }
+
+        #[test]
+        fn writer() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_running_count(2)
+                .with_max_prefix_width(10, PrefixEllipsis::Middle);
+
+            writer.write_all(b"first\n").unwrap();
+
+            assert_eq!(
+                " 1 we…2k: first\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[cfg(feature = "unicode")]
+        #[test]
+        fn writer_columns() {
+            let mut writer = PrefixWriter::new("数据库-primary: ".to_owned(), Vec::new())
+                .with_width_unit(Unit::Bytes)
+                .with_max_prefix_width(8, PrefixEllipsis::Trailing);
+
+            writer.write_all(b"first\n").unwrap();
+
+            assert_eq!("数据库-…first\n", String::from_utf8_lossy(writer.get_ref()));
+        }
+
+        #[cfg(feature = "unicode")]
+        #[test]
+        fn prefix_column() {
+            let output = |prefix: &str| {
+                let mut writer =
+                    PrefixWriter::new(prefix.to_owned(), Vec::new()).with_prefix_column(12);
+                writer.write_all(b"content\n").unwrap();
+
+                String::from_utf8(writer.get_ref().clone()).unwrap()
+            };
+
+            for prefix in [
+                "web: ",
+                "数据库: ",
+                "cafe\u{301}: ",
+                "👨\u{200d}👩\u{200d}👧: ",
+            ] {
+                let line = output(prefix);
+                let padded = line.strip_suffix("content\n").unwrap();
+
+                assert!(padded.starts_with(prefix), "{prefix}");
+                assert_eq!(12, Unit::Columns.measure(padded), "{prefix}");
+            }
+
+            assert_eq!(
+                "a-very-long-prefix: content\n",
+                output("a-very-long-prefix: ")
+            );
+        }
+
+        #[cfg(feature = "unicode")]
+        #[test]
+        fn prefix_column_escapes() {
+            let mut writer = PrefixWriter::new("\x1b[33m数据库\x1b[0m: ".to_owned(), Vec::new())
+                .with_prefix_column(10);
+            writer.write_all(b"content\n").unwrap();
+
+            assert_eq!(
+                "\x1b[33m数据库\x1b[0m:   content\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
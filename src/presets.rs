@@ -0,0 +1,202 @@
+//! Ready-made configurations of a [`PrefixWriter`] for common output
+//! styles.
+
+use std::io::Write;
+
+use crate::{
+    width::display_width,
+    PrefixWriter,
+    PrefixWriterBuilder,
+    Sanitization,
+};
+
+/// ANSI foreground colors used for names, the same palette docker compose
+/// cycles through.
+const COLORS: [u8; 6] = [36, 33, 32, 35, 34, 31];
+
+/// Width names are padded to by [`PrefixWriter::docker_style`].
+const DOCKER_NAME_WIDTH: usize = 12;
+
+impl<W: Write> PrefixWriter<W> {
+    /// Prefix lines with the name followed by `: ` and nothing else.
+    pub fn plain(name: &str, writer: W) -> Self {
+        PrefixWriterBuilder::builder(format!("{name}: ")).build(writer)
+    }
+
+    /// Prefix lines like docker compose does, with the name padded to 12
+    /// columns followed by ` | `. The name is colored with a color
+    /// derived from the name, so the same name always gets the same
+    /// color, unless the `NO_COLOR` environment variable is set. Escape
+    /// sequences of the lines besides colors are removed, see
+    /// [`PrefixWriter::with_sanitization`].
+    pub fn docker_style(name: &str, writer: W) -> Self {
+        let colors = std::env::var_os("NO_COLOR").is_none();
+
+        Self::docker_style_with(name, DOCKER_NAME_WIDTH, colors, writer)
+    }
+
+    /// Like [`PrefixWriter::docker_style`] but with the width the name is
+    /// padded to, like the width of the longest name of all writers, and
+    /// whether the name is colored.
+    pub fn docker_style_with(name: &str, width: usize, colors: bool, writer: W) -> Self {
+        let padding = " ".repeat(width.saturating_sub(display_width(name)));
+        let name = if colors {
+            format!("\x1b[{}m{name}\x1b[0m", name_color(name))
+        } else {
+            name.to_owned()
+        };
+
+        PrefixWriterBuilder::builder(format!("{name}{padding} | "))
+            .with_marker_prefix(format!("{name}{padding} ## "))
+            .with_sanitization(Sanitization::new().with_colors(colors))
+            .build(writer)
+    }
+
+    /// Prefix lines for logs of CI systems, which often do not render
+    /// colors, with the time since the previous line and the name in
+    /// brackets, like `+0.250s [web] `. Escape sequences of the lines are
+    /// removed, runs of empty lines are squeezed to one and the output
+    /// always ends with a newline when flushed.
+    pub fn ci_style(name: &str, writer: W) -> Self {
+        PrefixWriterBuilder::builder(format!("[{name}] "))
+            .with_marker_prefix(format!("[{name}] ## "))
+            .with_delta_time_prefix(true)
+            .with_sanitization(Sanitization::new())
+            .with_squeeze_empty_lines(true)
+            .with_trailing_newline(true)
+            .build(writer)
+    }
+}
+
+/// Pick a color for the name with FNV-1a, which unlike the hasher of the
+/// standard library is stable across releases.
+fn name_color(name: &str) -> u8 {
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+
+    // The remainder is always smaller than the length of the palette.
+    #[allow(clippy::cast_possible_truncation)]
+    COLORS[(hash % COLORS.len() as u64) as usize]
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::Write,
+        sync::{
+            Arc,
+            Mutex,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    use crate::PrefixWriter;
+
+    const INPUT: &str = "first\n\n\nsecond";
+
+    fn run(mut writer: PrefixWriter<Vec<u8>>) -> String {
+        writer.write_all(INPUT.as_bytes()).unwrap();
+        writer.write_marker("done").unwrap();
+
+        String::from_utf8_lossy(writer.get_ref()).into_owned()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            run,
+            Arc,
+            Duration,
+            Instant,
+            Mutex,
+            PrefixWriter,
+            Write,
+        };
+
+        #[test]
+        fn plain() {
+            assert_eq!(
+                "web: first\n\n\nweb: second\nweb: == done\n",
+                run(PrefixWriter::plain("web", Vec::new()))
+            );
+        }
+
+        #[test]
+        fn docker_style() {
+            assert_eq!(
+                concat!(
+                    "\x1b[33mweb\x1b[0m          | first\n",
+                    "\n",
+                    "\n",
+                    "\x1b[33mweb\x1b[0m          | second\n",
+                    "\x1b[33mweb\x1b[0m          ## done\n",
+                ),
+                run(PrefixWriter::docker_style_with("web", 12, true, Vec::new()))
+            );
+
+            assert_eq!(
+                "db    | first\n\n\ndb    | second\ndb    ## done\n",
+                run(PrefixWriter::docker_style_with("db", 5, false, Vec::new()))
+            );
+
+            let long = run(PrefixWriter::docker_style_with(
+                "database",
+                5,
+                false,
+                Vec::new(),
+            ));
+            assert!(long.starts_with("database | first\n"));
+        }
+
+        #[test]
+        fn docker_style_sanitizes() {
+            for colors in [false, true] {
+                let mut writer = PrefixWriter::docker_style_with("web", 3, colors, Vec::new());
+                writer
+                    .write_all(b"\x1b]0;title\x07\x1b[32mgreen\x1b[0m\x1b[2J\n")
+                    .unwrap();
+
+                let output = String::from_utf8_lossy(writer.get_ref()).into_owned();
+                let (_, line) = output.split_once(" | ").unwrap();
+
+                if colors {
+                    assert_eq!("\x1b[32mgreen\x1b[0m\n", line);
+                } else {
+                    assert_eq!("green\n", line);
+                }
+            }
+        }
+
+        #[test]
+        fn ci_style() {
+            let now = Arc::new(Mutex::new(Instant::now()));
+            let clock = Arc::clone(&now);
+
+            let mut writer = PrefixWriter::ci_style("web", Vec::new())
+                .with_clock(move || *clock.lock().unwrap());
+
+            writer.write_all(b"first\n\n\n\x1b[31m").unwrap();
+            *now.lock().unwrap() += Duration::from_millis(250);
+            writer.write_all(b"second\x1b[0m\n\nthird").unwrap();
+            writer.flush().unwrap();
+            writer.write_marker("done").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "+0.000s [web] first\n",
+                    "\n",
+                    "+0.250s [web] second\n",
+                    "\n",
+                    "+0.000s [web] third\n",
+                    "[web] ## done\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
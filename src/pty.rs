@@ -0,0 +1,84 @@
+//! Running commands on a pseudo-terminal with their output prefixed.
+
+use std::io::{
+    Read,
+    Write,
+};
+
+use portable_pty::{
+    native_pty_system,
+    CommandBuilder,
+    ExitStatus,
+    PtySize,
+};
+
+use crate::PrefixWriter;
+
+/// Error number Linux reports when reading from the master side after the
+/// command closed the terminal.
+const EIO: i32 = 5;
+
+/// Run the command on a pseudo-terminal of the default size of 24 rows
+/// and 80 columns, see [`run_prefixed_pty_with_size`].
+///
+/// # Errors
+///
+/// Returns the errors of allocating the pseudo-terminal, spawning the
+/// command and writing to the sink.
+pub fn run_prefixed_pty(
+    cmd: CommandBuilder,
+    prefix: String,
+    sink: impl Write,
+) -> std::io::Result<ExitStatus> {
+    run_prefixed_pty_with_size(cmd, prefix, PtySize::default(), sink)
+}
+
+/// Run the command on a pseudo-terminal of the given size and write its
+/// output prefixed to the sink until it exits. Programs that disable
+/// colors and progress output when writing to a pipe keep them this way.
+/// Progress lines redrawn with `\r` are prefixed on every redraw.
+///
+/// # Errors
+///
+/// Returns the errors of allocating the pseudo-terminal, spawning the
+/// command and writing to the sink.
+pub fn run_prefixed_pty_with_size(
+    cmd: CommandBuilder,
+    prefix: String,
+    size: PtySize,
+    sink: impl Write,
+) -> std::io::Result<ExitStatus> {
+    let pair = native_pty_system()
+        .openpty(size)
+        .map_err(std::io::Error::other)?;
+
+    let mut child = pair
+        .slave
+        .spawn_command(cmd)
+        .map_err(std::io::Error::other)?;
+
+    // The output only ends once nothing holds the slave side open anymore.
+    drop(pair.slave);
+
+    let mut reader = pair
+        .master
+        .try_clone_reader()
+        .map_err(std::io::Error::other)?;
+
+    let mut writer = PrefixWriter::new(prefix, sink).with_carriage_return_prefix(true);
+
+    let mut buffer = [0; 4096];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(count) => writer.write_all(&buffer[..count])?,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) if err.raw_os_error() == Some(EIO) => break,
+            Err(err) => return Err(err),
+        }
+    }
+
+    writer.flush()?;
+
+    child.wait()
+}
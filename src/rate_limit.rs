@@ -0,0 +1,62 @@
+//! Limiting how many lines are written per second.
+
+use std::time::{
+    Duration,
+    Instant,
+};
+
+/// Counts lines in windows of one second and drops the lines exceeding
+/// the limit of a window.
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimit {
+    max_lines: u32,
+
+    window_start: Option<Instant>,
+    lines: u32,
+    suppressed: u64,
+}
+
+impl RateLimit {
+    pub(crate) fn new(max_lines: u32) -> Self {
+        Self {
+            max_lines,
+
+            window_start: None,
+            lines: 0,
+            suppressed: 0,
+        }
+    }
+
+    pub(crate) fn max_lines(&self) -> u32 {
+        self.max_lines
+    }
+
+    /// Decide whether a line at `now` is written. Returns the number of
+    /// lines that were dropped since the last written line, or `None` if
+    /// this line has to be dropped as well.
+    pub(crate) fn admit(&mut self, now: Instant) -> Option<u64> {
+        let window_over = self
+            .window_start
+            .is_none_or(|start| now.saturating_duration_since(start) >= Duration::from_secs(1));
+
+        if window_over {
+            self.window_start = Some(now);
+            self.lines = 0;
+        }
+
+        if self.lines >= self.max_lines {
+            self.suppressed += 1;
+            return None;
+        }
+
+        self.lines += 1;
+
+        Some(std::mem::take(&mut self.suppressed))
+    }
+
+    /// Count lines as dropped again after the notice about them could not
+    /// be written.
+    pub(crate) fn restore_suppressed(&mut self, suppressed: u64) {
+        self.suppressed += suppressed;
+    }
+}
@@ -0,0 +1,117 @@
+//! Writing pre-formatted output through a [`PrefixWriter`] without
+//! prefixes.
+
+use std::io::Write;
+
+use crate::PrefixWriter;
+
+/// Writes straight to the writer of a [`PrefixWriter`] without prefixing
+/// anything, see [`PrefixWriter::suspend_prefixing`]. Prefixing resumes
+/// once the guard is dropped.
+#[derive(Debug)]
+pub struct RawGuard<'a, W: Write> {
+    writer: &'a mut PrefixWriter<W>,
+    line_open: bool,
+}
+
+impl<W: Write> Write for RawGuard<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.writer.get_mut().write(buf)?;
+        self.writer.output_offset += written as u64;
+
+        if let Some(last) = buf[..written].last() {
+            self.line_open = *last != b'\n';
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for RawGuard<'_, W> {
+    fn drop(&mut self) {
+        self.writer.raw_line_open = self.line_open;
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Suspend prefixing to write a block of pre-formatted output, like a
+    /// table or a diff, as it is. A pending incomplete line is completed
+    /// first. After the guard is dropped prefixing resumes on a fresh
+    /// line, when the raw output ended within a line the next line starts
+    /// with a newline. The guard borrows the writer, so suspending can not
+    /// be nested.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the writer while completing the pending
+    /// line.
+    pub fn suspend_prefixing(&mut self) -> std::io::Result<RawGuard<'_, W>> {
+        self.write_unfinished()?;
+
+        let terminator = self.terminator();
+        self.write_remainder(&terminator)?;
+
+        Ok(RawGuard {
+            writer: self,
+            line_open: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn raw_block() {
+            const RAW: &[u8] = b"| a | b |\n|---|---|\n\n  \r\n";
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            writer.write_all(b"before\nincomplete").unwrap();
+            writer.suspend_prefixing().unwrap().write_all(RAW).unwrap();
+            writer.write_all(b"after\n").unwrap();
+
+            let mut expected = b"prefix: before\nprefix: incomplete\n".to_vec();
+            expected.extend_from_slice(RAW);
+            expected.extend_from_slice(b"prefix: after\n");
+
+            assert_eq!(expected, *writer.get_ref());
+        }
+
+        #[test]
+        fn drop_mid_raw_line() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            {
+                let mut raw = writer.suspend_prefixing().unwrap();
+                raw.write_all(b"raw\nmid").unwrap();
+            }
+
+            writer.write_all(b"first\nsec").unwrap();
+            writer.write_all(b"ond\n").unwrap();
+
+            assert_eq!(
+                "raw\nmid\nprefix: first\nprefix: second\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Separating blocks of lines with a record separator.
+
+use std::io::Write;
+
+use crate::PrefixWriter;
+
+impl<W: Write> PrefixWriter<W> {
+    /// Separate blocks of lines ended with [`PrefixWriter::end_record`]
+    /// by the separator, like the ASCII record separator `0x1e`, so
+    /// parsers reading the output can tell where a block ends. The
+    /// separator is written as it is, without prefix or terminator.
+    #[must_use]
+    pub fn with_record_separator(self, separator: Vec<u8>) -> Self {
+        Self {
+            record_separator: Some(separator),
+            ..self
+        }
+    }
+
+    /// End the current block of lines. Flushes the incomplete line like
+    /// [`Write::flush`] and writes the separator of
+    /// [`PrefixWriter::with_record_separator`], if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the writer.
+    pub fn end_record(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+
+        if self.muted {
+            return Ok(());
+        }
+
+        if let Some(separator) = self.record_separator.clone() {
+            self.write_rendered_bytes(&separator, 0, 0)?;
+            self.writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+    const SEPARATOR: &[u8] = b"\x1e\n";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            PrefixWriter,
+            Write,
+            PREFIX,
+            SEPARATOR,
+        };
+
+        #[test]
+        fn between_blocks() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_record_separator(SEPARATOR.to_vec());
+
+            writer.write_all(b"first\nblock\n").unwrap();
+            writer.end_record().unwrap();
+            writer.write_all(b"second\nblo").unwrap();
+            writer.write_all(b"ck").unwrap();
+            writer.flush().unwrap();
+
+            let output = String::from_utf8_lossy(writer.get_ref()).into_owned();
+
+            assert_eq!(1, output.matches('\x1e').count());
+            assert_eq!(
+                "prefix: first\nprefix: block\n\x1e\nprefix: second\nprefix: block",
+                output
+            );
+        }
+
+        #[test]
+        fn flushes_incomplete_line() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_record_separator(SEPARATOR.to_vec());
+
+            writer.write_all(b"incomplete").unwrap();
+            writer.end_record().unwrap();
+
+            assert_eq!(
+                "prefix: incomplete\x1e\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
@@ -0,0 +1,343 @@
+//! Grouping lines that continue a record, like the lines of a stack
+//! trace, with the line starting the record.
+
+use std::io::Write;
+
+use crate::{
+    PrefixWriteError,
+    PrefixWriter,
+};
+
+/// How lines are grouped into records, see
+/// [`PrefixWriter::with_records`].
+#[derive(Debug, Clone)]
+pub struct RecordDetection {
+    is_continuation: fn(&str) -> bool,
+    continuation_prefix: Option<String>,
+    atomic: bool,
+    max_lines: usize,
+}
+
+/// Detects records and collects the rendered lines of atomic records.
+#[derive(Debug, Clone)]
+pub(crate) struct Records {
+    detection: RecordDetection,
+
+    /// Whether a record was started, so a continuation has something to
+    /// continue.
+    in_record: bool,
+
+    /// Whether the line that is written continues the record.
+    continuation: bool,
+
+    /// Rendered lines of the atomic record that is collected.
+    group: Vec<u8>,
+    grouped_lines: usize,
+}
+
+impl Default for RecordDetection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecordDetection {
+    /// Create a new [`RecordDetection`] where lines starting with
+    /// whitespace, `at ` or `Caused by` continue the record, like the
+    /// lines of Java and Python stack traces. Continuations get the same
+    /// prefix as the first line of a record and records are not atomic.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            is_continuation: |line| {
+                line.starts_with(char::is_whitespace)
+                    || line.starts_with("at ")
+                    || line.starts_with("Caused by")
+            },
+            continuation_prefix: None,
+            atomic: false,
+            max_lines: 1000,
+        }
+    }
+
+    /// Set which lines continue the record of the previous line.
+    #[must_use]
+    pub fn with_is_continuation(self, is_continuation: fn(&str) -> bool) -> Self {
+        Self {
+            is_continuation,
+            ..self
+        }
+    }
+
+    /// Prefix lines continuing a record with `continuation_prefix`
+    /// instead of the prefix.
+    #[must_use]
+    pub fn with_continuation_prefix(self, continuation_prefix: String) -> Self {
+        Self {
+            continuation_prefix: Some(continuation_prefix),
+            ..self
+        }
+    }
+
+    /// Write every record with a single write, so writers sharing the
+    /// output can not split it. A record is written once the next record
+    /// starts or the writer is flushed.
+    #[must_use]
+    pub fn with_atomic(self, atomic: bool) -> Self {
+        Self { atomic, ..self }
+    }
+
+    /// Set how many lines of an atomic record are collected at most, the
+    /// collected lines are written before the next line of a longer
+    /// record. Defaults to 1000.
+    #[must_use]
+    pub fn with_max_lines(self, max_lines: usize) -> Self {
+        Self {
+            max_lines: max_lines.max(1),
+            ..self
+        }
+    }
+}
+
+impl Records {
+    pub(crate) fn new(detection: RecordDetection) -> Self {
+        Self {
+            detection,
+
+            in_record: false,
+            continuation: false,
+
+            group: Vec::new(),
+            grouped_lines: 0,
+        }
+    }
+
+    pub(crate) fn is_atomic(&self) -> bool {
+        self.detection.atomic
+    }
+
+    /// The prefix of the line that is written, if it continues a record
+    /// and continuations have their own prefix.
+    pub(crate) fn continuation_prefix(&self) -> Option<&str> {
+        self.detection
+            .continuation_prefix
+            .as_deref()
+            .filter(|_| self.continuation)
+    }
+
+    /// Whether the collected record reached the maximum number of lines
+    /// and has to be written before collecting more.
+    pub(crate) fn is_full(&self) -> bool {
+        self.grouped_lines >= self.detection.max_lines
+    }
+
+    /// Bytes of the collected group the next line is written after.
+    pub(crate) fn pending_len(&self) -> usize {
+        if self.is_atomic() && !self.is_full() {
+            self.group.len()
+        } else {
+            0
+        }
+    }
+
+    /// Collect the rendered line of an atomic record.
+    pub(crate) fn collect(&mut self, rendered: &[u8]) {
+        self.group.extend_from_slice(rendered);
+        self.grouped_lines += 1;
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Group lines into records of a first line and the lines continuing
+    /// it, like a stack trace with its header. Continuations can get their
+    /// own prefix and records can be written atomically, see
+    /// [`RecordDetection`].
+    #[must_use]
+    pub fn with_records(self, detection: RecordDetection) -> Self {
+        Self {
+            records: Some(Records::new(detection)),
+            ..self
+        }
+    }
+
+    /// Decide whether the line starts a new record or continues the
+    /// current one. The collected previous record is written when a new
+    /// one starts.
+    pub(crate) fn detect_record(&mut self, content: &str) -> Result<(), PrefixWriteError> {
+        let Some(ref mut records) = self.records else {
+            return Ok(());
+        };
+
+        records.continuation = records.in_record && (records.detection.is_continuation)(content);
+        records.in_record = true;
+
+        if records.continuation {
+            return Ok(());
+        }
+
+        self.write_record_group()
+    }
+
+    /// Write the collected lines of an atomic record with a single write.
+    /// The lines are kept when writing fails.
+    pub(crate) fn write_record_group(&mut self) -> Result<(), PrefixWriteError> {
+        let Some(group) = self
+            .records
+            .as_mut()
+            .filter(|records| !records.group.is_empty())
+            .map(|records| std::mem::take(&mut records.group))
+        else {
+            return Ok(());
+        };
+
+        if let Err(err) = self.write_rendered_bytes(&group, 0, 0) {
+            if let Some(ref mut records) = self.records {
+                records.group = group;
+            }
+            return Err(err);
+        }
+
+        if let Some(ref mut records) = self.records {
+            records.grouped_lines = 0;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::RecordDetection;
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "app: ";
+
+    const TRACE: &str = concat!(
+        "Starting request\n",
+        "Exception in thread \"main\" java.lang.IllegalStateException: outer\n",
+        "\tat com.example.Service.handle(Service.java:42)\n",
+        "\tat com.example.Main.main(Main.java:10)\n",
+        "Caused by: java.io.IOException: inner\n",
+        "\tat com.example.Store.read(Store.java:7)\n",
+        "\t... 2 more\n",
+        "Retrying\n",
+        "java.lang.NullPointerException\n",
+        "\tat com.example.Service.retry(Service.java:50)\n",
+        "Done\n",
+    );
+
+    /// Records every call to [`Write::write`] separately.
+    #[derive(Debug, Default)]
+    struct WriteRecorder(Vec<String>);
+
+    impl Write for WriteRecorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.push(String::from_utf8_lossy(buf).into_owned());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Write the trace in chunks of `chunk` bytes.
+    fn write_chunked(writer: &mut impl Write, chunk: usize) {
+        for chunk in TRACE.as_bytes().chunks(chunk) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            write_chunked,
+            PrefixWriter,
+            RecordDetection,
+            Write,
+            WriteRecorder,
+            PREFIX,
+            TRACE,
+        };
+
+        #[test]
+        fn continuation_prefix() {
+            for chunk in 1..=TRACE.len() {
+                let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_records(
+                    RecordDetection::new().with_continuation_prefix("   | ".to_owned()),
+                );
+
+                write_chunked(&mut writer, chunk);
+
+                assert_eq!(
+                    concat!(
+                        "app: Starting request\n",
+                        "app: Exception in thread \"main\" java.lang.IllegalStateException: \
+                         outer\n",
+                        "   | \tat com.example.Service.handle(Service.java:42)\n",
+                        "   | \tat com.example.Main.main(Main.java:10)\n",
+                        "   | Caused by: java.io.IOException: inner\n",
+                        "   | \tat com.example.Store.read(Store.java:7)\n",
+                        "   | \t... 2 more\n",
+                        "app: Retrying\n",
+                        "app: java.lang.NullPointerException\n",
+                        "   | \tat com.example.Service.retry(Service.java:50)\n",
+                        "app: Done\n",
+                    ),
+                    String::from_utf8_lossy(writer.get_ref()),
+                    "{chunk}"
+                );
+            }
+        }
+
+        #[test]
+        fn atomic() {
+            for chunk in 1..=TRACE.len() {
+                let mut writer = PrefixWriter::new(PREFIX.to_owned(), WriteRecorder::default())
+                    .with_records(RecordDetection::new().with_atomic(true));
+
+                write_chunked(&mut writer, chunk);
+
+                assert_eq!(
+                    vec![
+                        "app: Starting request\n",
+                        concat!(
+                            "app: Exception in thread \"main\" java.lang.IllegalStateException: \
+                             outer\n",
+                            "app: \tat com.example.Service.handle(Service.java:42)\n",
+                            "app: \tat com.example.Main.main(Main.java:10)\n",
+                            "app: Caused by: java.io.IOException: inner\n",
+                            "app: \tat com.example.Store.read(Store.java:7)\n",
+                            "app: \t... 2 more\n",
+                        ),
+                        "app: Retrying\n",
+                        concat!(
+                            "app: java.lang.NullPointerException\n",
+                            "app: \tat com.example.Service.retry(Service.java:50)\n",
+                        ),
+                        "app: Done\n",
+                    ],
+                    writer.get_ref().0,
+                    "{chunk}"
+                );
+            }
+        }
+
+        #[test]
+        fn bounded() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), WriteRecorder::default())
+                .with_records(RecordDetection::new().with_atomic(true).with_max_lines(3));
+
+            writer.write_all(b"header\n a\n b\n c\n d\nnext\n").unwrap();
+
+            assert_eq!(
+                vec!["app: header\napp:  a\napp:  b\n", "app:  c\napp:  d\n"],
+                writer.get_ref().0
+            );
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Prefixing text in one call.
+
+use std::io::Write;
+
+use crate::PrefixWriter;
+
+/// Prefix the input like a new [`PrefixWriter`] with the default options
+/// and return the output, including the last line even without a
+/// terminator, for tests and previews.
+#[must_use]
+pub fn render(prefix: &str, input: &[u8]) -> Vec<u8> {
+    let mut writer = PrefixWriter::new(prefix.to_owned(), Vec::new());
+
+    match writer.write_all(input).and_then(|()| writer.flush()) {
+        Ok(()) => writer.writer,
+        Err(_) => unreachable!("writing into a vector never fails"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::render;
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Output of a [`PrefixWriter`] built by hand.
+    fn manual(input: &[u8]) -> Vec<u8> {
+        let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+        writer.write_all(input).unwrap();
+        writer.flush().unwrap();
+
+        writer.get_ref().clone()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            manual,
+            render,
+            PREFIX,
+        };
+
+        #[test]
+        fn same_as_manual() {
+            let inputs: [&[u8]; 6] = [
+                b"",
+                b"first\n",
+                b"first\n\nsecond\r\n",
+                b"first\nincomplete",
+                b"\xffinvalid\n",
+                b"\n\n\n",
+            ];
+
+            for input in inputs {
+                assert_eq!(manual(input), render(PREFIX, input));
+            }
+
+            assert_eq!(
+                "prefix: first\nprefix: incomplete",
+                String::from_utf8_lossy(&render(PREFIX, b"first\nincomplete"))
+            );
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Showing the memory and CPU usage of the process in the prefix.
+
+use std::{
+    io::Write,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use crate::{
+    shared::Shared,
+    PrefixWriter,
+};
+
+/// Memory and CPU usage of the process at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResourceUsage {
+    rss: u64,
+    cpu_percent: f32,
+}
+
+impl ResourceUsage {
+    /// Create a new [`ResourceUsage`] from the resident set size in bytes
+    /// and the CPU usage in percent, where 100 percent is one fully used
+    /// core.
+    #[must_use]
+    pub fn new(rss: u64, cpu_percent: f32) -> Self {
+        Self { rss, cpu_percent }
+    }
+
+    /// Render the usage compactly, like `[rss 1.2G cpu 85%]`.
+    fn render(self) -> String {
+        format!(
+            "[rss {} cpu {:.0}%]",
+            format_bytes(self.rss),
+            self.cpu_percent
+        )
+    }
+}
+
+/// Source of the resource usage.
+type Sampler = Box<dyn FnMut() -> ResourceUsage + Send>;
+
+/// Samples the resource usage at most once per interval and keeps the
+/// rendered usage in between.
+#[derive(Clone)]
+pub(crate) struct ResourceMonitor {
+    interval: Duration,
+    sampler: Shared<Sampler>,
+
+    sampled: Option<(Instant, String)>,
+}
+
+impl std::fmt::Debug for ResourceMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResourceMonitor")
+            .field("interval", &self.interval)
+            .field("sampled", &self.sampled)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ResourceMonitor {
+    pub(crate) fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// The rendered usage at `now`, sampled again once the interval
+    /// passed since the last sample.
+    pub(crate) fn render(&mut self, now: Instant) -> &str {
+        let outdated = self.sampled.as_ref().is_none_or(|(sampled_at, _)| {
+            now.saturating_duration_since(*sampled_at) >= self.interval
+        });
+
+        if outdated {
+            self.sampled = Some((now, self.sampler.lock()().render()));
+        }
+
+        self.sampled.as_ref().map_or("", |(_, rendered)| rendered)
+    }
+}
+
+/// Format a size in bytes with a binary unit and at most two digits
+/// before the unit when there are decimals, like `512B`, `1.2G` or
+/// `340M`.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "K", "M", "G", "T", "P"];
+
+    let mut unit = 0;
+    let mut value = bytes;
+    while value >= 1024 && unit < UNITS.len() - 1 {
+        value /= 1024;
+        unit += 1;
+    }
+
+    if unit == 0 || value >= 10 {
+        return format!("{value}{}", UNITS[unit]);
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let scaled = bytes as f64 / 1024_f64.powi(i32::try_from(unit).unwrap_or(i32::MAX));
+
+    format!("{:.1}{}", (scaled * 10.0).floor() / 10.0, UNITS[unit])
+}
+
+/// Sample the resource usage of the current process with `sysinfo`.
+#[cfg(feature = "sysinfo")]
+fn sysinfo_sampler() -> Sampler {
+    use sysinfo::{
+        ProcessRefreshKind,
+        ProcessesToUpdate,
+        System,
+    };
+
+    let mut system = System::new();
+    let pid = sysinfo::get_current_pid().ok();
+
+    Box::new(move || {
+        let Some(pid) = pid else {
+            return ResourceUsage::new(0, 0.0);
+        };
+
+        system.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&[pid]),
+            false,
+            ProcessRefreshKind::nothing().with_memory().with_cpu(),
+        );
+
+        system
+            .process(pid)
+            .map_or(ResourceUsage::new(0, 0.0), |process| {
+                ResourceUsage::new(process.memory(), process.cpu_usage())
+            })
+    })
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Start the prefix of every line with the memory and CPU usage of
+    /// the process, like `[rss 1.2G cpu 85%] `. The usage is sampled at
+    /// most once per interval, measured with the clock set by
+    /// [`PrefixWriter::with_clock`], lines in between show the last
+    /// sample. The CPU usage is measured since the last sample, so the
+    /// first sample shows no usage.
+    #[cfg(feature = "sysinfo")]
+    #[must_use]
+    pub fn with_resource_usage(self, interval: Duration) -> Self {
+        self.with_resource_sampler(interval, sysinfo_sampler())
+    }
+
+    /// Like [`PrefixWriter::with_resource_usage`] but with the usage
+    /// taken from the sampler, for example to measure something else or
+    /// to control the values in tests.
+    #[must_use]
+    pub fn with_resource_sampler(
+        self,
+        interval: Duration,
+        sampler: impl FnMut() -> ResourceUsage + Send + 'static,
+    ) -> Self {
+        Self {
+            resource_monitor: Some(ResourceMonitor {
+                interval,
+                sampler: Shared::new(Box::new(sampler)),
+
+                sampled: None,
+            }),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::Write,
+        sync::{
+            Arc,
+            Mutex,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    use super::{
+        format_bytes,
+        ResourceUsage,
+    };
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            format_bytes,
+            Arc,
+            Duration,
+            Instant,
+            Mutex,
+            PrefixWriter,
+            ResourceUsage,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn sampling_interval() {
+            let start = Instant::now();
+            let now = Arc::new(Mutex::new(start));
+
+            let mut samples = vec![
+                ResourceUsage::new(3 << 30, 12.0),
+                ResourceUsage::new(1288 << 20, 85.4),
+                ResourceUsage::new(512, 0.0),
+            ]
+            .into_iter();
+
+            let clock = Arc::clone(&now);
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_clock(move || *clock.lock().unwrap())
+                .with_resource_sampler(Duration::from_secs(1), move || samples.next().unwrap());
+
+            writer.write_all(b"first\nsecond\n").unwrap();
+
+            *now.lock().unwrap() = start + Duration::from_millis(999);
+            writer.write_all(b"third\n").unwrap();
+
+            *now.lock().unwrap() = start + Duration::from_secs(1);
+            writer.write_all(b"fourth\n").unwrap();
+
+            *now.lock().unwrap() = start + Duration::from_secs(5);
+            writer.write_all(b"fifth\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "[rss 3.0G cpu 12%] prefix: first\n",
+                    "[rss 3.0G cpu 12%] prefix: second\n",
+                    "[rss 3.0G cpu 12%] prefix: third\n",
+                    "[rss 1.2G cpu 85%] prefix: fourth\n",
+                    "[rss 512B cpu 0%] prefix: fifth\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn byte_sizes() {
+            assert_eq!("0B", format_bytes(0));
+            assert_eq!("1023B", format_bytes(1023));
+            assert_eq!("1.0K", format_bytes(1024));
+            assert_eq!("1.9K", format_bytes(2047));
+            assert_eq!("10K", format_bytes(10 * 1024));
+            assert_eq!("340M", format_bytes(340 << 20));
+            assert_eq!("1.2G", format_bytes(1288 << 20));
+            assert_eq!("16383P", format_bytes(u64::MAX));
+        }
+    }
+}
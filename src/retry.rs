@@ -0,0 +1,256 @@
+//! Retrying writes that failed with transient errors.
+
+use std::{
+    io::{
+        ErrorKind,
+        Write,
+    },
+    time::Duration,
+};
+
+use crate::PrefixWriter;
+
+/// Sleeps between attempts of a write.
+pub(crate) type Sleep = Box<dyn Fn(Duration) + Send>;
+
+/// When and how often writes failing with transient errors are tried
+/// again, see [`PrefixWriter::with_retry_policy`].
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: fn(u32) -> Duration,
+    retry_on: fn(&std::io::Error) -> bool,
+}
+
+impl RetryPolicy {
+    /// Create a new [`RetryPolicy`] trying a write at most `max_attempts`
+    /// times, including the first attempt. Writes failing with
+    /// [`ErrorKind::TimedOut`], [`ErrorKind::ConnectionReset`] or
+    /// [`ErrorKind::ConnectionAborted`] are retried, after waiting 10
+    /// milliseconds for the first retry and twice as long for every
+    /// further retry.
+    #[must_use]
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            backoff: |attempt| Duration::from_millis(10 << attempt.saturating_sub(1).min(16)),
+            retry_on: |err| {
+                matches!(
+                    err.kind(),
+                    ErrorKind::TimedOut | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+                )
+            },
+        }
+    }
+
+    /// Set how long to wait before retrying after the failed attempt with
+    /// the number, starting at 1.
+    #[must_use]
+    pub fn with_backoff(self, backoff: fn(u32) -> Duration) -> Self {
+        Self { backoff, ..self }
+    }
+
+    /// Set which errors are retried.
+    #[must_use]
+    pub fn with_retry_on(self, retry_on: fn(&std::io::Error) -> bool) -> Self {
+        Self { retry_on, ..self }
+    }
+
+    /// Number of the attempts a write may take.
+    pub(crate) fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// How long to wait before retrying after `failed` attempts failed,
+    /// the last one with `err`, or `None` if it is not retried.
+    pub(crate) fn backoff(&self, err: &std::io::Error, failed: u32) -> Option<Duration> {
+        (failed < self.max_attempts && (self.retry_on)(err)).then(|| (self.backoff)(failed))
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Retry writes to the writer that fail with transient errors
+    /// according to the policy instead of returning the error. A retry
+    /// continues with the first byte that was not written, so nothing is
+    /// written twice. Errors are returned once the attempts of a write
+    /// are exhausted. When the start of a line was written by then, the
+    /// next write or flush continues the line where it stopped and
+    /// returns the error if it fails again.
+    #[must_use]
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy: Some(retry_policy),
+            ..self
+        }
+    }
+
+    /// Set how to wait between retries instead of
+    /// [`std::thread::sleep`], for example to not wait in tests.
+    #[must_use]
+    pub fn with_sleep(self, sleep: impl Fn(Duration) + Send + 'static) -> Self {
+        Self {
+            sleep: Box::new(sleep),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        collections::VecDeque,
+        io::{
+            ErrorKind,
+            Write,
+        },
+        sync::{
+            Arc,
+            Mutex,
+        },
+        time::Duration,
+    };
+
+    use super::RetryPolicy;
+    use crate::{
+        PrefixWriter,
+        WriteStage,
+    };
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Follows a script of results for its writes, every entry either
+    /// accepts up to that many bytes or fails with the error kind. Writes
+    /// after the script are accepted completely.
+    #[derive(Debug, Default)]
+    struct ScriptedWriter {
+        buffer: Vec<u8>,
+        script: VecDeque<Result<usize, ErrorKind>>,
+    }
+
+    impl Write for ScriptedWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            match self.script.pop_front() {
+                Some(Ok(count)) => self.buffer.write(&buf[..count.min(buf.len())]),
+                Some(Err(kind)) => Err(kind.into()),
+                None => self.buffer.write(buf),
+            }
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn flaky_writer(
+        script: impl IntoIterator<Item = Result<usize, ErrorKind>>,
+    ) -> (PrefixWriter<ScriptedWriter>, Arc<Mutex<Vec<Duration>>>) {
+        let sleeps = Arc::new(Mutex::new(Vec::new()));
+
+        let sink = ScriptedWriter {
+            buffer: Vec::new(),
+            script: script.into_iter().collect(),
+        };
+
+        let recorded = Arc::clone(&sleeps);
+        let writer = PrefixWriter::new(PREFIX.to_owned(), sink)
+            .with_retry_policy(RetryPolicy::new(3))
+            .with_sleep(move |duration| recorded.lock().unwrap().push(duration));
+
+        (writer, sleeps)
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            flaky_writer,
+            Duration,
+            ErrorKind,
+            Write,
+            WriteStage,
+            PREFIX,
+        };
+
+        #[test]
+        fn success_after_retries() {
+            let (mut writer, sleeps) = flaky_writer([
+                Ok(4),
+                Err(ErrorKind::TimedOut),
+                Ok(6),
+                Err(ErrorKind::ConnectionReset),
+                Err(ErrorKind::TimedOut),
+            ]);
+
+            writer.write_all(b"first\nsecond\n").unwrap();
+
+            assert_eq!(
+                "prefix: first\nprefix: second\n",
+                String::from_utf8_lossy(&writer.get_ref().buffer)
+            );
+            assert_eq!(
+                vec![
+                    Duration::from_millis(10),
+                    Duration::from_millis(10),
+                    Duration::from_millis(20)
+                ],
+                *sleeps.lock().unwrap()
+            );
+            assert_eq!(0, writer.stats().get(PREFIX).unwrap().write_errors);
+        }
+
+        #[test]
+        fn exhausted() {
+            let (mut writer, sleeps) = flaky_writer([
+                Ok(3),
+                Err(ErrorKind::TimedOut),
+                Err(ErrorKind::TimedOut),
+                Err(ErrorKind::TimedOut),
+                Err(ErrorKind::TimedOut),
+                Err(ErrorKind::TimedOut),
+                Err(ErrorKind::TimedOut),
+            ]);
+
+            assert_eq!(6, writer.write_checked(b"first\n").unwrap());
+
+            let err = writer.write_checked(b"second\n").unwrap_err();
+            assert_eq!(ErrorKind::TimedOut, err.kind());
+            assert_eq!(WriteStage::Prefix, err.stage());
+            assert_eq!(1, err.line());
+            assert_eq!(3, err.emitted());
+            assert_eq!(4, sleeps.lock().unwrap().len());
+            assert_eq!(2, writer.stats().get(PREFIX).unwrap().write_errors);
+
+            writer.flush().unwrap();
+            assert_eq!(
+                "prefix: first\n",
+                String::from_utf8_lossy(&writer.get_ref().buffer)
+            );
+        }
+
+        #[test]
+        fn exhausted_then_written() {
+            let (mut writer, _) = flaky_writer([
+                Ok(3),
+                Err(ErrorKind::TimedOut),
+                Err(ErrorKind::TimedOut),
+                Err(ErrorKind::TimedOut),
+            ]);
+
+            writer.write_all(b"first\n").unwrap();
+            writer.write_all(b"second\n").unwrap();
+            assert_eq!(
+                "prefix: first\nprefix: second\n",
+                String::from_utf8_lossy(&writer.get_ref().buffer)
+            );
+        }
+
+        #[test]
+        fn not_retried() {
+            let (mut writer, sleeps) = flaky_writer([Err(ErrorKind::BrokenPipe)]);
+
+            let err = writer.write_checked(b"first\n").unwrap_err();
+            assert_eq!(ErrorKind::BrokenPipe, err.kind());
+            assert!(sleeps.lock().unwrap().is_empty());
+        }
+    }
+}
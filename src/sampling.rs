@@ -0,0 +1,349 @@
+//! Keeping only a deterministic sample of the lines of chatty streams.
+
+use std::io::Write;
+
+use crate::{
+    render_line,
+    PrefixWriteError,
+    PrefixWriter,
+};
+
+/// Which lines are kept, see [`PrefixWriter::with_sampling`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sampling {
+    every: u64,
+    seed: Option<u64>,
+    note_every: Option<u64>,
+}
+
+/// Counters of the lines seen by [`PrefixWriter::with_sampling`], see
+/// [`PrefixWriter::sampling_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SamplingStats {
+    /// Lines that reached the sampling.
+    pub seen: u64,
+
+    /// Lines that were kept.
+    pub kept: u64,
+
+    /// Lines that were skipped.
+    pub skipped: u64,
+}
+
+/// Decides which lines are kept and counts them.
+#[derive(Debug, Clone)]
+pub(crate) struct Sampler {
+    sampling: Sampling,
+    stats: SamplingStats,
+
+    /// Lines skipped since the last note.
+    skipped_since_note: u64,
+
+    /// Seen lines at the last note.
+    noted_at: u64,
+}
+
+impl Sampling {
+    /// Create a new [`Sampling`] keeping every `every`th line, starting
+    /// with the first one. `every` is at least 1, which keeps all lines.
+    #[must_use]
+    pub fn every(every: u64) -> Self {
+        Self {
+            every: every.max(1),
+            seed: None,
+            note_every: None,
+        }
+    }
+
+    /// Instead of counting, keep a line when the hash of its content
+    /// with the seed falls into the sample, which keeps about one of
+    /// `every` lines. The same lines are kept in every run with the same
+    /// seed, no matter which lines came before.
+    #[must_use]
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    /// Write a prefixed note like `[sampling 1/100, 9,900 lines skipped]`
+    /// after every `note_every` seen lines, counting the lines skipped
+    /// since the previous note. The note is written before the next line
+    /// or when flushing, whatever comes first.
+    #[must_use]
+    pub fn with_note_every(self, note_every: u64) -> Self {
+        Self {
+            note_every: Some(note_every.max(1)),
+            ..self
+        }
+    }
+}
+
+impl Sampler {
+    pub(crate) fn new(sampling: Sampling) -> Self {
+        Self {
+            sampling,
+            stats: SamplingStats::default(),
+
+            skipped_since_note: 0,
+            noted_at: 0,
+        }
+    }
+
+    /// Describe the sampling for [`PrefixWriter::describe`].
+    pub(crate) fn describe(&self) -> String {
+        match self.sampling.seed {
+            Some(seed) => format!("sampling 1/{} with seed {seed}", self.sampling.every),
+            None => format!("sampling 1/{}", self.sampling.every),
+        }
+    }
+
+    pub(crate) fn stats(&self) -> SamplingStats {
+        self.stats
+    }
+
+    /// The note that is due before the next line, if any.
+    fn due_note(&self) -> Option<String> {
+        let note_every = self.sampling.note_every?;
+
+        (self.stats.seen > self.noted_at && self.stats.seen.is_multiple_of(note_every)).then(|| {
+            format!(
+                "[sampling 1/{}, {} lines skipped]",
+                self.sampling.every,
+                separate_thousands(self.skipped_since_note)
+            )
+        })
+    }
+
+    /// Count the note returned by [`Sampler::due_note`] as written.
+    fn noted(&mut self) {
+        self.noted_at = self.stats.seen;
+        self.skipped_since_note = 0;
+    }
+
+    /// Decide whether the line is kept and count it.
+    fn keep(&mut self, content: &str) -> bool {
+        let keep = match self.sampling.seed {
+            Some(seed) => fnv1a(seed, content).is_multiple_of(self.sampling.every),
+            None => self.stats.seen.is_multiple_of(self.sampling.every),
+        };
+
+        self.stats.seen += 1;
+        if keep {
+            self.stats.kept += 1;
+        } else {
+            self.stats.skipped += 1;
+            self.skipped_since_note += 1;
+        }
+
+        keep
+    }
+}
+
+/// 64 bit FNV-1a hash of the seed and the content, which unlike the hash
+/// of the standard library is guaranteed to stay the same.
+fn fnv1a(seed: u64, content: &str) -> u64 {
+    seed.to_le_bytes()
+        .iter()
+        .chain(content.as_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Format the number with a comma between every three digits.
+fn separate_thousands(number: u64) -> String {
+    let digits = number.to_string();
+
+    let mut separated = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            separated.push(',');
+        }
+        separated.push(digit);
+    }
+
+    separated
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Keep only a sample of the lines, for streams that are only useful
+    /// statistically. Unlike [`PrefixWriter::with_rate_limit`] the sample
+    /// depends on the lines alone and not on time, so the same input
+    /// always keeps the same lines. Skipped lines count as written for
+    /// everything but the sampling.
+    #[must_use]
+    pub fn with_sampling(self, sampling: Sampling) -> Self {
+        Self {
+            sampler: Some(Sampler::new(sampling)),
+            ..self
+        }
+    }
+
+    /// Counters of the lines seen by [`PrefixWriter::with_sampling`], or
+    /// `None` without sampling.
+    #[must_use]
+    pub fn sampling_stats(&self) -> Option<SamplingStats> {
+        self.sampler.as_ref().map(Sampler::stats)
+    }
+
+    /// Write the due note and decide whether the line is kept.
+    pub(crate) fn sample(
+        &mut self,
+        content: &str,
+        terminator: &str,
+    ) -> Result<bool, PrefixWriteError> {
+        self.write_sampling_note(terminator)?;
+
+        Ok(self
+            .sampler
+            .as_mut()
+            .is_none_or(|sampler| sampler.keep(content)))
+    }
+
+    /// Write the note of the sampling if one is due.
+    pub(crate) fn write_sampling_note(&mut self, terminator: &str) -> Result<(), PrefixWriteError> {
+        let Some(note) = self.sampler.as_ref().and_then(Sampler::due_note) else {
+            return Ok(());
+        };
+
+        let mut rendered = self.start_rendered();
+        render_line(&self.prefix, &note, terminator, &mut rendered);
+
+        self.write_rendered(rendered.as_bytes(), note.len(), terminator.len())?;
+
+        if let Some(ref mut sampler) = self.sampler {
+            sampler.noted();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::{
+        separate_thousands,
+        Sampling,
+        SamplingStats,
+    };
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Write the lines `0` to `lines` and return the written output.
+    fn sampled(sampling: Sampling, lines: u64) -> (String, Option<SamplingStats>) {
+        let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_sampling(sampling);
+
+        for line in 0..lines {
+            writeln!(writer, "{line}").unwrap();
+        }
+
+        (
+            String::from_utf8_lossy(writer.get_ref()).into_owned(),
+            writer.sampling_stats(),
+        )
+    }
+
+    /// The numbers of the lines that were kept.
+    fn kept(output: &str) -> Vec<u64> {
+        output
+            .lines()
+            .filter_map(|line| line.strip_prefix(PREFIX)?.parse().ok())
+            .collect()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            kept,
+            sampled,
+            separate_thousands,
+            PrefixWriter,
+            Sampling,
+            SamplingStats,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn every_nth() {
+            let (output, stats) = sampled(Sampling::every(4), 14);
+
+            assert_eq!(vec![0, 4, 8, 12], kept(&output));
+            assert_eq!(
+                Some(SamplingStats {
+                    seen: 14,
+                    kept: 4,
+                    skipped: 10,
+                }),
+                stats
+            );
+        }
+
+        #[test]
+        fn seeded() {
+            let sampling = Sampling::every(4).with_seed(7);
+
+            let (output, _) = sampled(sampling, 20);
+            assert_eq!(vec![2, 6, 11, 15, 19], kept(&output));
+
+            let (again, _) = sampled(sampling, 20);
+            assert_eq!(output, again);
+
+            let (other_seed, _) = sampled(Sampling::every(4).with_seed(8), 20);
+            assert_eq!(vec![1, 5, 9, 10, 14, 18], kept(&other_seed));
+        }
+
+        #[test]
+        fn note_cadence() {
+            let (output, _) = sampled(Sampling::every(3).with_note_every(5), 11);
+
+            assert_eq!(
+                concat!(
+                    "prefix: 0\n",
+                    "prefix: 3\n",
+                    "prefix: [sampling 1/3, 3 lines skipped]\n",
+                    "prefix: 6\n",
+                    "prefix: 9\n",
+                    "prefix: [sampling 1/3, 3 lines skipped]\n",
+                ),
+                output
+            );
+        }
+
+        #[test]
+        fn note_on_flush() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_sampling(Sampling::every(3).with_note_every(5));
+
+            for line in 0..5 {
+                writeln!(writer, "{line}").unwrap();
+            }
+            writer.flush().unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                concat!(
+                    "prefix: 0\n",
+                    "prefix: 3\n",
+                    "prefix: [sampling 1/3, 3 lines skipped]\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn thousands() {
+            assert_eq!(
+                ["0", "999", "1,000", "9,900", "1,234,567"],
+                [0, 999, 1000, 9900, 1_234_567].map(separate_thousands)
+            );
+        }
+    }
+}
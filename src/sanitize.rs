@@ -0,0 +1,390 @@
+//! Neutralizing control characters and escape sequences of untrusted
+//! output.
+
+use std::io::Write;
+
+use crate::PrefixWriter;
+
+/// Escape character starting escape sequences.
+const ESC: char = '\x1b';
+
+/// How the control characters of lines are neutralized, see
+/// [`PrefixWriter::with_sanitization`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sanitization {
+    pub(crate) visible: bool,
+    pub(crate) colors: bool,
+}
+
+impl Sanitization {
+    /// Create a new [`Sanitization`] removing all control characters
+    /// besides tabs and all escape sequences.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show control characters escaped, like `\u{1b}]0;title\u{7}`,
+    /// instead of removing them together with the sequences they start.
+    #[must_use]
+    pub fn with_visible(self, visible: bool) -> Self {
+        Self { visible, ..self }
+    }
+
+    /// Keep escape sequences that only set colors and styles (SGR), like
+    /// `\x1b[1;31m`, so colored output stays colored.
+    #[must_use]
+    pub fn with_colors(self, colors: bool) -> Self {
+        Self { colors, ..self }
+    }
+}
+
+/// Where in an escape sequence the sanitizer is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Outside of any sequence.
+    Text,
+
+    /// After the escape character.
+    Escape,
+
+    /// In a control sequence (CSI), like cursor movements and colors.
+    Control,
+
+    /// In a command string (OSC, DCS, APC, PM or SOS) that ends with the
+    /// string terminator or a bell.
+    Command,
+
+    /// After an escape character in a command string, which is either
+    /// the string terminator or starts a new sequence.
+    CommandEscape,
+}
+
+/// State machine neutralizing control characters, continuing sequences
+/// split between lines written without a terminator.
+#[derive(Debug, Clone)]
+pub(crate) struct Sanitizer {
+    sanitization: Sanitization,
+    state: State,
+
+    /// The sequence so far while it might still be kept as a color.
+    pending: String,
+}
+
+impl Sanitizer {
+    pub(crate) fn new(sanitization: Sanitization) -> Self {
+        Self {
+            sanitization,
+            state: State::Text,
+            pending: String::new(),
+        }
+    }
+
+    pub(crate) fn sanitization(&self) -> Sanitization {
+        self.sanitization
+    }
+
+    /// Neutralize the content of a line. A sequence that is not complete
+    /// at the end of the content continues with the next content unless
+    /// the line is `terminated`, terminals would not continue it either.
+    pub(crate) fn sanitize(&mut self, content: &str, terminated: bool) -> String {
+        let mut sanitized = String::with_capacity(content.len());
+
+        for character in content.chars() {
+            self.push(character, &mut sanitized);
+        }
+
+        if terminated {
+            self.abort(&mut sanitized);
+            self.state = State::Text;
+        }
+
+        sanitized
+    }
+
+    fn push(&mut self, character: char, out: &mut String) {
+        match self.state {
+            State::Text => self.text(character, out),
+
+            State::Escape => match character {
+                '[' => {
+                    self.pending.push(character);
+                    self.state = State::Control;
+                }
+                ']' | 'P' | '_' | '^' | 'X' => {
+                    self.pending.push(character);
+                    self.discard_pending(out);
+                    self.state = State::Command;
+                }
+                // Intermediate bytes of sequences like `ESC ( B`.
+                '\x20'..='\x2f' => self.pending.push(character),
+                '\x30'..='\x7e' => {
+                    self.pending.push(character);
+                    self.discard_pending(out);
+                    self.state = State::Text;
+                }
+                _ => {
+                    self.abort(out);
+                    self.text(character, out);
+                }
+            },
+
+            State::Control => match character {
+                '\x20'..='\x3f' => self.pending.push(character),
+                '\x40'..='\x7e' => {
+                    self.pending.push(character);
+
+                    if self.sanitization.colors && is_color(&self.pending) {
+                        out.push_str(&self.pending);
+                        self.pending.clear();
+                    } else {
+                        self.discard_pending(out);
+                    }
+
+                    self.state = State::Text;
+                }
+                _ => {
+                    self.abort(out);
+                    self.text(character, out);
+                }
+            },
+
+            State::Command => match character {
+                ESC => {
+                    self.escaped(character, out);
+                    self.state = State::CommandEscape;
+                }
+                '\x07' | '\u{9c}' => {
+                    self.escaped(character, out);
+                    self.state = State::Text;
+                }
+                _ => self.escaped(character, out),
+            },
+
+            State::CommandEscape => {
+                if character == '\\' {
+                    self.escaped(character, out);
+                    self.state = State::Text;
+                } else {
+                    // The escape character already ended the string.
+                    self.pending.push(ESC);
+                    self.state = State::Escape;
+                    self.push(character, out);
+                }
+            }
+        }
+    }
+
+    /// Handle a character outside of any sequence.
+    fn text(&mut self, character: char, out: &mut String) {
+        match character {
+            ESC => {
+                self.pending.push(character);
+                self.state = State::Escape;
+            }
+            '\u{9b}' => {
+                self.pending.push(character);
+                self.state = State::Control;
+            }
+            '\u{90}' | '\u{98}' | '\u{9d}' | '\u{9e}' | '\u{9f}' => {
+                self.escaped(character, out);
+                self.state = State::Command;
+            }
+            '\t' => out.push(character),
+            _ if is_control(character) => self.escaped(character, out),
+            _ => out.push(character),
+        }
+    }
+
+    /// Show the character escaped if sanitized output is visible, drop it
+    /// otherwise.
+    fn escaped(&self, character: char, out: &mut String) {
+        if !self.sanitization.visible {
+            return;
+        }
+
+        if is_control(character) {
+            out.extend(character.escape_debug());
+        } else {
+            out.push(character);
+        }
+    }
+
+    /// Drop the sequence so far, or show it escaped.
+    fn discard_pending(&mut self, out: &mut String) {
+        for character in std::mem::take(&mut self.pending).chars() {
+            self.escaped(character, out);
+        }
+    }
+
+    /// End an incomplete sequence, which is then dropped.
+    fn abort(&mut self, out: &mut String) {
+        self.discard_pending(out);
+        self.state = State::Text;
+    }
+}
+
+/// Whether the character is a C0 or C1 control character or delete.
+fn is_control(character: char) -> bool {
+    matches!(character, '\0'..='\x1f' | '\x7f'..='\u{9f}')
+}
+
+/// Whether the complete control sequence only sets colors and styles.
+fn is_color(sequence: &str) -> bool {
+    sequence
+        .strip_prefix("\x1b[")
+        .and_then(|sequence| sequence.strip_suffix('m'))
+        .is_some_and(|parameters| {
+            parameters
+                .chars()
+                .all(|character| matches!(character, '0'..='9' | ';' | ':'))
+        })
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Neutralize control characters and escape sequences in the lines,
+    /// for output of untrusted programs that could otherwise retitle the
+    /// terminal, move the cursor or set the clipboard with OSC 52. Control
+    /// characters besides tabs, escape sequences and C1 control
+    /// characters are removed or shown escaped. Prefixes and markers are
+    /// not sanitized.
+    ///
+    /// Lines are sanitized once they are complete, a sequence split
+    /// between lines that were flushed without a terminator continues
+    /// with the next line.
+    #[must_use]
+    pub fn with_sanitization(self, sanitization: Sanitization) -> Self {
+        Self {
+            sanitizer: Some(Sanitizer::new(sanitization)),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::Sanitization;
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Lines with a title set, a cursor movement, a clipboard write and a
+    /// color.
+    const INPUT: &str = concat!(
+        "title\x1b]0;pwned\x07 set\n",
+        "cursor\x1b[2;5H moved\n",
+        "clipboard\x1b]52;c;ZWNobyBwd25lZA==\x1b\\ set\n",
+        "\x1b[1;31mred\x1b[0m and \u{9b}2Jc1\n",
+        "bell\x07 and\ttab\n",
+    );
+
+    fn run(sanitization: Sanitization, chunks: &[&str]) -> String {
+        let mut writer =
+            PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_sanitization(sanitization);
+
+        for chunk in chunks {
+            writer.write_all(chunk.as_bytes()).unwrap();
+        }
+
+        String::from_utf8_lossy(writer.get_ref()).into_owned()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            run,
+            PrefixWriter,
+            Sanitization,
+            Write,
+            INPUT,
+            PREFIX,
+        };
+
+        #[test]
+        fn remove() {
+            assert_eq!(
+                concat!(
+                    "prefix: title set\n",
+                    "prefix: cursor moved\n",
+                    "prefix: clipboard set\n",
+                    "prefix: red and c1\n",
+                    "prefix: bell and\ttab\n",
+                ),
+                run(Sanitization::new(), &[INPUT])
+            );
+        }
+
+        #[test]
+        fn keep_colors() {
+            assert_eq!(
+                concat!(
+                    "prefix: title set\n",
+                    "prefix: cursor moved\n",
+                    "prefix: clipboard set\n",
+                    "prefix: \x1b[1;31mred\x1b[0m and c1\n",
+                    "prefix: bell and\ttab\n",
+                ),
+                run(Sanitization::new().with_colors(true), &[INPUT])
+            );
+        }
+
+        #[test]
+        fn visible() {
+            assert_eq!(
+                concat!(
+                    "prefix: title\\u{1b}]0;pwned\\u{7} set\n",
+                    "prefix: cursor\\u{1b}[2;5H moved\n",
+                    "prefix: clipboard\\u{1b}]52;c;ZWNobyBwd25lZA==\\u{1b}\\ set\n",
+                    "prefix: \x1b[1;31mred\x1b[0m and \\u{9b}2Jc1\n",
+                    "prefix: bell\\u{7} and\ttab\n",
+                ),
+                run(
+                    Sanitization::new().with_visible(true).with_colors(true),
+                    &[INPUT]
+                )
+            );
+        }
+
+        #[test]
+        fn split_between_writes() {
+            let chunks = INPUT
+                .char_indices()
+                .map(|(index, character)| &INPUT[index..index + character.len_utf8()])
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                run(Sanitization::new().with_colors(true), &[INPUT]),
+                run(Sanitization::new().with_colors(true), &chunks)
+            );
+        }
+
+        #[test]
+        fn split_between_flushes() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_sanitization(Sanitization::new().with_colors(true));
+
+            writer.write_all(b"before\x1b]0;pw").unwrap();
+            writer.flush().unwrap();
+            writer.write_all(b"ned\x07after\x1b[3").unwrap();
+            writer.flush().unwrap();
+            writer.write_all(b"2mgreen\n").unwrap();
+
+            assert_eq!(
+                "prefix: beforeprefix: afterprefix: \x1b[32mgreen\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn unterminated_command() {
+            assert_eq!(
+                "prefix: title\nprefix: next\n",
+                run(Sanitization::new(), &["title\x1b]0;never ends\nnext\n"])
+            );
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Changing the prefix of a [`PrefixWriter`] for a block of lines.
+
+use std::{
+    io::Write,
+    ops::{
+        Deref,
+        DerefMut,
+    },
+};
+
+use crate::PrefixWriter;
+
+/// Writes through a [`PrefixWriter`] with a temporary prefix, see
+/// [`PrefixWriter::prefix_scope`]. The previous prefix is restored once
+/// the guard is dropped.
+#[derive(Debug)]
+pub struct PrefixGuard<'a, W: Write> {
+    writer: &'a mut PrefixWriter<W>,
+    previous: String,
+}
+
+impl<W: Write> Deref for PrefixGuard<'_, W> {
+    type Target = PrefixWriter<W>;
+
+    fn deref(&self) -> &Self::Target {
+        self.writer
+    }
+}
+
+impl<W: Write> DerefMut for PrefixGuard<'_, W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.writer
+    }
+}
+
+impl<W: Write> Write for PrefixGuard<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Drop for PrefixGuard<'_, W> {
+    fn drop(&mut self) {
+        self.writer.prefix = std::mem::take(&mut self.previous);
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Use the prefix until the returned guard is dropped, then restore
+    /// the previous prefix, also when unwinding. Scopes can be nested by
+    /// creating a scope from the guard. An incomplete line still pending
+    /// once the guard is dropped is written with the restored prefix.
+    pub fn prefix_scope(&mut self, prefix: impl Into<String>) -> PrefixGuard<'_, W> {
+        let previous = std::mem::replace(&mut self.prefix, prefix.into());
+
+        PrefixGuard {
+            writer: self,
+            previous,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn nested_scopes() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            writer.write_all(b"outer\n").unwrap();
+            {
+                let mut scope = writer.prefix_scope("  step: ");
+                scope.write_all(b"first\n").unwrap();
+
+                {
+                    let mut nested = scope.prefix_scope("    detail: ");
+                    nested.write_all(b"second\n").unwrap();
+                }
+
+                scope.write_all(b"third\n").unwrap();
+            }
+            writer.write_all(b"done\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "prefix: outer\n",
+                    "  step: first\n",
+                    "    detail: second\n",
+                    "  step: third\n",
+                    "prefix: done\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
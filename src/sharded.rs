@@ -0,0 +1,316 @@
+//! Prefixing from many threads into one shared writer without
+//! serializing the threads on every line.
+
+use std::{
+    io::Write,
+    sync::{
+        Arc,
+        Mutex,
+        PoisonError,
+    },
+};
+
+use crate::PrefixWriter;
+
+/// Shares one writer between many threads. Every thread gets its own
+/// [`ShardHandle`] which assembles prefixed lines in a private buffer
+/// and only locks the shared writer to push complete lines. Partial
+/// lines of different threads can therefore never mix.
+#[derive(Debug)]
+pub struct ShardedPrefixWriter<W: Write> {
+    pub(crate) prefix: String,
+    sink: Arc<Mutex<W>>,
+}
+
+/// Per-thread handle of a [`ShardedPrefixWriter`]. Incomplete lines stay
+/// in the handle until they are completed or the handle is flushed.
+/// Dropping the handle flushes it, so its last line is pushed to the
+/// shared writer even without a terminator. Errors while flushing on
+/// drop are ignored, call [`Write::flush`] to handle them.
+#[derive(Debug)]
+pub struct ShardHandle<W: Write> {
+    buffer: PrefixWriter<Vec<u8>>,
+    sink: Arc<Mutex<W>>,
+}
+
+impl<W: Write> ShardedPrefixWriter<W> {
+    /// Create a new [`ShardedPrefixWriter`] using the prefix for
+    /// prefixing lines and the writer shared by all handles.
+    pub fn new(prefix: String, writer: W) -> Self {
+        Self {
+            prefix,
+            sink: Arc::new(Mutex::new(writer)),
+        }
+    }
+
+    /// Create a new [`ShardHandle`] with its own line buffer that
+    /// writes into the shared writer.
+    #[must_use]
+    pub fn handle(&self) -> ShardHandle<W> {
+        self.handle_with_prefix(self.prefix.clone())
+    }
+
+    /// Like [`ShardedPrefixWriter::handle`] but the handle uses its own
+    /// prefix instead of the shared one.
+    #[must_use]
+    pub fn handle_with_prefix(&self, prefix: String) -> ShardHandle<W> {
+        ShardHandle {
+            buffer: PrefixWriter::new(prefix, Vec::new()),
+            sink: Arc::clone(&self.sink),
+        }
+    }
+
+    /// Get back the shared writer. Returns `None` as long as handles
+    /// created by [`ShardedPrefixWriter::handle`] are still alive.
+    #[must_use]
+    pub fn into_inner(self) -> Option<W> {
+        Arc::try_unwrap(self.sink)
+            .ok()
+            .map(|sink| sink.into_inner().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl<W: Write> ShardHandle<W> {
+    /// Prefix the next lines of the handle with the prefix.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn set_prefix(&mut self, prefix: String) {
+        self.buffer.prefix = prefix;
+    }
+
+    /// Push all complete lines that are buffered in the handle to the
+    /// shared writer. The lock is only held for this single write. Bytes
+    /// the shared writer accepted are removed from the buffer right away,
+    /// so after an error the next push continues where this one stopped.
+    fn push(&mut self) -> std::io::Result<()> {
+        let pending = self.buffer.get_mut();
+
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut sink = self.sink.lock().unwrap_or_else(PoisonError::into_inner);
+
+        while !pending.is_empty() {
+            match sink.write(pending) {
+                Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    pending.drain(..written);
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ShardHandle<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.push()?;
+        let written = self.buffer.write(buf)?;
+
+        // The bytes are accepted once they are buffered, lines the shared
+        // writer did not take are pushed by the next write or flush,
+        // which also returns the error.
+        let _ = self.push();
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.buffer.flush()?;
+        self.push()?;
+
+        self.sink
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .flush()
+    }
+}
+
+impl<W: Write> Drop for ShardHandle<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use rand::Rng;
+    use std::io::Write;
+
+    use super::ShardedPrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Accepts `limit` bytes, then fails once and accepts everything
+    /// after that.
+    struct FlakyWriter {
+        written: Vec<u8>,
+        limit: Option<usize>,
+    }
+
+    impl Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let Some(limit) = self.limit else {
+                self.written.extend_from_slice(buf);
+                return Ok(buf.len());
+            };
+
+            if self.written.len() == limit {
+                self.limit = None;
+                return Err(std::io::ErrorKind::TimedOut.into());
+            }
+
+            let count = buf.len().min(limit - self.written.len());
+            self.written.extend_from_slice(&buf[..count]);
+
+            Ok(count)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            FlakyWriter,
+            Rng,
+            ShardedPrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn single_handle() {
+            let writer = ShardedPrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            let mut handle = writer.handle();
+            handle.write_all(b"first\nsec").unwrap();
+            handle.write_all(b"ond\nthird").unwrap();
+            handle.flush().unwrap();
+            drop(handle);
+
+            let got = writer.into_inner().unwrap();
+
+            assert_eq!(
+                "prefix: first\nprefix: second\nprefix: third",
+                String::from_utf8_lossy(&got)
+            );
+        }
+
+        #[test]
+        fn write_after_flush() {
+            let writer = ShardedPrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            let mut handle = writer.handle();
+            handle.write_all(b"foo").unwrap();
+            handle.flush().unwrap();
+            handle.write_all(b"bar\n").unwrap();
+            handle.flush().unwrap();
+            drop(handle);
+
+            let got = writer.into_inner().unwrap();
+
+            assert_eq!("prefix: fooprefix: bar\n", String::from_utf8_lossy(&got));
+        }
+
+        #[test]
+        fn partial_push() {
+            let sink = FlakyWriter {
+                written: Vec::new(),
+                limit: Some(5),
+            };
+            let writer = ShardedPrefixWriter::new(PREFIX.to_owned(), sink);
+
+            let mut handle = writer.handle();
+            handle.write_all(b"first\nsecond\n").unwrap();
+            handle.flush().unwrap();
+            drop(handle);
+
+            let got = writer.into_inner().unwrap();
+
+            assert_eq!(
+                "prefix: first\nprefix: second\n",
+                String::from_utf8_lossy(&got.written)
+            );
+        }
+
+        #[test]
+        fn drop_pushes() {
+            let writer = ShardedPrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            let mut handle = writer.handle();
+            handle.write_all(b"first\nlast").unwrap();
+            drop(handle);
+
+            let got = writer.into_inner().unwrap();
+
+            assert_eq!("prefix: first\nprefix: last", String::from_utf8_lossy(&got));
+        }
+
+        #[test]
+        fn into_inner_with_live_handle() {
+            let writer = ShardedPrefixWriter::new(PREFIX.to_owned(), Vec::new());
+            let _handle = writer.handle();
+
+            assert!(writer.into_inner().is_none());
+        }
+
+        #[test]
+        fn stresstest() {
+            const THREADS: usize = 16;
+            const LINES: usize = 500;
+
+            let writer = ShardedPrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            std::thread::scope(|scope| {
+                for thread in 0..THREADS {
+                    let mut handle = writer.handle();
+
+                    scope.spawn(move || {
+                        let mut rng = rand::thread_rng();
+
+                        let mut input = Vec::new();
+                        for line in 0..LINES {
+                            writeln!(input, "thread {thread} line {line}").unwrap();
+                        }
+
+                        let mut input = input.as_slice();
+                        while !input.is_empty() {
+                            let chunk = rng.gen_range(1..=input.len().min(16));
+                            handle.write_all(&input[..chunk]).unwrap();
+                            input = &input[chunk..];
+                        }
+
+                        handle.flush().unwrap();
+                    });
+                }
+            });
+
+            let got = writer.into_inner().unwrap();
+            let got = String::from_utf8_lossy(&got);
+
+            let mut next_line = [0; THREADS];
+            for line in got.lines() {
+                let content = line.strip_prefix(PREFIX).unwrap();
+                let (thread, number) = content
+                    .strip_prefix("thread ")
+                    .and_then(|rest| rest.split_once(" line "))
+                    .unwrap();
+
+                let thread: usize = thread.parse().unwrap();
+                let number: usize = number.parse().unwrap();
+
+                assert_eq!(next_line[thread], number);
+                next_line[thread] += 1;
+            }
+
+            assert_eq!([LINES; THREADS], next_line);
+        }
+    }
+}
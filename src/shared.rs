@@ -0,0 +1,93 @@
+//! Closures and iterators shared between a writer and its previews.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc,
+        Mutex,
+        MutexGuard,
+        PoisonError,
+    },
+};
+
+/// Shared between a [`crate::PrefixWriter`] and the scratch writer of
+/// [`crate::PrefixWriter::preview`], so the preview can use closures and
+/// iterators without taking them from the writer.
+pub(crate) struct Shared<T>(Arc<Mutex<T>>);
+
+impl<T> Shared<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Self(Arc::new(Mutex::new(value)))
+    }
+
+    /// Lock the value. A closure that panicked while it was locked is
+    /// still used.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+/// Items taken from the iterator for previews that the writer did not
+/// consume yet.
+struct Lookahead {
+    iter: Box<dyn Iterator<Item = String> + Send>,
+    peeked: VecDeque<String>,
+}
+
+/// Iterator of [`crate::PrefixWriter::with_prefix_iter`]. The iterator
+/// of a preview only looks ahead, the items it takes are still consumed
+/// by the writer afterwards.
+pub(crate) struct PrefixIter {
+    lookahead: Shared<Lookahead>,
+    previewed: Option<usize>,
+}
+
+impl PrefixIter {
+    pub(crate) fn new(iter: impl Iterator<Item = String> + Send + 'static) -> Self {
+        Self {
+            lookahead: Shared::new(Lookahead {
+                iter: Box::new(iter),
+                peeked: VecDeque::new(),
+            }),
+            previewed: None,
+        }
+    }
+
+    /// Iterator over the same items that only looks ahead.
+    pub(crate) fn preview(&self) -> Self {
+        Self {
+            lookahead: self.lookahead.clone(),
+            previewed: Some(0),
+        }
+    }
+}
+
+impl Iterator for PrefixIter {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let mut lookahead = self.lookahead.lock();
+
+        let Some(ref mut previewed) = self.previewed else {
+            return match lookahead.peeked.pop_front() {
+                Some(item) => Some(item),
+                None => lookahead.iter.next(),
+            };
+        };
+
+        if *previewed == lookahead.peeked.len() {
+            let item = lookahead.iter.next()?;
+            lookahead.peeked.push_back(item);
+        }
+
+        *previewed += 1;
+
+        lookahead.peeked.get(*previewed - 1).cloned()
+    }
+}
@@ -0,0 +1,296 @@
+//! Flushing writers when the process shuts down, so incomplete lines are
+//! not lost.
+
+use std::{
+    cell::RefCell,
+    io::Write,
+    sync::{
+        atomic::{
+            AtomicU64,
+            Ordering,
+        },
+        Arc,
+        Mutex,
+        MutexGuard,
+        PoisonError,
+        Weak,
+    },
+};
+
+use crate::PrefixWriter;
+
+/// Writers registered with [`PrefixWriter::register_for_shutdown`].
+static REGISTRY: Mutex<Vec<(u64, Weak<dyn Flushable>)>> = Mutex::new(Vec::new());
+
+/// Source of the ids identifying registered writers.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Ids of the registered writers the current thread is in the middle
+    /// of writing to or flushing.
+    static IN_USE: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Marks a registered writer as in use by the current thread while it
+/// is alive.
+struct InUse(u64);
+
+impl InUse {
+    fn enter(id: u64) -> Self {
+        IN_USE.with_borrow_mut(|in_use| in_use.push(id));
+
+        Self(id)
+    }
+
+    fn contains(id: u64) -> bool {
+        IN_USE.with_borrow(|in_use| in_use.contains(&id))
+    }
+}
+
+impl Drop for InUse {
+    fn drop(&mut self) {
+        IN_USE.with_borrow_mut(|in_use| {
+            if let Some(index) = in_use.iter().rposition(|id| *id == self.0) {
+                in_use.remove(index);
+            }
+        });
+    }
+}
+
+/// Writer that can be flushed through the registry.
+trait Flushable: Send + Sync {
+    fn flush(&self) -> std::io::Result<()>;
+}
+
+impl<W: Write + Send> Flushable for Mutex<PrefixWriter<W>> {
+    fn flush(&self) -> std::io::Result<()> {
+        self.lock().unwrap_or_else(PoisonError::into_inner).flush()
+    }
+}
+
+/// A [`PrefixWriter`] that is flushed by [`flush_all_registered`], see
+/// [`PrefixWriter::register_for_shutdown`]. Dropping it removes it from
+/// the registry.
+///
+/// Callbacks of the writer, like [`PrefixWriter::with_on_raw_line`], may
+/// call [`flush_all_registered`] in the middle of a write, the writer
+/// that is calling them is skipped. Locking the writer again from a
+/// callback with [`RegisteredWriter::lock`] deadlocks.
+#[derive(Debug)]
+pub struct RegisteredWriter<W: Write + Send + 'static> {
+    writer: Arc<Mutex<PrefixWriter<W>>>,
+    id: u64,
+}
+
+impl<W: Write + Send + 'static> RegisteredWriter<W> {
+    /// Lock the writer, for example to change its configuration or to
+    /// get to the underlying writer.
+    pub fn lock(&self) -> MutexGuard<'_, PrefixWriter<W>> {
+        self.writer.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<W: Write + Send + 'static> Write for RegisteredWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let _in_use = InUse::enter(self.id);
+        self.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let _in_use = InUse::enter(self.id);
+        self.lock().flush()
+    }
+}
+
+impl<W: Write + Send + 'static> Drop for RegisteredWriter<W> {
+    fn drop(&mut self) {
+        registry().retain(|(id, _)| *id != self.id);
+    }
+}
+
+impl<W: Write + Send + 'static> PrefixWriter<W> {
+    /// Register the writer to be flushed by [`flush_all_registered`],
+    /// for example at exit or when the process receives a signal, see
+    /// [`crate::install_flush_on_signal`]. The writer is shared with the
+    /// registry, so it is used through the returned handle.
+    #[must_use]
+    pub fn register_for_shutdown(self) -> RegisteredWriter<W> {
+        let writer = Arc::new(Mutex::new(self));
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+        let flushable: Arc<dyn Flushable> = writer.clone();
+        registry().push((id, Arc::downgrade(&flushable)));
+
+        RegisteredWriter { writer, id }
+    }
+}
+
+/// Flush all writers registered with
+/// [`PrefixWriter::register_for_shutdown`], writing their incomplete
+/// lines. Every writer is flushed even when flushing another one failed.
+/// Writers the calling thread is in the middle of writing to, because
+/// this is called from one of their callbacks, are skipped instead of
+/// deadlocking on them.
+///
+/// # Errors
+///
+/// Returns the first error of flushing a writer.
+pub fn flush_all_registered() -> std::io::Result<()> {
+    // Flush outside the lock of the registry, so a writer being dropped
+    // meanwhile does not wait for all flushes.
+    let writers = registry()
+        .iter()
+        .filter(|(id, _)| !InUse::contains(*id))
+        .filter_map(|(_, writer)| writer.upgrade())
+        .collect::<Vec<_>>();
+
+    writers
+        .iter()
+        .map(|writer| writer.flush())
+        .fold(Ok(()), Result::and)
+}
+
+fn registry() -> MutexGuard<'static, Vec<(u64, Weak<dyn Flushable>)>> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Flush all registered writers when the process receives one of the
+/// signals, then handle the signal the default way, which usually
+/// terminates the process. The signals are received by a thread started
+/// by this, not in a signal handler, so flushing is safe.
+///
+/// # Errors
+///
+/// Returns the errors of registering the signals.
+#[cfg(all(unix, feature = "signal-hook"))]
+pub fn install_flush_on_signal(signals: &[std::ffi::c_int]) -> std::io::Result<()> {
+    let mut signals = signal_hook::iterator::Signals::new(signals)?;
+
+    std::thread::Builder::new()
+        .name("prefix_writer-shutdown".to_owned())
+        .spawn(move || {
+            for signal in signals.forever() {
+                // Nobody is left to report errors to.
+                let _ = flush_all_registered();
+                let _ = signal_hook::low_level::emulate_default_handler(signal);
+            }
+        })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+fn is_registered(id: u64) -> bool {
+    registry().iter().any(|(registered, _)| *registered == id)
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::Write,
+        sync::{
+            Arc,
+            Mutex,
+        },
+    };
+
+    use super::{
+        flush_all_registered,
+        is_registered,
+    };
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Writer that can still be read after it was moved into the writer
+    /// under test.
+    #[derive(Debug, Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            flush_all_registered,
+            is_registered,
+            PrefixWriter,
+            SharedBuffer,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn flush_all_from_callback() {
+            let (output, other) = (SharedBuffer::default(), SharedBuffer::default());
+
+            let mut other_writer =
+                PrefixWriter::new(PREFIX.to_owned(), other.clone()).register_for_shutdown();
+            other_writer.write_all(b"other").unwrap();
+
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), output.clone())
+                .with_on_raw_line(|_, _| flush_all_registered().unwrap())
+                .register_for_shutdown();
+
+            writer.write_all(b"first\nsecond\nthi").unwrap();
+            writer.write_all(b"rd\n").unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                "prefix: first\nprefix: second\nprefix: third\n",
+                output.contents()
+            );
+            assert_eq!("prefix: other", other.contents());
+        }
+
+        #[test]
+        fn flush_registered() {
+            let (first, second) = (SharedBuffer::default(), SharedBuffer::default());
+
+            let mut first_writer =
+                PrefixWriter::new(PREFIX.to_owned(), first.clone()).register_for_shutdown();
+            let mut second_writer =
+                PrefixWriter::new(PREFIX.to_owned(), second.clone()).register_for_shutdown();
+
+            first_writer.write_all(b"complete\nincomplete").unwrap();
+            second_writer.write_all(b"partial").unwrap();
+            assert_eq!("prefix: complete\n", first.contents());
+
+            flush_all_registered().unwrap();
+            assert_eq!("prefix: complete\nprefix: incomplete", first.contents());
+            assert_eq!("prefix: partial", second.contents());
+        }
+
+        #[test]
+        fn deregister_on_drop() {
+            let buffer = SharedBuffer::default();
+
+            let mut writer =
+                PrefixWriter::new(PREFIX.to_owned(), buffer.clone()).register_for_shutdown();
+            let id = writer.id;
+            assert!(is_registered(id));
+
+            writer.write_all(b"dropped").unwrap();
+            drop(writer);
+            assert!(!is_registered(id));
+
+            flush_all_registered().unwrap();
+            assert_eq!("", buffer.contents());
+        }
+    }
+}
@@ -0,0 +1,409 @@
+//! Showing the lines of two streams next to each other in two columns.
+
+use std::{
+    collections::VecDeque,
+    io::Write,
+    sync::{
+        Arc,
+        Mutex,
+        PoisonError,
+    },
+};
+
+use crate::{
+    is_empty_content,
+    Unit,
+};
+
+/// Separator between the left and the right column.
+const SEPARATOR: &str = " ┆ ";
+
+/// Writes the lines of two streams as rows of two columns, the first
+/// line of the left stream next to the first line of the right stream
+/// and so on. Every cell is prefixed with the name of its stream and
+/// fitted to the column width, lines that are too long are truncated with
+/// an ellipsis. Rows are written as soon as both streams completed their
+/// line for it.
+#[derive(Debug)]
+pub struct SideBySideWriter<W: Write> {
+    state: Arc<Mutex<State<W>>>,
+}
+
+/// One of the two streams of a [`SideBySideWriter`].
+#[derive(Debug)]
+pub struct SideHandle<W: Write> {
+    state: Arc<Mutex<State<W>>>,
+    side: usize,
+}
+
+#[derive(Debug)]
+struct State<W: Write> {
+    sink: W,
+    column_width: usize,
+    width_unit: Unit,
+    columns: [Column; 2],
+}
+
+#[derive(Debug)]
+struct Column {
+    name: String,
+    incomplete: Vec<u8>,
+    lines: VecDeque<String>,
+}
+
+impl Column {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            incomplete: Vec::new(),
+            lines: VecDeque::new(),
+        }
+    }
+
+    /// Render the cell of the line at the index, or an empty cell without
+    /// a line. Cells are padded to the width if `padded`, otherwise they
+    /// are only truncated.
+    fn cell(&self, index: usize, width: usize, unit: Unit, padded: bool) -> String {
+        let content = match self.lines.get(index) {
+            Some(line) if !is_empty_content(line.as_bytes()) => format!("{}{line}", self.name),
+            _ => String::new(),
+        };
+
+        if padded {
+            unit.fit(&content, width)
+        } else {
+            unit.truncate(&content, width)
+        }
+    }
+}
+
+impl<W: Write> SideBySideWriter<W> {
+    /// Create a new [`SideBySideWriter`] with the names prefixing the
+    /// cells of the left and the right column and the writer receiving
+    /// the rows. Columns are 40 characters wide.
+    pub fn new(left: String, right: String, writer: W) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                sink: writer,
+                column_width: 40,
+                width_unit: Unit::default(),
+                columns: [Column::new(left), Column::new(right)],
+            })),
+        }
+    }
+
+    /// Set the width of a column, including the name.
+    #[must_use]
+    pub fn with_column_width(self, column_width: usize) -> Self {
+        self.lock().column_width = column_width;
+
+        self
+    }
+
+    /// Set the unit the column width is measured in. Defaults to
+    /// [`Unit::Chars`].
+    #[must_use]
+    pub fn with_width_unit(self, width_unit: Unit) -> Self {
+        self.lock().width_unit = width_unit;
+
+        self
+    }
+
+    /// Create the handles for writing the left and the right stream.
+    #[must_use]
+    pub fn handles(&self) -> (SideHandle<W>, SideHandle<W>) {
+        let handle = |side| SideHandle {
+            state: Arc::clone(&self.state),
+            side,
+        };
+
+        (handle(0), handle(1))
+    }
+
+    /// Write all remaining lines, including incomplete ones, as rows with
+    /// an empty cell for the stream that has no line left.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the writer.
+    pub fn finish(&self) -> std::io::Result<()> {
+        let mut state = self.lock();
+
+        for column in &mut state.columns {
+            if !column.incomplete.is_empty() {
+                let line = String::from_utf8_lossy(&column.incomplete).into_owned();
+                column.incomplete.clear();
+                column.lines.push_back(line);
+            }
+        }
+
+        state.write_rows(true)?;
+        state.sink.flush()
+    }
+
+    /// Get back the writer. Returns `None` as long as handles created by
+    /// [`SideBySideWriter::handles`] are still alive.
+    #[must_use]
+    pub fn into_inner(self) -> Option<W> {
+        Arc::try_unwrap(self.state).ok().map(|state| {
+            state
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner)
+                .sink
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State<W>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<W: Write> State<W> {
+    /// Write the rows for which both streams have a line, or all rows
+    /// when `drain` is set. The lines stay queued when the rows can not
+    /// be written.
+    fn write_rows(&mut self, drain: bool) -> std::io::Result<()> {
+        let [left, right] = &mut self.columns;
+
+        let rows = if drain {
+            left.lines.len().max(right.lines.len())
+        } else {
+            left.lines.len().min(right.lines.len())
+        };
+
+        let mut rendered = String::new();
+        for index in 0..rows {
+            rendered.push_str(&left.cell(index, self.column_width, self.width_unit, true));
+
+            let right_cell = right.cell(index, self.column_width, self.width_unit, false);
+            if right_cell.is_empty() {
+                rendered.push_str(SEPARATOR.trim_end());
+            } else {
+                rendered.push_str(SEPARATOR);
+                rendered.push_str(&right_cell);
+            }
+
+            rendered.push('\n');
+        }
+
+        self.sink.write_all(rendered.as_bytes())?;
+
+        for column in [left, right] {
+            column.lines.drain(..rows.min(column.lines.len()));
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for SideHandle<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.write_rows(false)?;
+
+        let column = &mut state.columns[self.side];
+
+        for byte in buf {
+            if *byte == b'\n' {
+                let line = column
+                    .incomplete
+                    .strip_suffix(b"\r")
+                    .unwrap_or(&column.incomplete);
+                let line = String::from_utf8_lossy(line).into_owned();
+
+                column.incomplete.clear();
+                column.lines.push_back(line);
+            } else {
+                column.incomplete.push(*byte);
+            }
+        }
+
+        // The bytes are accepted once they are queued, rows that can not
+        // be written are written by the next write, flush or finish, which
+        // also returns the error.
+        let _ = state.write_rows(false);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        state.write_rows(false)?;
+        state.sink.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::SideBySideWriter;
+    use crate::Unit;
+
+    /// Fails the first write and accepts everything after that.
+    #[derive(Debug, Default)]
+    struct FailingOnce {
+        failed: bool,
+        data: Vec<u8>,
+    }
+
+    impl Write for FailingOnce {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if !self.failed {
+                self.failed = true;
+                return Err(std::io::ErrorKind::TimedOut.into());
+            }
+
+            self.data.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            FailingOnce,
+            SideBySideWriter,
+            Unit,
+            Write,
+        };
+
+        #[test]
+        fn unequal_line_counts() {
+            let view = SideBySideWriter::new("a: ".to_owned(), "b: ".to_owned(), Vec::new())
+                .with_column_width(10);
+            let (mut a, mut b) = view.handles();
+
+            a.write_all(b"one\ntwo\nthree\n").unwrap();
+            b.write_all(b"first\n\n").unwrap();
+            drop((a, b));
+            view.finish().unwrap();
+
+            assert_eq!(
+                concat!(
+                    "a: one     ┆ b: first\n",
+                    "a: two     ┆\n",
+                    "a: three   ┆\n",
+                ),
+                String::from_utf8_lossy(&view.into_inner().unwrap())
+            );
+        }
+
+        #[test]
+        fn rows_on_arrival() {
+            let view = SideBySideWriter::new("a: ".to_owned(), "b: ".to_owned(), Vec::new())
+                .with_column_width(8);
+            let (mut a, mut b) = view.handles();
+
+            a.write_all(b"one\ntwo\n").unwrap();
+            b.write_all(b"uno\r\n").unwrap();
+
+            let view_rows =
+                || String::from_utf8_lossy(&view.state.lock().unwrap().sink).into_owned();
+            assert_eq!("a: one   ┆ b: uno\n", view_rows());
+
+            b.write_all(b"\ntres\n").unwrap();
+            assert_eq!("a: one   ┆ b: uno\na: two   ┆\n", view_rows());
+
+            view.finish().unwrap();
+            assert_eq!(
+                "a: one   ┆ b: uno\na: two   ┆\n         ┆ b: tres\n",
+                view_rows()
+            );
+        }
+
+        #[test]
+        fn truncation() {
+            let view = SideBySideWriter::new("a: ".to_owned(), "b: ".to_owned(), Vec::new())
+                .with_column_width(8);
+            let (mut a, mut b) = view.handles();
+
+            a.write_all("long line ü\nshort\n".as_bytes()).unwrap();
+            b.write_all("exactly\nüüüüüüü\n".as_bytes()).unwrap();
+            drop((a, b));
+            view.finish().unwrap();
+
+            assert_eq!(
+                concat!("a: long… ┆ b: exac…\n", "a: short ┆ b: üüüü…\n",),
+                String::from_utf8_lossy(&view.into_inner().unwrap())
+            );
+        }
+
+        #[test]
+        fn width_unit() {
+            let rows = |unit| {
+                let view = SideBySideWriter::new("a: ".to_owned(), "b: ".to_owned(), Vec::new())
+                    .with_column_width(8)
+                    .with_width_unit(unit);
+                let (mut a, mut b) = view.handles();
+
+                a.write_all("日本語\n".as_bytes()).unwrap();
+                b.write_all("ü\n".as_bytes()).unwrap();
+                drop((a, b));
+
+                String::from_utf8_lossy(&view.into_inner().unwrap()).into_owned()
+            };
+
+            assert_eq!("a: …   ┆ b: ü\n", rows(Unit::Bytes));
+            assert_eq!("a: 日本語   ┆ b: ü\n", rows(Unit::Chars));
+            #[cfg(feature = "unicode")]
+            assert_eq!("a: 日本… ┆ b: ü\n", rows(Unit::Columns));
+        }
+
+        #[test]
+        fn trailing_whitespace() {
+            let view = SideBySideWriter::new("a: ".to_owned(), "b: ".to_owned(), Vec::new())
+                .with_column_width(8);
+            let (mut a, mut b) = view.handles();
+
+            a.write_all(b"one  \ntwo\n").unwrap();
+            b.write_all(b"uno  \n\t\n").unwrap();
+            drop((a, b));
+
+            assert_eq!(
+                "a: one   ┆ b: uno  \na: two   ┆ b: \t\n",
+                String::from_utf8_lossy(&view.into_inner().unwrap())
+            );
+        }
+
+        #[test]
+        fn failed_write() {
+            let view =
+                SideBySideWriter::new("a: ".to_owned(), "b: ".to_owned(), FailingOnce::default())
+                    .with_column_width(8);
+            let (mut a, mut b) = view.handles();
+
+            a.write_all(b"one\ntwo\n").unwrap();
+            b.write_all(b"uno\n").unwrap();
+            b.write_all(b"dos\n").unwrap();
+            drop((a, b));
+
+            assert_eq!(
+                "a: one   ┆ b: uno\na: two   ┆ b: dos\n",
+                String::from_utf8_lossy(&view.into_inner().unwrap().data)
+            );
+        }
+
+        #[test]
+        fn incomplete_lines_at_finish() {
+            let view = SideBySideWriter::new("a: ".to_owned(), "b: ".to_owned(), Vec::new())
+                .with_column_width(8);
+            let (mut a, mut b) = view.handles();
+
+            a.write_all(b"one\ntw").unwrap();
+            b.write_all(b"uno").unwrap();
+            view.finish().unwrap();
+            drop((a, b));
+
+            assert_eq!(
+                "a: one   ┆ b: uno\na: tw    ┆\n",
+                String::from_utf8_lossy(&view.into_inner().unwrap())
+            );
+        }
+    }
+}
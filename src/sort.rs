@@ -0,0 +1,235 @@
+//! Buffering all lines and writing them sorted or reversed.
+
+use std::{
+    cmp::Ordering,
+    io::Write,
+};
+
+use crate::{
+    shared::Shared,
+    PrefixWriteError,
+    PrefixWriter,
+};
+
+/// Order of lines set by [`PrefixWriter::with_sort`].
+type Compare = Box<dyn Fn(&str, &str) -> Ordering + Send>;
+
+/// Order in which buffered lines are written.
+#[derive(Clone)]
+enum Order {
+    Lexicographic,
+    Compare(Shared<Compare>),
+    Reverse,
+}
+
+/// Lines buffered until the next flush.
+#[derive(Clone)]
+pub(crate) struct Sort {
+    order: Order,
+
+    /// Content and original terminator of every buffered line.
+    lines: Vec<(String, String)>,
+}
+
+impl Sort {
+    pub(crate) fn push(&mut self, content: &str, original_terminator: &str) {
+        self.lines
+            .push((content.to_owned(), original_terminator.to_owned()));
+    }
+
+    pub(crate) fn is_reverse(&self) -> bool {
+        matches!(self.order, Order::Reverse)
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Buffer all lines and write them sorted and prefixed when the
+    /// writer is flushed, for a deterministic output of lines that arrive
+    /// in any order. Lines are compared by their content with `compare`,
+    /// or lexicographically with `None`. Lines that compare equal keep
+    /// their order. All written lines end with the terminator, including
+    /// an incomplete line completed by the flush.
+    ///
+    /// Nothing is written before the flush, so all lines of the stream
+    /// are kept in memory until then and a writer that is never flushed
+    /// never writes anything.
+    ///
+    /// ```
+    /// use std::io::Write;
+    ///
+    /// use prefix_writer::PrefixWriter;
+    ///
+    /// let mut writer = PrefixWriter::new("prefix: ".to_owned(), Vec::new())
+    ///     .with_sort(None::<fn(&str, &str) -> std::cmp::Ordering>);
+    ///
+    /// writer.write_all(b"b\na\n").unwrap();
+    /// writer.flush().unwrap();
+    ///
+    /// assert_eq!(b"prefix: a\nprefix: b\n", writer.get_ref().as_slice());
+    /// ```
+    #[must_use]
+    pub fn with_sort(
+        self,
+        compare: Option<impl Fn(&str, &str) -> Ordering + Send + 'static>,
+    ) -> Self {
+        Self {
+            sort: Some(Sort {
+                order: compare.map_or(Order::Lexicographic, |compare| {
+                    Order::Compare(Shared::new(Box::new(compare)))
+                }),
+                lines: Vec::new(),
+            }),
+            ..self
+        }
+    }
+
+    /// Buffer all lines and write them prefixed in reverse order when the
+    /// writer is flushed, last line first, like `tac`. Like
+    /// [`PrefixWriter::with_sort`], which this replaces, all written lines
+    /// end with the terminator, so an incomplete last line becomes the
+    /// complete first line of the output. Only the lines buffered since
+    /// the last flush are reversed.
+    #[must_use]
+    pub fn with_reverse(self, reverse: bool) -> Self {
+        let sort = if reverse {
+            Some(Sort {
+                order: Order::Reverse,
+                lines: Vec::new(),
+            })
+        } else {
+            self.sort.filter(|sort| !sort.is_reverse())
+        };
+
+        Self { sort, ..self }
+    }
+
+    /// Write the lines buffered by [`PrefixWriter::with_sort`] in their
+    /// order. Lines that could not be written stay buffered.
+    pub(crate) fn write_sorted(&mut self) -> Result<(), PrefixWriteError> {
+        let Some(mut sort) = self.sort.take() else {
+            return Ok(());
+        };
+
+        match sort.order {
+            Order::Lexicographic => sort.lines.sort_by(|a, b| a.0.cmp(&b.0)),
+            Order::Compare(ref compare) => {
+                let compare = compare.lock();
+                sort.lines.sort_by(|a, b| compare(&a.0, &b.0));
+            }
+            Order::Reverse => sort.lines.reverse(),
+        }
+
+        let terminator = self.terminator();
+        let count = sort.lines.len();
+
+        let mut lines = std::mem::take(&mut sort.lines).into_iter().enumerate();
+        let mut result = Ok(());
+        for (index, (content, original_terminator)) in lines.by_ref() {
+            if let Err(err) = self.emit_logical_line(
+                &content,
+                &original_terminator,
+                &terminator,
+                index + 1 == count,
+            ) {
+                sort.lines.push((content, original_terminator));
+                result = Err(err);
+                break;
+            }
+        }
+        sort.lines.extend(lines.map(|(_, line)| line));
+
+        self.sort = Some(sort);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+
+        #[test]
+        fn lexicographic() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_sort(None::<fn(&str, &str) -> std::cmp::Ordering>);
+
+            writer.write_all(b"charlie\nalpha\r\n").unwrap();
+            writer.write_all(b"delta\nbra").unwrap();
+            writer.write_all(b"vo").unwrap();
+            assert!(writer.get_ref().is_empty());
+
+            writer.flush().unwrap();
+            assert_eq!(
+                concat!(
+                    "prefix: alpha\n",
+                    "prefix: bravo\n",
+                    "prefix: charlie\n",
+                    "prefix: delta\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+
+            writer.write_all(b"zulu\necho\n").unwrap();
+            writer.flush().unwrap();
+            assert!(String::from_utf8_lossy(writer.get_ref())
+                .ends_with("prefix: delta\nprefix: echo\nprefix: zulu\n"));
+        }
+
+        #[test]
+        fn reverse() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_reverse(true);
+
+            writer.write_all(b"a\nb\nc\n").unwrap();
+            assert!(writer.get_ref().is_empty());
+
+            writer.flush().unwrap();
+            assert_eq!(
+                "prefix: c\nprefix: b\nprefix: a\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+
+            writer.write_all(b"d\ne").unwrap();
+            writer.flush().unwrap();
+            assert!(String::from_utf8_lossy(writer.get_ref())
+                .ends_with("prefix: a\nprefix: e\nprefix: d\n"));
+        }
+
+        #[test]
+        fn key_function() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_sort(Some(
+                |a: &str, b: &str| {
+                    let key = |line: &str| line.split_once(' ').map(|(key, _)| key.to_owned());
+                    key(a).cmp(&key(b))
+                },
+            ));
+
+            writer
+                .write_all(b"3 third\n1 first\n2 second\n1 also first\n")
+                .unwrap();
+            writer.flush().unwrap();
+
+            assert_eq!(
+                concat!(
+                    "prefix: 1 first\n",
+                    "prefix: 1 also first\n",
+                    "prefix: 2 second\n",
+                    "prefix: 3 third\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
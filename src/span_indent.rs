@@ -0,0 +1,169 @@
+//! Indenting lines by the depth of the `tracing` spans they are written
+//! in.
+
+use std::{
+    cell::Cell,
+    io::Write,
+};
+
+use tracing::{
+    span,
+    Subscriber,
+};
+use tracing_subscriber::{
+    layer::Context,
+    Layer,
+};
+
+use crate::PrefixWriter;
+
+thread_local! {
+    /// Number of spans the current thread is in.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Layer tracking how many spans the current thread is in, for
+/// [`PrefixWriter::with_span_indent`]. Async tasks enter their spans
+/// whenever they are polled, so the depth follows tasks as well.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpanDepthLayer;
+
+impl<S: Subscriber> Layer<S> for SpanDepthLayer {
+    fn on_enter(&self, _id: &span::Id, _ctx: Context<'_, S>) {
+        DEPTH.set(DEPTH.get() + 1);
+    }
+
+    fn on_exit(&self, _id: &span::Id, _ctx: Context<'_, S>) {
+        DEPTH.set(DEPTH.get().saturating_sub(1));
+    }
+}
+
+/// Number of spans tracked by [`SpanDepthLayer`] the current thread is
+/// in.
+pub(crate) fn depth() -> usize {
+    DEPTH.get()
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Follow the prefix of every line with the indent unit once for
+    /// every span the writing thread is in, as tracked by
+    /// [`SpanDepthLayer`]. The depth is taken when a line is complete,
+    /// so a line started in one span and ended in another is indented
+    /// for the span it ended in.
+    #[must_use]
+    pub fn with_span_indent(self, indent_unit: String) -> Self {
+        Self {
+            span_indent: Some(indent_unit),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        fmt::Write as _,
+        io::Write,
+        sync::{
+            Arc,
+            Mutex,
+        },
+    };
+    use tracing::{
+        field::{
+            Field,
+            Visit,
+        },
+        Event,
+        Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::{
+            Context,
+            SubscriberExt,
+        },
+        Layer,
+    };
+
+    use super::SpanDepthLayer;
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "app | ";
+
+    /// Layer writing the message of every event as a line to the writer.
+    struct MessageLayer(Arc<Mutex<PrefixWriter<Vec<u8>>>>);
+
+    struct Message(String);
+
+    impl Visit for Message {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "message" {
+                write!(self.0, "{value:?}").unwrap();
+            }
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for MessageLayer {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let mut message = Message(String::new());
+            event.record(&mut message);
+
+            writeln!(self.0.lock().unwrap(), "{}", message.0).unwrap();
+        }
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            MessageLayer,
+            PrefixWriter,
+            SpanDepthLayer,
+            SubscriberExt,
+            PREFIX,
+        };
+        use std::sync::{
+            Arc,
+            Mutex,
+        };
+
+        #[test]
+        fn nested_spans() {
+            let writer = Arc::new(Mutex::new(
+                PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_span_indent("  ".to_owned()),
+            ));
+
+            let subscriber = tracing_subscriber::registry()
+                .with(SpanDepthLayer)
+                .with(MessageLayer(writer.clone()));
+
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info!("top");
+
+                tracing::info_span!("outer").in_scope(|| {
+                    tracing::info!("in outer");
+
+                    tracing::info_span!("inner").in_scope(|| {
+                        tracing::info!("in inner\nsecond line");
+                    });
+
+                    tracing::info!("back in outer");
+                });
+
+                tracing::info!("top again");
+            });
+
+            assert_eq!(
+                concat!(
+                    "app | top\n",
+                    "app |   in outer\n",
+                    "app |     in inner\n",
+                    "app |     second line\n",
+                    "app |   back in outer\n",
+                    "app | top again\n",
+                ),
+                String::from_utf8_lossy(writer.lock().unwrap().get_ref())
+            );
+        }
+    }
+}
@@ -0,0 +1,414 @@
+//! Statistics of writers for monitoring.
+
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    time::Duration,
+};
+
+#[cfg(feature = "prometheus")]
+use std::fmt::Write as _;
+
+use crate::PrefixWriter;
+
+/// Counters of a single prefix in [`Stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WriterStats {
+    /// Lines written, including notices.
+    pub lines_written: u64,
+
+    /// Bytes written to the writer.
+    pub bytes_written: u64,
+
+    /// Lines dropped by muting, the verbosity, the sampling or the rate
+    /// limit.
+    pub lines_dropped: u64,
+
+    /// Writes of lines and notices that failed with an error of the
+    /// writer. A write that is retried by the retry policy only counts
+    /// once, when it finally fails.
+    pub write_errors: u64,
+
+    /// Time since the last successful write to the writer, or `None` if
+    /// nothing was written yet.
+    pub last_write_age: Option<Duration>,
+}
+
+/// Statistics of one or more writers by their prefix, see
+/// [`PrefixWriter::stats`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Stats {
+    writers: BTreeMap<String, WriterStats>,
+}
+
+impl Stats {
+    /// Statistics of the writers with the prefix, if any.
+    #[must_use]
+    pub fn get(&self, prefix: &str) -> Option<&WriterStats> {
+        self.writers.get(prefix)
+    }
+
+    /// Add the statistics of other writers. Counters of writers with the
+    /// same prefix are summed, the age of the last write is the most
+    /// recent one.
+    pub fn merge(&mut self, other: Stats) {
+        for (prefix, other) in other.writers {
+            let stats = self.writers.entry(prefix).or_default();
+
+            stats.lines_written += other.lines_written;
+            stats.bytes_written += other.bytes_written;
+            stats.lines_dropped += other.lines_dropped;
+            stats.write_errors += other.write_errors;
+            stats.last_write_age = match (stats.last_write_age, other.last_write_age) {
+                (Some(age), Some(other_age)) => Some(age.min(other_age)),
+                (age, other_age) => age.or(other_age),
+            };
+        }
+    }
+
+    /// Render the statistics in the Prometheus text exposition format,
+    /// with the metric names starting with the namespace and every writer
+    /// in its own series with a `prefix` label. Characters of the
+    /// namespace that are not allowed in metric names are replaced with
+    /// `_`. Writers that did not write yet have no last write age.
+    #[cfg(feature = "prometheus")]
+    #[must_use]
+    pub fn to_prometheus(&self, namespace: &str) -> String {
+        let namespace = metric_namespace(namespace);
+        let mut rendered = String::new();
+
+        let counters: [Counter; 4] = [
+            ("lines_written_total", "Lines written.", |stats| {
+                stats.lines_written
+            }),
+            ("bytes_written_total", "Bytes written.", |stats| {
+                stats.bytes_written
+            }),
+            (
+                "lines_dropped_total",
+                "Lines dropped before writing.",
+                |stats| stats.lines_dropped,
+            ),
+            ("write_errors_total", "Writes that failed.", |stats| {
+                stats.write_errors
+            }),
+        ];
+
+        for (name, help, value) in counters {
+            let _ = writeln!(rendered, "# HELP {namespace}{name} {help}");
+            let _ = writeln!(rendered, "# TYPE {namespace}{name} counter");
+
+            for (prefix, stats) in &self.writers {
+                let _ = writeln!(
+                    rendered,
+                    "{namespace}{name}{{prefix=\"{}\"}} {}",
+                    escape_label_value(prefix),
+                    value(stats)
+                );
+            }
+        }
+
+        let name = "last_write_age_seconds";
+        let _ = writeln!(
+            rendered,
+            "# HELP {namespace}{name} Seconds since the last write."
+        );
+        let _ = writeln!(rendered, "# TYPE {namespace}{name} gauge");
+
+        for (prefix, stats) in &self.writers {
+            if let Some(age) = stats.last_write_age {
+                let _ = writeln!(
+                    rendered,
+                    "{namespace}{name}{{prefix=\"{}\"}} {}",
+                    escape_label_value(prefix),
+                    age.as_secs_f64()
+                );
+            }
+        }
+
+        rendered
+    }
+}
+
+/// Name, help text and value of a counter metric.
+#[cfg(feature = "prometheus")]
+type Counter = (&'static str, &'static str, fn(&WriterStats) -> u64);
+
+/// The namespace as the start of metric names, made of letters, digits,
+/// `_` and `:` and not starting with a digit, followed by `_`.
+#[cfg(feature = "prometheus")]
+fn metric_namespace(namespace: &str) -> String {
+    if namespace.is_empty() {
+        return String::new();
+    }
+
+    let mut metric_namespace = namespace
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() || matches!(character, '_' | ':') {
+                character
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    if metric_namespace.starts_with(|character: char| character.is_ascii_digit()) {
+        metric_namespace.insert(0, '_');
+    }
+
+    metric_namespace.push('_');
+    metric_namespace
+}
+
+/// Escape backslashes, double quotes and line feeds of a label value.
+#[cfg(feature = "prometheus")]
+fn escape_label_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for character in value.chars() {
+        match character {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(character),
+        }
+    }
+
+    escaped
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Statistics of the writer under its prefix, which can be merged
+    /// with those of other writers with [`Stats::merge`]. The age of the
+    /// last write is measured with the clock of
+    /// [`PrefixWriter::with_clock`].
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        let stats = WriterStats {
+            lines_written: self.lines_written,
+            bytes_written: self.output_offset,
+            lines_dropped: self.dropped_lines,
+            write_errors: self.write_errors,
+            last_write_age: self
+                .last_write
+                .map(|last_write| self.clock.lock()().saturating_duration_since(last_write)),
+        };
+
+        Stats {
+            writers: BTreeMap::from([(self.prefix.clone(), stats)]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::Write,
+        sync::{
+            Arc,
+            Mutex,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+
+    use super::WriterStats;
+    use crate::PrefixWriter;
+
+    /// Writer failing every write.
+    struct Failing;
+
+    impl Write for Failing {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("failing"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Writer with the prefix and a clock that only advances with the
+    /// returned handle.
+    fn writer(prefix: &str) -> (PrefixWriter<Vec<u8>>, Arc<Mutex<Instant>>) {
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let clock = Arc::clone(&now);
+
+        let writer = PrefixWriter::new(prefix.to_owned(), Vec::new())
+            .with_clock(move || *clock.lock().unwrap());
+
+        (writer, now)
+    }
+
+    /// Check that the output follows the rules of the Prometheus text
+    /// exposition format and return the samples by name and labels.
+    #[cfg(feature = "prometheus")]
+    fn parse_exposition(exposition: &str) -> Vec<(String, String, f64)> {
+        fn is_metric_name(name: &str) -> bool {
+            !name.is_empty()
+                && !name.starts_with(|character: char| character.is_ascii_digit())
+                && name
+                    .chars()
+                    .all(|character| character.is_ascii_alphanumeric() || "_:".contains(character))
+        }
+
+        assert!(exposition.ends_with('\n'));
+
+        let mut samples = Vec::new();
+        let mut typed = Vec::<String>::new();
+
+        for line in exposition.lines() {
+            if let Some(help) = line.strip_prefix("# HELP ") {
+                let (name, text) = help.split_once(' ').unwrap();
+                assert!(is_metric_name(name), "{line}");
+                assert!(!text.is_empty(), "{line}");
+                continue;
+            }
+
+            if let Some(kind) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = kind.split_once(' ').unwrap();
+                assert!(is_metric_name(name), "{line}");
+                assert!(matches!(kind, "counter" | "gauge"), "{line}");
+                assert!(!typed.iter().any(|typed| typed == name), "{line}");
+                if kind == "counter" {
+                    assert!(name.ends_with("_total"), "{line}");
+                }
+                typed.push(name.to_owned());
+                continue;
+            }
+
+            let (name, rest) = line.split_once("{prefix=\"").unwrap();
+            assert!(is_metric_name(name), "{line}");
+            assert_eq!(Some(name), typed.last().map(String::as_str), "{line}");
+
+            let mut label = String::new();
+            let mut characters = rest.chars();
+            loop {
+                match characters.next().unwrap() {
+                    '\\' => match characters.next().unwrap() {
+                        '\\' => label.push('\\'),
+                        '"' => label.push('"'),
+                        'n' => label.push('\n'),
+                        other => panic!("invalid escape `\\{other}` in {line}"),
+                    },
+                    '"' => break,
+                    other => label.push(other),
+                }
+            }
+
+            let value = characters.as_str().strip_prefix("} ").unwrap();
+            samples.push((name.to_owned(), label, value.parse().unwrap()));
+        }
+
+        samples
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            writer,
+            Duration,
+            Failing,
+            PrefixWriter,
+            Write,
+            WriterStats,
+        };
+
+        #[test]
+        fn counters() {
+            let (mut writer, now) = writer("web: ");
+            assert_eq!(Some(&WriterStats::default()), writer.stats().get("web: "));
+
+            writer.write_all(b"first\nsecond\n").unwrap();
+            writer.mute();
+            writer.write_all(b"muted\n").unwrap();
+            *now.lock().unwrap() += Duration::from_secs(3);
+
+            assert_eq!(
+                Some(&WriterStats {
+                    lines_written: 2,
+                    bytes_written: 23,
+                    lines_dropped: 1,
+                    write_errors: 0,
+                    last_write_age: Some(Duration::from_secs(3)),
+                }),
+                writer.stats().get("web: ")
+            );
+
+            let mut failing = PrefixWriter::new("db: ".to_owned(), Failing);
+            failing.write_all(b"lost\n").unwrap_err();
+            assert_eq!(1, failing.stats().get("db: ").unwrap().write_errors);
+        }
+
+        #[test]
+        fn merge() {
+            let (mut first, first_now) = writer("web: ");
+            let (mut second, _) = writer("web: ");
+            let (mut other, _) = writer("db: ");
+
+            first.write_all(b"first\n").unwrap();
+            *first_now.lock().unwrap() += Duration::from_secs(5);
+            second.write_all(b"second\nthird\n").unwrap();
+            other.write_all(b"other\n").unwrap();
+
+            let mut stats = first.stats();
+            stats.merge(second.stats());
+            stats.merge(other.stats());
+
+            assert_eq!(
+                Some(&WriterStats {
+                    lines_written: 3,
+                    bytes_written: 34,
+                    lines_dropped: 0,
+                    write_errors: 0,
+                    last_write_age: Some(Duration::ZERO),
+                }),
+                stats.get("web: ")
+            );
+            assert_eq!(1, stats.get("db: ").unwrap().lines_written);
+            assert_eq!(None, stats.get("cache: "));
+        }
+
+        #[cfg(feature = "prometheus")]
+        #[test]
+        fn prometheus() {
+            use super::parse_exposition;
+
+            let (mut web, _) = writer("web \"quoted\" \\ ");
+            let (idle, _) = writer("idle: ");
+            web.write_all(b"first\nsecond\n").unwrap();
+
+            let mut stats = web.stats();
+            stats.merge(idle.stats());
+
+            let exposition = stats.to_prometheus("prefix-writer");
+
+            assert!(exposition.contains(
+                "prefix_writer_lines_written_total{prefix=\"web \\\"quoted\\\" \\\\ \"} 2\n"
+            ));
+
+            let samples = parse_exposition(&exposition);
+            assert_eq!(9, samples.len());
+            assert!(samples.contains(&(
+                "prefix_writer_bytes_written_total".to_owned(),
+                "web \"quoted\" \\ ".to_owned(),
+                43.0
+            )));
+            assert!(samples.contains(&(
+                "prefix_writer_last_write_age_seconds".to_owned(),
+                "web \"quoted\" \\ ".to_owned(),
+                0.0
+            )));
+            assert!(!samples.iter().any(|(name, prefix, _)| {
+                name == "prefix_writer_last_write_age_seconds" && prefix == "idle: "
+            }));
+
+            parse_exposition(&stats.to_prometheus("9"));
+            parse_exposition(&stats.to_prometheus(""));
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Prefixing a [`Stream`] of chunks of bytes.
+
+use std::{
+    io::Write,
+    pin::Pin,
+    task::{
+        ready,
+        Context,
+        Poll,
+    },
+};
+
+use bytes::Bytes;
+use futures_core::Stream;
+
+use crate::{
+    incomplete_char_len,
+    PrefixWriter,
+};
+
+/// Stream of prefixed lines created by [`prefix_stream`].
+#[derive(Debug)]
+pub struct PrefixStream<S> {
+    stream: Option<S>,
+    writer: PrefixWriter<Vec<u8>>,
+
+    /// Start of a character that continues in the next chunk.
+    held: Vec<u8>,
+}
+
+/// Prefix the lines of a stream of chunks, like the frames of a framed
+/// source. Every item of the returned stream contains only complete
+/// prefixed lines, the start of a line is carried over and merged with
+/// the next chunk, as is a character split between chunks. When the
+/// stream ends the incomplete line is the last item. Errors of the
+/// stream are passed on as they are.
+pub fn prefix_stream<S>(stream: S, prefix: String) -> PrefixStream<S>
+where
+    S: Stream<Item = std::io::Result<Bytes>> + Unpin,
+{
+    PrefixStream {
+        stream: Some(stream),
+        writer: PrefixWriter::new(prefix, Vec::new()),
+        held: Vec::new(),
+    }
+}
+
+impl<S> PrefixStream<S> {
+    /// Take the prefixed lines written so far, if there are any.
+    fn take_lines(&mut self) -> Option<Bytes> {
+        let lines = std::mem::take(self.writer.get_mut());
+
+        (!lines.is_empty()).then(|| lines.into())
+    }
+}
+
+impl<S> Stream for PrefixStream<S>
+where
+    S: Stream<Item = std::io::Result<Bytes>> + Unpin,
+{
+    type Item = std::io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        while let Some(ref mut stream) = this.stream {
+            let Some(chunk) = ready!(Pin::new(stream).poll_next(cx)) else {
+                this.stream = None;
+
+                let held = std::mem::take(&mut this.held);
+                if let Err(err) = this
+                    .writer
+                    .write_all(&held)
+                    .and_then(|()| this.writer.flush())
+                {
+                    return Poll::Ready(Some(Err(err)));
+                }
+
+                break;
+            };
+
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(err) => return Poll::Ready(Some(Err(err))),
+            };
+
+            this.held.extend_from_slice(&chunk);
+            let complete = this.held.len() - incomplete_char_len(&this.held);
+
+            if let Err(err) = this.writer.write_all(&this.held[..complete]) {
+                return Poll::Ready(Some(Err(err)));
+            }
+            this.held.drain(..complete);
+
+            if let Some(lines) = this.take_lines() {
+                return Poll::Ready(Some(Ok(lines)));
+            }
+        }
+
+        Poll::Ready(this.take_lines().map(Ok))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use futures::{
+        executor::block_on,
+        stream,
+        StreamExt,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::prefix_stream;
+
+    const PREFIX: &str = "prefix: ";
+
+    /// Prefix the chunks, returning the items of the prefixed stream.
+    fn items(chunks: Vec<std::io::Result<&'static [u8]>>) -> Vec<Result<String, String>> {
+        let chunks = stream::iter(
+            chunks
+                .into_iter()
+                .map(|chunk| chunk.map(Bytes::from_static)),
+        );
+
+        block_on(prefix_stream(chunks, PREFIX.to_owned()).collect::<Vec<_>>())
+            .into_iter()
+            .map(|item| {
+                item.map(|lines| String::from_utf8(lines.to_vec()).unwrap())
+                    .map_err(|err| err.to_string())
+            })
+            .collect()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            items,
+        };
+
+        #[test]
+        fn line_aligned() {
+            let input: &[u8] = "first\nsec|ond\nthi|rd\n|gr\u{fc}\u{df}e|\n|end".as_bytes();
+            let chunks = input
+                .split(|byte| *byte == b'|')
+                .map(Ok)
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                vec![
+                    Ok("prefix: first\n".to_owned()),
+                    Ok("prefix: second\n".to_owned()),
+                    Ok("prefix: third\n".to_owned()),
+                    Ok("prefix: grüße\n".to_owned()),
+                    Ok("prefix: end".to_owned()),
+                ],
+                items(chunks)
+            );
+        }
+
+        #[test]
+        fn split_characters() {
+            let input = "日本\nü\n".as_bytes();
+            let chunks = input.chunks(1).map(Ok).collect::<Vec<_>>();
+
+            assert_eq!(
+                vec![
+                    Ok("prefix: 日本\n".to_owned()),
+                    Ok("prefix: ü\n".to_owned())
+                ],
+                items(chunks)
+            );
+        }
+
+        #[test]
+        fn errors_pass_through() {
+            let chunks = vec![
+                Ok(&b"first\nsec"[..]),
+                Err(std::io::Error::other("broken frame")),
+                Ok(&b"ond\n"[..]),
+            ];
+
+            assert_eq!(
+                vec![
+                    Ok("prefix: first\n".to_owned()),
+                    Err("broken frame".to_owned()),
+                    Ok("prefix: second\n".to_owned()),
+                ],
+                items(chunks)
+            );
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Prefixing the lines of tokio tasks with a prefix set for the task.
+
+use std::{
+    future::Future,
+    io::Write,
+};
+
+use crate::{
+    ShardHandle,
+    ShardedPrefixWriter,
+};
+
+tokio::task_local! {
+    /// Prefix set by [`with_task_prefix`] for the current task.
+    static TASK_PREFIX: String;
+}
+
+/// Run the future with the prefix for all lines it writes through a
+/// [`TaskPrefixedWriter`], like the name of a task or the id of a
+/// request. Nested calls layer: the prefix of the inner call follows the
+/// prefix of the outer one, so `with_task_prefix("req-1 ", ..)` around
+/// `with_task_prefix("db ", ..)` prefixes lines with `req-1 db `.
+pub async fn with_task_prefix<F: Future>(prefix: String, future: F) -> F::Output {
+    let prefix = TASK_PREFIX
+        .try_with(|outer| format!("{outer}{prefix}"))
+        .unwrap_or(prefix);
+
+    TASK_PREFIX.scope(prefix, future).await
+}
+
+/// Handle of a [`ShardedPrefixWriter`] that prefixes lines with the
+/// prefix of the task writing them, set by [`with_task_prefix`], or the
+/// prefix of the [`ShardedPrefixWriter`] outside of such a task. Lines
+/// are pushed to the shared writer as a whole, like those of a
+/// [`ShardHandle`].
+#[derive(Debug)]
+pub struct TaskPrefixedWriter<W: Write> {
+    handle: ShardHandle<W>,
+    default_prefix: String,
+}
+
+impl<W: Write> ShardedPrefixWriter<W> {
+    /// Create a new [`TaskPrefixedWriter`] writing into the shared
+    /// writer. Every task needs its own, incomplete lines stay in it
+    /// until they are completed or it is flushed.
+    #[must_use]
+    pub fn task_handle(&self) -> TaskPrefixedWriter<W> {
+        TaskPrefixedWriter {
+            handle: self.handle(),
+            default_prefix: self.prefix.clone(),
+        }
+    }
+}
+
+impl<W: Write> TaskPrefixedWriter<W> {
+    /// Use the prefix of the current task for the next lines.
+    fn update_prefix(&mut self) {
+        let prefix = TASK_PREFIX
+            .try_with(Clone::clone)
+            .unwrap_or_else(|_| self.default_prefix.clone());
+
+        self.handle.set_prefix(prefix);
+    }
+}
+
+impl<W: Write> Write for TaskPrefixedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update_prefix();
+        self.handle.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.update_prefix();
+        self.handle.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::Write,
+        pin::Pin,
+        task::{
+            Context,
+            Poll,
+            Waker,
+        },
+    };
+    use tokio::io::{
+        AsyncWrite,
+        DuplexStream,
+    };
+
+    use super::with_task_prefix;
+    use crate::ShardedPrefixWriter;
+
+    const PREFIX: &str = "default: ";
+
+    /// Writes to the client side of a duplex stream without waiting, so
+    /// the stream needs room for everything written.
+    #[derive(Debug)]
+    struct DuplexWriter(DuplexStream);
+
+    impl Write for DuplexWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let mut context = Context::from_waker(Waker::noop());
+
+            match Pin::new(&mut self.0).poll_write(&mut context, buf) {
+                Poll::Ready(result) => result,
+                Poll::Pending => Err(std::io::ErrorKind::WouldBlock.into()),
+            }
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            with_task_prefix,
+            DuplexWriter,
+            ShardedPrefixWriter,
+            Write,
+            PREFIX,
+        };
+        use std::sync::Arc;
+        use tokio::io::AsyncReadExt;
+
+        #[tokio::test]
+        async fn task_prefixes() {
+            const TASKS: usize = 4;
+            const LINES: usize = 20;
+
+            let (client, mut server) = tokio::io::duplex(64 * 1024);
+            let reader = tokio::spawn(async move {
+                let mut output = String::new();
+                server.read_to_string(&mut output).await.unwrap();
+                output
+            });
+
+            let writer = Arc::new(ShardedPrefixWriter::new(
+                PREFIX.to_owned(),
+                DuplexWriter(client),
+            ));
+
+            let tasks = (0..TASKS)
+                .map(|task| {
+                    let writer = Arc::clone(&writer);
+
+                    tokio::spawn(with_task_prefix(format!("task {task}: "), async move {
+                        let mut handle = writer.task_handle();
+
+                        for line in 0..LINES {
+                            write!(handle, "line {line} of ").unwrap();
+                            tokio::task::yield_now().await;
+                            writeln!(handle, "task {task}").unwrap();
+                        }
+                    }))
+                })
+                .collect::<Vec<_>>();
+
+            for task in tasks {
+                task.await.unwrap();
+            }
+
+            let mut handle = writer.task_handle();
+            with_task_prefix("outer ".to_owned(), async {
+                with_task_prefix("inner: ".to_owned(), async {
+                    writeln!(writer.task_handle(), "nested").unwrap();
+                })
+                .await;
+            })
+            .await;
+            writeln!(handle, "outside").unwrap();
+            drop(handle);
+
+            drop(Arc::into_inner(writer).unwrap().into_inner().unwrap());
+            let output = reader.await.unwrap();
+            let mut lines = output.lines().collect::<Vec<_>>();
+
+            assert_eq!(
+                vec!["outer inner: nested", "default: outside"],
+                lines.split_off(TASKS * LINES)
+            );
+            assert_eq!(TASKS * LINES, lines.len());
+            for line in lines {
+                let (prefix, task) = line.split_once(": line ").unwrap();
+                assert_eq!(prefix, format!("task {}", task.rsplit(' ').next().unwrap()));
+            }
+        }
+    }
+}
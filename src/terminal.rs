@@ -0,0 +1,263 @@
+//! Prefixing only when the output is shown on a terminal.
+
+use std::{
+    ffi::OsStr,
+    io::{
+        IsTerminal,
+        Write,
+    },
+};
+
+use crate::PrefixWriter;
+
+/// Environment variable overriding [`PrefixMode::Auto`], `0` disables
+/// prefixing and any other value enables it.
+const FORCE_PREFIX: &str = "FORCE_PREFIX";
+
+/// Whether a writer prefixes at all, see
+/// [`PrefixWriter::with_prefix_mode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PrefixMode {
+    /// Always prefix.
+    Always,
+
+    /// Never prefix, pass everything through as it is.
+    Never,
+
+    /// Prefix when the writer is a terminal, unless the `FORCE_PREFIX`
+    /// environment variable says otherwise.
+    #[default]
+    Auto,
+}
+
+impl PrefixMode {
+    /// Whether to prefix with the value of `FORCE_PREFIX` and whether the
+    /// writer is a terminal.
+    fn enabled(self, force_prefix: Option<&OsStr>, is_terminal: impl FnOnce() -> bool) -> bool {
+        match (self, force_prefix) {
+            (Self::Always, _) => true,
+            (Self::Never, _) => false,
+            (Self::Auto, Some(force_prefix)) if !force_prefix.is_empty() => force_prefix != "0",
+            (Self::Auto, _) => is_terminal(),
+        }
+    }
+}
+
+impl<W: Write + IsTerminal> PrefixWriter<W> {
+    /// Create a new [`PrefixWriter`] like [`PrefixWriter::new`] that only
+    /// prefixes when the writer is a terminal, with
+    /// [`PrefixMode::Auto`]. Output redirected to a file or piped to
+    /// another program is passed through as it is.
+    pub fn auto(prefix: String, writer: W) -> Self {
+        Self::new(prefix, writer).with_prefix_mode(PrefixMode::Auto)
+    }
+
+    /// Decide whether the writer prefixes at all. When it does not it is a
+    /// passthrough, every write and flush goes straight to the writer
+    /// without any of the other options, see
+    /// [`PrefixWriter::prefixing_enabled`]. Unlike
+    /// [`PrefixWriter::with_tty_aware`], which only leaves out the prefix,
+    /// nothing is buffered or transformed. The writer and `FORCE_PREFIX`
+    /// are checked once, when this is set.
+    #[must_use]
+    pub fn with_prefix_mode(self, prefix_mode: PrefixMode) -> Self {
+        let force_prefix = std::env::var_os(FORCE_PREFIX);
+        let enabled = prefix_mode.enabled(force_prefix.as_deref(), || self.writer.is_terminal());
+
+        Self {
+            passthrough: !enabled,
+            ..self
+        }
+    }
+
+    /// Only prefix lines when the writer is a terminal, so output piped
+    /// to another program or redirected to a file stays as it is. The
+    /// writer is checked once, when this is set. Use
+    /// [`PrefixWriter::with_terminal`] to override the check.
+    #[must_use]
+    pub fn with_tty_aware(self, tty_aware: bool) -> Self {
+        let terminal = tty_aware.then(|| self.writer.is_terminal());
+
+        Self { terminal, ..self }
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Only prefix lines if `terminal` is set, like
+    /// [`PrefixWriter::with_tty_aware`] but with the result of the check
+    /// given instead of checking the writer. Also works for writers that
+    /// do not implement [`IsTerminal`], like buffers.
+    #[must_use]
+    pub fn with_terminal(self, terminal: bool) -> Self {
+        Self {
+            terminal: Some(terminal),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::{
+        Read,
+        Seek,
+        Write,
+    };
+
+    use super::PrefixMode;
+    use crate::PrefixWriter;
+
+    const PREFIX: &str = "prefix: ";
+    const INPUT: &[u8] = b"first\nsecond\n";
+
+    /// Write the chunks to a temporary file with the writer built from it
+    /// and return what ended up in the file.
+    fn file_output(
+        name: &str,
+        chunks: &[&[u8]],
+        build: impl FnOnce(std::fs::File) -> PrefixWriter<std::fs::File>,
+    ) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!(
+            "prefix_writer-terminal-{name}-{}",
+            std::process::id()
+        ));
+
+        let file = std::fs::File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+
+        let mut writer = build(file);
+        for chunk in chunks {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let mut file = writer.get_ref();
+        let mut output = Vec::new();
+        file.rewind().unwrap();
+        file.read_to_end(&mut output).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+
+        output
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            file_output,
+            PrefixMode,
+            PrefixWriter,
+            Read,
+            Seek,
+            Write,
+            INPUT,
+            PREFIX,
+        };
+        use std::ffi::OsStr;
+
+        #[test]
+        fn buffer_is_no_terminal() {
+            let run = |writer: PrefixWriter<Vec<u8>>| {
+                let mut writer = writer;
+                writer.write_all(INPUT).unwrap();
+
+                String::from_utf8_lossy(writer.get_ref()).into_owned()
+            };
+
+            let writer = || PrefixWriter::new(PREFIX.to_owned(), Vec::new());
+
+            assert_eq!("first\nsecond\n", run(writer().with_terminal(false)));
+            assert_eq!(
+                "prefix: first\nprefix: second\n",
+                run(writer().with_terminal(true))
+            );
+        }
+
+        #[test]
+        fn tty_aware_file() {
+            let path =
+                std::env::temp_dir().join(format!("prefix_writer-terminal-{}", std::process::id()));
+
+            let run = |tty_aware, terminal: Option<bool>| {
+                let file = std::fs::File::options()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(&path)
+                    .unwrap();
+
+                let mut writer =
+                    PrefixWriter::new(PREFIX.to_owned(), file).with_tty_aware(tty_aware);
+                if let Some(terminal) = terminal {
+                    writer = writer.with_terminal(terminal);
+                }
+                writer.write_all(INPUT).unwrap();
+
+                let mut file = writer.get_ref();
+                let mut output = String::new();
+                file.rewind().unwrap();
+                file.read_to_string(&mut output).unwrap();
+
+                output
+            };
+
+            assert_eq!("first\nsecond\n", run(true, None));
+            assert_eq!("prefix: first\nprefix: second\n", run(false, None));
+            assert_eq!("prefix: first\nprefix: second\n", run(true, Some(true)));
+
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn prefix_mode_overrides() {
+            let terminal = || true;
+            let no_terminal = || false;
+
+            assert!(PrefixMode::Always.enabled(None, no_terminal));
+            assert!(PrefixMode::Always.enabled(Some(OsStr::new("0")), no_terminal));
+            assert!(!PrefixMode::Never.enabled(None, terminal));
+            assert!(!PrefixMode::Never.enabled(Some(OsStr::new("1")), terminal));
+
+            assert!(PrefixMode::Auto.enabled(None, terminal));
+            assert!(!PrefixMode::Auto.enabled(None, no_terminal));
+            assert!(PrefixMode::Auto.enabled(Some(OsStr::new("1")), no_terminal));
+            assert!(!PrefixMode::Auto.enabled(Some(OsStr::new("0")), terminal));
+            assert!(!PrefixMode::Auto.enabled(Some(OsStr::new("")), no_terminal));
+        }
+
+        #[test]
+        fn passthrough() {
+            let chunks: &[&[u8]] = &[b"fir", b"st\r\n\ninvalid \xff\xfe", b"\nlast \x1b[1m"];
+
+            let output = file_output("never", chunks, |file| {
+                let writer = PrefixWriter::new(PREFIX.to_owned(), file)
+                    .with_running_count(2)
+                    .with_prefix_mode(PrefixMode::Never);
+                assert!(!writer.prefixing_enabled());
+                writer
+            });
+
+            assert_eq!(chunks.concat(), output);
+        }
+
+        #[test]
+        fn always_on_file() {
+            let output = file_output("always", &[INPUT], |file| {
+                let writer =
+                    PrefixWriter::new(PREFIX.to_owned(), file).with_prefix_mode(PrefixMode::Always);
+                assert!(writer.prefixing_enabled());
+                writer
+            });
+
+            assert_eq!(b"prefix: first\nprefix: second\n".to_vec(), output);
+        }
+    }
+}
@@ -0,0 +1,546 @@
+//! Formatting wall clock time of a configurable clock and time zone.
+
+use std::{
+    fmt::Write as _,
+    io::Write,
+};
+
+use time::{
+    OffsetDateTime,
+    UtcOffset,
+};
+
+use crate::{
+    shared::Shared,
+    LineContext,
+    Prefix,
+    PrefixWriter,
+};
+
+/// Clock and time zone of timestamps, see
+/// [`PrefixWriter::with_timestamp_clock`] and [`Timestamp`].
+#[derive(Default)]
+pub enum ClockConfig {
+    /// The system clock in UTC, like `2024-05-17T08:03:09.042Z`.
+    #[default]
+    Utc,
+
+    /// The system clock in the local time zone, like
+    /// `2024-05-17T10:03:09.042+02:00`. Falls back to UTC when the local
+    /// offset can not be determined, which on Unix is the case while the
+    /// process has more than one thread.
+    Local,
+
+    /// The system clock at the offset, like
+    /// `2024-05-17T10:03:09.042+02:00` for two hours east of UTC.
+    FixedOffset(UtcOffset),
+
+    /// A custom clock returning the time at its offset, for example a
+    /// synthetic clock in tests.
+    Custom(Box<dyn FnMut() -> OffsetDateTime + Send>),
+}
+
+impl std::fmt::Debug for ClockConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Utc => f.write_str("Utc"),
+            Self::Local => f.write_str("Local"),
+            Self::FixedOffset(offset) => f.debug_tuple("FixedOffset").field(offset).finish(),
+            Self::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+impl ClockConfig {
+    /// The current time of the clock.
+    fn time(&mut self) -> OffsetDateTime {
+        match self {
+            Self::Utc => OffsetDateTime::now_utc(),
+            Self::Local => OffsetDateTime::now_utc()
+                .to_offset(UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC)),
+            Self::FixedOffset(offset) => OffsetDateTime::now_utc().to_offset(*offset),
+            Self::Custom(clock) => clock(),
+        }
+    }
+
+    /// Format the current time of the clock as RFC 3339.
+    pub(crate) fn now(&mut self) -> String {
+        let utc = matches!(self, Self::Utc);
+
+        rfc3339(self.time(), utc)
+    }
+}
+
+/// Error of a format of a [`Timestamp`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimestampFormatError {
+    /// The format contains a specifier that is not supported.
+    UnknownSpecifier {
+        /// The specifier, like `%Q`.
+        specifier: String,
+    },
+
+    /// The format contains `%Z` but the name of the time zone is not
+    /// known for the clock, which is only the case for
+    /// [`ClockConfig::Utc`].
+    UnknownZoneName {
+        /// The clock, like `FixedOffset(+02:00:00)`.
+        clock: String,
+    },
+}
+
+impl std::fmt::Display for TimestampFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownSpecifier { specifier } => {
+                write!(f, "unknown specifier `{specifier}` in timestamp format")
+            }
+
+            Self::UnknownZoneName { clock } => write!(
+                f,
+                "`%Z` in timestamp format needs a clock with a known time zone name, not `{clock}`"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TimestampFormatError {}
+
+/// Part of a parsed timestamp format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    Literal(String),
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Millisecond,
+    Offset { colon: bool },
+    ZoneName,
+}
+
+/// Prefix source rendering the time of a clock in a format, see
+/// [`Prefix`].
+#[derive(Debug)]
+pub struct Timestamp {
+    clock: ClockConfig,
+    format: Vec<Item>,
+}
+
+impl Timestamp {
+    /// Create a new [`Timestamp`] rendering the time of the clock as RFC
+    /// 3339 with milliseconds, like `2024-05-17T08:03:09.042Z`.
+    #[must_use]
+    pub fn rfc3339(clock: ClockConfig) -> Self {
+        Self {
+            clock,
+            format: Vec::new(),
+        }
+    }
+
+    /// Create a new [`Timestamp`] rendering the time of the clock in the
+    /// format, which supports `%Y`, `%m`, `%d`, `%H`, `%M`, `%S`, `%3f`
+    /// for milliseconds, `%z` for the offset like `+0200`, `%:z` for the
+    /// offset like `+02:00`, `%Z` for the name of the time zone and `%%`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TimestampFormatError`] when the format contains an
+    /// unknown specifier or `%Z` with a clock other than
+    /// [`ClockConfig::Utc`], for which the name of the time zone is not
+    /// known.
+    pub fn new(clock: ClockConfig, format: &str) -> Result<Self, TimestampFormatError> {
+        let format = parse_format(format)?;
+
+        if format.contains(&Item::ZoneName) && !matches!(clock, ClockConfig::Utc) {
+            return Err(TimestampFormatError::UnknownZoneName {
+                clock: format!("{clock:?}"),
+            });
+        }
+
+        Ok(Self { clock, format })
+    }
+}
+
+impl Prefix for Timestamp {
+    fn render(&mut self, _context: &LineContext<'_>, out: &mut String) {
+        if self.format.is_empty() {
+            out.push_str(&self.clock.now());
+            return;
+        }
+
+        let time = self.clock.time();
+
+        for item in &self.format {
+            let _ = match item {
+                Item::Literal(literal) => out.write_str(literal),
+                Item::Year => write!(out, "{:04}", time.year()),
+                Item::Month => write!(out, "{:02}", u8::from(time.month())),
+                Item::Day => write!(out, "{:02}", time.day()),
+                Item::Hour => write!(out, "{:02}", time.hour()),
+                Item::Minute => write!(out, "{:02}", time.minute()),
+                Item::Second => write!(out, "{:02}", time.second()),
+                Item::Millisecond => write!(out, "{:03}", time.millisecond()),
+                Item::Offset { colon } => write_offset(time.offset(), *colon, out),
+                Item::ZoneName => out.write_str("UTC"),
+            };
+        }
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Set the clock and time zone of timestamps, like those of
+    /// [`crate::FieldSource::Timestamp`]. Defaults to
+    /// [`ClockConfig::Utc`].
+    #[must_use]
+    pub fn with_timestamp_clock(self, timestamp_clock: ClockConfig) -> Self {
+        Self {
+            timestamp_clock: Shared::new(timestamp_clock),
+            ..self
+        }
+    }
+
+    /// Start the prefix of every line with the time of the clock set with
+    /// [`PrefixWriter::with_timestamp_clock`] as RFC 3339, like
+    /// `2024-05-17T08:03:09.042Z web: `. Use [`Timestamp`] with
+    /// [`PrefixWriter::with_prefix_source`] for other formats.
+    #[must_use]
+    pub fn with_timestamp_prefix(self, timestamp_prefix: bool) -> Self {
+        Self {
+            timestamp_prefix,
+            ..self
+        }
+    }
+}
+
+/// Parse a timestamp format into its parts, see [`Timestamp::new`].
+fn parse_format(format: &str) -> Result<Vec<Item>, TimestampFormatError> {
+    let mut items = Vec::new();
+    let mut literal = String::new();
+    let mut chars = format.chars();
+
+    while let Some(character) = chars.next() {
+        if character != '%' {
+            literal.push(character);
+            continue;
+        }
+
+        let item = match chars.next() {
+            Some('%') => {
+                literal.push('%');
+                continue;
+            }
+            Some('Y') => Item::Year,
+            Some('m') => Item::Month,
+            Some('d') => Item::Day,
+            Some('H') => Item::Hour,
+            Some('M') => Item::Minute,
+            Some('S') => Item::Second,
+            Some('z') => Item::Offset { colon: false },
+            Some('Z') => Item::ZoneName,
+            Some('3') if chars.next() == Some('f') => Item::Millisecond,
+            Some(':') if chars.next() == Some('z') => Item::Offset { colon: true },
+            Some(other) => {
+                return Err(TimestampFormatError::UnknownSpecifier {
+                    specifier: format!("%{other}"),
+                })
+            }
+            None => {
+                return Err(TimestampFormatError::UnknownSpecifier {
+                    specifier: "%".to_owned(),
+                })
+            }
+        };
+
+        if !literal.is_empty() {
+            items.push(Item::Literal(std::mem::take(&mut literal)));
+        }
+        items.push(item);
+    }
+
+    if !literal.is_empty() {
+        items.push(Item::Literal(literal));
+    }
+
+    Ok(items)
+}
+
+/// Write the offset like `+0200`, or like `+02:00` with the colon.
+fn write_offset(offset: UtcOffset, colon: bool, out: &mut String) -> std::fmt::Result {
+    let sign = if offset.is_negative() { '-' } else { '+' };
+    let (hours, minutes) = (
+        offset.whole_hours().unsigned_abs(),
+        offset.minutes_past_hour().unsigned_abs(),
+    );
+
+    if colon {
+        write!(out, "{sign}{hours:02}:{minutes:02}")
+    } else {
+        write!(out, "{sign}{hours:02}{minutes:02}")
+    }
+}
+
+/// Format the time as RFC 3339 with milliseconds, like
+/// `2024-05-17T10:03:09.042+02:00`, or with `Z` instead of the offset
+/// like `2024-05-17T08:03:09.042Z` for `utc`.
+pub(crate) fn rfc3339(time: OffsetDateTime, utc: bool) -> String {
+    let mut formatted = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}",
+        time.year(),
+        u8::from(time.month()),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+        time.millisecond()
+    );
+
+    if utc {
+        formatted.push('Z');
+    } else {
+        let _ = write_offset(time.offset(), true, &mut formatted);
+    }
+
+    formatted
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use time::{
+        OffsetDateTime,
+        UtcOffset,
+    };
+
+    use super::{
+        rfc3339,
+        ClockConfig,
+        Timestamp,
+        TimestampFormatError,
+    };
+    use crate::{
+        FieldSource,
+        PrefixWriter,
+    };
+
+    /// The time at the seconds and milliseconds since the Unix epoch.
+    fn at(secs: i64, millis: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(secs).unwrap() + time::Duration::milliseconds(millis)
+    }
+
+    fn offset(hours: i8, minutes: i8) -> UtcOffset {
+        UtcOffset::from_hms(hours, minutes, 0).unwrap()
+    }
+
+    /// Clock returning the time at the offset, advancing a second every
+    /// time it is read.
+    fn ticking(offset: UtcOffset) -> ClockConfig {
+        let mut secs = 1_715_932_989;
+
+        ClockConfig::Custom(Box::new(move || {
+            secs += 1;
+            at(secs, 42).to_offset(offset)
+        }))
+    }
+
+    fn run(timestamp: Timestamp) -> String {
+        let mut writer =
+            PrefixWriter::new(String::new(), Vec::new()).with_prefix_source((timestamp, " web: "));
+
+        writer.write_all(b"first\n").unwrap();
+
+        String::from_utf8_lossy(writer.get_ref()).into_owned()
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            at,
+            offset,
+            rfc3339,
+            run,
+            ticking,
+            ClockConfig,
+            FieldSource,
+            PrefixWriter,
+            Timestamp,
+            TimestampFormatError,
+            UtcOffset,
+            Write,
+        };
+
+        #[test]
+        fn format() {
+            assert_eq!("1970-01-01T00:00:00.000Z", rfc3339(at(0, 0), true));
+            assert_eq!(
+                "2000-02-29T23:59:59.999Z",
+                rfc3339(at(951_868_799, 999), true)
+            );
+            assert_eq!(
+                "2024-05-17T08:03:09.042Z",
+                rfc3339(at(1_715_932_989, 42), true)
+            );
+            assert_eq!(
+                "2100-03-01T00:00:00.000Z",
+                rfc3339(at(4_107_542_400, 0), true)
+            );
+            assert_eq!("1969-12-31T23:59:59.000Z", rfc3339(at(-1, 0), true));
+        }
+
+        #[test]
+        fn offsets() {
+            let time = at(1_715_932_989, 0);
+
+            assert_eq!(
+                "2024-05-17T10:03:09.000+02:00",
+                rfc3339(time.to_offset(offset(2, 0)), false)
+            );
+            assert_eq!(
+                "2024-05-17T02:33:09.000-05:30",
+                rfc3339(time.to_offset(offset(-5, -30)), false)
+            );
+            assert_eq!(
+                "2024-05-17T08:03:09.000+00:00",
+                rfc3339(time.to_offset(UtcOffset::UTC), false)
+            );
+            assert_eq!(
+                "1969-12-31T23:00:00.000-01:00",
+                rfc3339(at(0, 0).to_offset(offset(-1, 0)), false)
+            );
+        }
+
+        #[test]
+        fn zones() {
+            let before = time::OffsetDateTime::now_utc();
+            let utc = run(Timestamp::rfc3339(ClockConfig::Utc));
+            let (timestamp, line) = utc.split_once(' ').unwrap();
+            assert_eq!("web: first\n", line);
+            assert!(timestamp.ends_with('Z'));
+            assert!(timestamp.starts_with(&before.year().to_string()));
+
+            let fixed = run(Timestamp::rfc3339(ClockConfig::FixedOffset(offset(5, 30))));
+            assert!(fixed.contains("+05:30 web: first\n"), "{fixed}");
+
+            let local_offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+            let local = run(Timestamp::new(ClockConfig::Local, "%:z").unwrap());
+            assert_eq!(
+                format!(
+                    "{}{:02}:{:02} web: first\n",
+                    if local_offset.is_negative() { '-' } else { '+' },
+                    local_offset.whole_hours().unsigned_abs(),
+                    local_offset.minutes_past_hour().unsigned_abs()
+                ),
+                local
+            );
+
+            assert_eq!(
+                "2024-05-17T06:03:10.042-02:00 web: first\n",
+                run(Timestamp::rfc3339(ticking(offset(-2, 0))))
+            );
+        }
+
+        #[test]
+        fn custom_format() {
+            let timestamp =
+                Timestamp::new(ticking(offset(2, 0)), "%Y/%m/%d %H:%M:%S.%3f %z %:z 100%%")
+                    .unwrap();
+
+            assert_eq!(
+                "2024/05/17 10:03:10.042 +0200 +02:00 100% web: first\n",
+                run(timestamp)
+            );
+
+            let mut secs = 1_715_932_989;
+            let utc = Timestamp::new(
+                ClockConfig::Custom(Box::new(move || {
+                    secs += 1;
+                    at(secs, 0)
+                })),
+                "%H:%M",
+            )
+            .unwrap();
+            assert_eq!("08:03 web: first\n", run(utc));
+
+            assert_eq!(
+                "UTC web: first\n",
+                run(Timestamp::new(ClockConfig::Utc, "%Z").unwrap())
+            );
+        }
+
+        #[test]
+        fn format_errors() {
+            for clock in [
+                ClockConfig::FixedOffset(offset(2, 0)),
+                ClockConfig::Local,
+                ticking(UtcOffset::UTC),
+            ] {
+                let err = Timestamp::new(clock, "%H:%M %Z").unwrap_err();
+                assert!(matches!(err, TimestampFormatError::UnknownZoneName { .. }));
+            }
+
+            assert_eq!(
+                "`%Z` in timestamp format needs a clock with a known time zone name, not \
+                 `FixedOffset(+02:00:00)`",
+                Timestamp::new(ClockConfig::FixedOffset(offset(2, 0)), "%Z")
+                    .unwrap_err()
+                    .to_string()
+            );
+            assert!(Timestamp::new(ClockConfig::Utc, "%H:%M %Z").is_ok());
+
+            assert_eq!(
+                TimestampFormatError::UnknownSpecifier {
+                    specifier: "%Q".to_owned()
+                },
+                Timestamp::new(ClockConfig::Utc, "%Q").unwrap_err()
+            );
+            assert_eq!(
+                "unknown specifier `%` in timestamp format",
+                Timestamp::new(ClockConfig::Utc, "%H%")
+                    .unwrap_err()
+                    .to_string()
+            );
+        }
+
+        #[test]
+        fn timestamp_prefix() {
+            let mut writer = PrefixWriter::new("web: ".to_owned(), Vec::new())
+                .with_timestamp_clock(ticking(offset(2, 0)))
+                .with_timestamp_prefix(true);
+
+            writer.write_all(b"first\n\nsecond\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "2024-05-17T10:03:10.042+02:00 web: first\n",
+                    "\n",
+                    "2024-05-17T10:03:11.042+02:00 web: second\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn custom_clock() {
+            let mut writer = PrefixWriter::new(String::new(), Vec::new())
+                .with_logfmt(vec![
+                    ("ts".to_owned(), FieldSource::Timestamp),
+                    ("msg".to_owned(), FieldSource::Content),
+                ])
+                .with_timestamp_clock(ticking(offset(1, 0)));
+
+            writer.write_all(b"first\nsecond\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "ts=2024-05-17T09:03:10.042+01:00 msg=first\n",
+                    "ts=2024-05-17T09:03:11.042+01:00 msg=second\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
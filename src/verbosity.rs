@@ -0,0 +1,573 @@
+//! Dropping lines below the verbosity level of their key.
+
+use std::{
+    collections::HashMap,
+    io::Write,
+    str::FromStr,
+    sync::{
+        Arc,
+        Mutex,
+        MutexGuard,
+        PoisonError,
+    },
+};
+
+use crate::PrefixWriter;
+
+/// Level of a line, from the most to the least verbose.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// Very detailed tracing.
+    Trace,
+
+    /// Debugging information.
+    Debug,
+
+    /// Regular information.
+    Info,
+
+    /// Warnings.
+    Warn,
+
+    /// Errors.
+    Error,
+}
+
+/// Names of the levels in [`Level`] order, as they are parsed.
+const LEVEL_NAMES: [(&str, Level); 5] = [
+    ("trace", Level::Trace),
+    ("debug", Level::Debug),
+    ("info", Level::Info),
+    ("warn", Level::Warn),
+    ("error", Level::Error),
+];
+
+/// Key of a [`VerbositySpec`] entry setting the level of all other keys.
+const DEFAULT_KEY: &str = "default";
+
+impl FromStr for Level {
+    type Err = VerbosityError;
+
+    /// Parse the name of the level, ignoring the case.
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        LEVEL_NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(level))
+            .map(|(_, level)| *level)
+            .ok_or_else(|| VerbosityError::UnknownLevel {
+                level: level.to_owned(),
+            })
+    }
+}
+
+/// Errors of parsing a [`VerbositySpec`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerbosityError {
+    /// The name is not one of the levels.
+    UnknownLevel {
+        /// The name of the level.
+        level: String,
+    },
+
+    /// An entry has a `=` but nothing before it.
+    MissingKey {
+        /// The entry.
+        entry: String,
+    },
+
+    /// An entry has a `=` but nothing after it.
+    MissingLevel {
+        /// The entry.
+        entry: String,
+    },
+}
+
+impl std::fmt::Display for VerbosityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownLevel { level } => write!(
+                f,
+                "unknown level `{level}`, expected one of trace, debug, info, warn or error"
+            ),
+
+            Self::MissingKey { entry } => write!(f, "missing key before `=` in `{entry}`"),
+
+            Self::MissingLevel { entry } => write!(f, "missing level after `=` in `{entry}`"),
+        }
+    }
+}
+
+impl std::error::Error for VerbosityError {}
+
+/// Lowest level of the lines that are kept for every key, like
+/// `db=debug,web=warn,default=info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerbositySpec {
+    default: Level,
+    keys: HashMap<String, Level>,
+}
+
+impl VerbositySpec {
+    /// Create a new [`VerbositySpec`] keeping lines of at least the level
+    /// for all keys.
+    #[must_use]
+    pub fn new(default: Level) -> Self {
+        Self {
+            default,
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Parse a spec of comma separated entries in the form `key=level`.
+    /// The level of the key `default`, or of an entry that is only a
+    /// level, applies to all other keys and is `info` if it is not given.
+    /// Levels are `trace`, `debug`, `info`, `warn` and `error`, in any
+    /// case. Whitespace around entries, keys and levels is ignored and
+    /// later entries win over earlier ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for unknown levels and for entries with a `=` but
+    /// without a key or a level.
+    pub fn parse(spec: &str) -> Result<Self, VerbosityError> {
+        let mut parsed = Self::new(Level::Info);
+
+        for entry in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let Some((key, level)) = entry.split_once('=') else {
+                parsed.default = entry.parse()?;
+                continue;
+            };
+
+            let (key, level) = (key.trim(), level.trim());
+            if key.is_empty() {
+                return Err(VerbosityError::MissingKey {
+                    entry: entry.to_owned(),
+                });
+            }
+            if level.is_empty() {
+                return Err(VerbosityError::MissingLevel {
+                    entry: entry.to_owned(),
+                });
+            }
+
+            let level = level.parse()?;
+            if key == DEFAULT_KEY {
+                parsed.default = level;
+            } else {
+                parsed.keys.insert(key.to_owned(), level);
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Keep lines of the key with at least the level.
+    #[must_use]
+    pub fn with_level(mut self, key: &str, level: Level) -> Self {
+        self.keys.insert(key.to_owned(), level);
+        self
+    }
+
+    /// Lowest level of the lines that are kept for the key.
+    #[must_use]
+    pub fn level(&self, key: &str) -> Level {
+        self.keys.get(key).copied().unwrap_or(self.default)
+    }
+}
+
+impl FromStr for VerbositySpec {
+    type Err = VerbosityError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        Self::parse(spec)
+    }
+}
+
+/// Closure deciding the level of a line.
+type ClassifyFn = Box<dyn Fn(&str) -> Level + Send + Sync>;
+
+/// Decides the level of every line, see [`Verbosity`].
+pub struct Classifier {
+    classify: ClassifyFn,
+}
+
+impl std::fmt::Debug for Classifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Classifier").finish_non_exhaustive()
+    }
+}
+
+impl Classifier {
+    /// Create a new [`Classifier`] giving a line the level of the first
+    /// of the rules whose keyword is a word of the line, ignoring the
+    /// case, and `unmatched` if there is none. Words are separated by
+    /// anything that is not alphanumeric, so `[WARN]` and `warn:` match
+    /// the keyword `warn` but `warning` does not.
+    #[must_use]
+    pub fn keywords(rules: Vec<(String, Level)>, unmatched: Level) -> Self {
+        Self::from_fn(move |line| {
+            rules
+                .iter()
+                .find(|(keyword, _)| {
+                    line.split(|character: char| !character.is_alphanumeric())
+                        .any(|word| word.eq_ignore_ascii_case(keyword))
+                })
+                .map_or(unmatched, |(_, level)| *level)
+        })
+    }
+
+    /// Create a new [`Classifier`] giving a line the level of its
+    /// closure.
+    #[must_use]
+    pub fn from_fn(classify: impl Fn(&str) -> Level + Send + Sync + 'static) -> Self {
+        Self {
+            classify: Box::new(classify),
+        }
+    }
+
+    /// The level of the content of the line.
+    #[must_use]
+    pub fn classify(&self, line: &str) -> Level {
+        (self.classify)(line)
+    }
+}
+
+impl Default for Classifier {
+    /// Classify lines by the names of the levels and a few common
+    /// abbreviations, lines without any are `info`.
+    fn default() -> Self {
+        let rules = [
+            ("error", Level::Error),
+            ("err", Level::Error),
+            ("warn", Level::Warn),
+            ("info", Level::Info),
+            ("debug", Level::Debug),
+            ("dbg", Level::Debug),
+            ("trace", Level::Trace),
+        ];
+
+        Self::keywords(
+            rules
+                .into_iter()
+                .map(|(keyword, level)| (keyword.to_owned(), level))
+                .collect(),
+            Level::Info,
+        )
+    }
+}
+
+/// Counters of the lines of a key seen by [`PrefixWriter::with_verbosity`],
+/// see [`Verbosity::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VerbosityStats {
+    /// Lines that were kept.
+    pub kept: u64,
+
+    /// Lines that were dropped because they were below the level of the
+    /// key.
+    pub dropped: u64,
+}
+
+/// Spec and counters shared by the clones of a [`Verbosity`].
+#[derive(Debug)]
+struct Shared {
+    spec: VerbositySpec,
+    stats: HashMap<String, VerbosityStats>,
+}
+
+/// Verbosity levels of many keys shared by their writers, set with
+/// [`PrefixWriter::with_verbosity`]. Lines below the level of their key
+/// are dropped before they are prefixed. Levels can be changed at any
+/// time with [`Verbosity::set_level`] and apply from the next line on.
+/// Clones share the same levels and counters.
+#[derive(Debug, Clone)]
+pub struct Verbosity {
+    shared: Arc<Mutex<Shared>>,
+    classifier: Arc<Classifier>,
+}
+
+impl Verbosity {
+    /// Create a new [`Verbosity`] with the levels of the spec, deciding
+    /// the level of every line with the classifier.
+    #[must_use]
+    pub fn new(spec: VerbositySpec, classifier: Classifier) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Shared {
+                spec,
+                stats: HashMap::new(),
+            })),
+            classifier: Arc::new(classifier),
+        }
+    }
+
+    /// Keep lines of the key with at least the level from the next line
+    /// on.
+    pub fn set_level(&self, key: &str, level: Level) {
+        self.lock().spec.keys.insert(key.to_owned(), level);
+    }
+
+    /// Lowest level of the lines that are kept for the key.
+    #[must_use]
+    pub fn level(&self, key: &str) -> Level {
+        self.lock().spec.level(key)
+    }
+
+    /// Counters of the lines of the key.
+    #[must_use]
+    pub fn stats(&self, key: &str) -> VerbosityStats {
+        self.lock().stats.get(key).copied().unwrap_or_default()
+    }
+
+    /// Decide whether the line of the key is kept and count it if
+    /// `counted`.
+    fn keep(&self, key: &str, content: &str, counted: bool) -> bool {
+        let level = self.classifier.classify(content);
+
+        let mut shared = self.lock();
+        let keep = level >= shared.spec.level(key);
+
+        if !counted {
+            return keep;
+        }
+
+        let stats = shared.stats.entry(key.to_owned()).or_default();
+        if keep {
+            stats.kept += 1;
+        } else {
+            stats.dropped += 1;
+        }
+
+        keep
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        self.shared.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The [`Verbosity`] of a writer and the key of its lines.
+#[derive(Debug, Clone)]
+pub(crate) struct KeyVerbosity {
+    verbosity: Verbosity,
+    key: String,
+    counted: bool,
+}
+
+impl KeyVerbosity {
+    pub(crate) fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The same verbosity without counting the lines, for previews.
+    pub(crate) fn uncounted(&self) -> Self {
+        Self {
+            counted: false,
+            ..self.clone()
+        }
+    }
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Drop lines below the level of the key in the verbosity, for
+    /// writers of many sources like the key writers of
+    /// [`crate::PerKeyFiles`] or the handles of a
+    /// [`crate::ShardedPrefixWriter`] that share one [`Verbosity`].
+    /// Dropped lines are counted in [`Verbosity::stats`] and otherwise
+    /// treated as if they were never written. Empty lines are classified
+    /// like any other line.
+    #[must_use]
+    pub fn with_verbosity(self, verbosity: &Verbosity, key: &str) -> Self {
+        Self {
+            verbosity: Some(KeyVerbosity {
+                verbosity: verbosity.clone(),
+                key: key.to_owned(),
+                counted: true,
+            }),
+            ..self
+        }
+    }
+
+    /// Whether the line is at least at the level of the key of the
+    /// writer, always without verbosity.
+    pub(crate) fn verbose_enough(&self, content: &str) -> bool {
+        self.verbosity.as_ref().is_none_or(|verbosity| {
+            verbosity
+                .verbosity
+                .keep(&verbosity.key, content, verbosity.counted)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    use super::{
+        Classifier,
+        Level,
+        Verbosity,
+        VerbosityError,
+        VerbositySpec,
+        VerbosityStats,
+    };
+    use crate::PrefixWriter;
+
+    /// Lines of every level and one without a level.
+    const INPUT: &str = concat!(
+        "TRACE entering\n",
+        "[debug] cache miss\n",
+        "info: started\n",
+        "no level\n",
+        "WARN slow query\n",
+        "error: lost connection\n",
+    );
+
+    fn writer(verbosity: &Verbosity, key: &str) -> PrefixWriter<Vec<u8>> {
+        PrefixWriter::new(format!("{key}: "), Vec::new()).with_verbosity(verbosity, key)
+    }
+
+    mod tests {
+        use super::{
+            assert_eq,
+            writer,
+            Classifier,
+            Level,
+            Verbosity,
+            VerbosityError,
+            VerbositySpec,
+            VerbosityStats,
+            Write,
+            INPUT,
+        };
+
+        #[test]
+        fn parse() {
+            let spec = VerbositySpec::parse(" db=debug, web = WARN,default=error ").unwrap();
+            assert_eq!(Level::Debug, spec.level("db"));
+            assert_eq!(Level::Warn, spec.level("web"));
+            assert_eq!(Level::Error, spec.level("cache"));
+
+            let spec: VerbositySpec = "trace,db=info,db=warn,,".parse().unwrap();
+            assert_eq!(Level::Warn, spec.level("db"));
+            assert_eq!(Level::Trace, spec.level("web"));
+
+            assert_eq!(Level::Info, VerbositySpec::parse("").unwrap().level("db"));
+        }
+
+        #[test]
+        fn parse_errors() {
+            let error = VerbositySpec::parse("db=verbose").unwrap_err();
+            assert_eq!(
+                VerbosityError::UnknownLevel {
+                    level: "verbose".to_owned()
+                },
+                error
+            );
+            assert_eq!(
+                "unknown level `verbose`, expected one of trace, debug, info, warn or error",
+                error.to_string()
+            );
+
+            assert_eq!(
+                "missing key before `=` in `=debug`",
+                VerbositySpec::parse("web=warn, =debug")
+                    .unwrap_err()
+                    .to_string()
+            );
+            assert_eq!(
+                "missing level after `=` in `db=`",
+                VerbositySpec::parse("db=").unwrap_err().to_string()
+            );
+        }
+
+        #[test]
+        fn keys_with_different_levels() {
+            let verbosity = Verbosity::new(
+                VerbositySpec::parse("db=debug,web=warn").unwrap(),
+                Classifier::default(),
+            );
+
+            let mut db = writer(&verbosity, "db");
+            let mut web = writer(&verbosity, "web");
+            db.write_all(INPUT.as_bytes()).unwrap();
+            web.write_all(INPUT.as_bytes()).unwrap();
+
+            assert_eq!(
+                concat!(
+                    "db: [debug] cache miss\n",
+                    "db: info: started\n",
+                    "db: no level\n",
+                    "db: WARN slow query\n",
+                    "db: error: lost connection\n",
+                ),
+                String::from_utf8_lossy(db.get_ref())
+            );
+            assert_eq!(
+                "web: WARN slow query\nweb: error: lost connection\n",
+                String::from_utf8_lossy(web.get_ref())
+            );
+
+            assert_eq!(
+                VerbosityStats {
+                    kept: 5,
+                    dropped: 1
+                },
+                verbosity.stats("db")
+            );
+            assert_eq!(
+                VerbosityStats {
+                    kept: 2,
+                    dropped: 4
+                },
+                verbosity.stats("web")
+            );
+            assert_eq!(VerbosityStats::default(), verbosity.stats("cache"));
+        }
+
+        #[test]
+        fn set_level() {
+            let verbosity = Verbosity::new(
+                VerbositySpec::new(Level::Error),
+                Classifier::keywords(vec![("slow".to_owned(), Level::Warn)], Level::Debug),
+            );
+            let mut web = writer(&verbosity, "web");
+
+            web.write_all(b"slow request\n").unwrap();
+            verbosity.set_level("web", Level::Warn);
+            web.write_all(b"slow request\nother\n").unwrap();
+            verbosity.set_level("web", Level::Trace);
+            web.write_all(b"other\n").unwrap();
+
+            assert_eq!(Level::Trace, verbosity.level("web"));
+            assert_eq!(Level::Error, verbosity.level("db"));
+            assert_eq!(
+                "web: slow request\nweb: other\n",
+                String::from_utf8_lossy(web.get_ref())
+            );
+        }
+
+        #[test]
+        fn closure_classifier() {
+            let verbosity = Verbosity::new(
+                VerbositySpec::new(Level::Warn),
+                Classifier::from_fn(|line| {
+                    if line.starts_with('!') {
+                        Level::Error
+                    } else {
+                        Level::Info
+                    }
+                }),
+            );
+            let mut writer = writer(&verbosity, "web");
+
+            writer.write_all(b"quiet\n!loud\n").unwrap();
+
+            assert_eq!("web: !loud\n", String::from_utf8_lossy(writer.get_ref()));
+        }
+    }
+}
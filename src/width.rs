@@ -0,0 +1,218 @@
+//! Measuring the width of text for options that pad, truncate or wrap.
+
+#[cfg(feature = "unicode")]
+use unicode_segmentation::UnicodeSegmentation;
+#[cfg(feature = "unicode")]
+use unicode_width::UnicodeWidthStr;
+
+/// Unit in which widths of text are measured. Text is only ever cut
+/// between characters, or between grapheme clusters for
+/// [`Unit::Graphemes`], so a cut never produces invalid text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Unit {
+    /// Bytes of the UTF-8 encoding. Cuts are rounded down to the start of
+    /// the character that would be split.
+    Bytes,
+
+    /// Unicode scalar values.
+    #[default]
+    Chars,
+
+    /// Columns the text takes up in a terminal, where wide characters
+    /// like CJK take up two columns and combining characters none.
+    #[cfg(feature = "unicode")]
+    Columns,
+
+    /// Grapheme clusters, what a reader sees as one character, like an
+    /// `e` with a combining accent or an emoji joined from several
+    /// emojis.
+    #[cfg(feature = "unicode")]
+    Graphemes,
+}
+
+impl Unit {
+    /// Width of the text.
+    pub(crate) fn measure(self, text: &str) -> usize {
+        match self {
+            Self::Bytes => text.len(),
+            Self::Chars => text.chars().count(),
+            #[cfg(feature = "unicode")]
+            Self::Columns => display_width(text),
+            #[cfg(feature = "unicode")]
+            Self::Graphemes => text.graphemes(true).count(),
+        }
+    }
+
+    /// Split the text into the smallest parts it may be cut between.
+    pub(crate) fn segments(self, text: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self {
+            #[cfg(feature = "unicode")]
+            Self::Graphemes => Box::new(text.graphemes(true)),
+            _ => Box::new(
+                text.char_indices()
+                    .map(|(index, character)| &text[index..index + character.len_utf8()]),
+            ),
+        }
+    }
+
+    /// Fit the text into exactly `width`, by truncating it with an
+    /// ellipsis when it is wider or padding it with spaces when it is
+    /// narrower. Characters are never split, so a truncated text can be
+    /// narrower than `width` and is padded as well.
+    pub(crate) fn fit(self, text: &str, width: usize) -> String {
+        let mut fitted = self.truncate(text, width);
+
+        let padding = width.saturating_sub(self.measure(&fitted));
+        fitted.extend(std::iter::repeat_n(' ', padding));
+
+        fitted
+    }
+
+    /// Truncate the text with an ellipsis when it is wider than `width`,
+    /// like [`Unit::fit`] but without padding.
+    pub(crate) fn truncate(self, text: &str, width: usize) -> String {
+        if self.measure(text) > width {
+            let ellipsis = self.measure("…");
+            let available = width.saturating_sub(ellipsis);
+
+            let mut used = 0;
+            let mut truncated = self
+                .segments(text)
+                .take_while(|segment| {
+                    used += self.measure(segment);
+                    used <= available
+                })
+                .collect::<String>();
+
+            if width >= ellipsis {
+                truncated.push('…');
+            }
+
+            truncated
+        } else {
+            text.to_owned()
+        }
+    }
+}
+
+/// Unit [`display_width`] measures in.
+#[cfg(feature = "unicode")]
+pub(crate) const DISPLAY_UNIT: Unit = Unit::Columns;
+
+/// Unit [`display_width`] measures in.
+#[cfg(not(feature = "unicode"))]
+pub(crate) const DISPLAY_UNIT: Unit = Unit::Chars;
+
+/// Columns the text takes up in a terminal, see `Unit::Columns`. ASCII
+/// text is as wide as it is long. Without the `unicode` feature the
+/// characters are counted instead.
+pub(crate) fn display_width(text: &str) -> usize {
+    #[cfg(feature = "unicode")]
+    return if text.is_ascii() {
+        text.len()
+    } else {
+        text.width()
+    };
+
+    #[cfg(not(feature = "unicode"))]
+    return text.chars().count();
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::Unit;
+
+    /// Family emoji joined from four emojis with zero width joiners.
+    const FAMILY: &str = "👨\u{200d}👩\u{200d}👧\u{200d}👦";
+
+    /// `e` followed by a combining acute accent.
+    const COMBINING: &str = "cafe\u{301} cre\u{300}me";
+
+    const UNITS: &[Unit] = &[
+        Unit::Bytes,
+        Unit::Chars,
+        #[cfg(feature = "unicode")]
+        Unit::Columns,
+        #[cfg(feature = "unicode")]
+        Unit::Graphemes,
+    ];
+
+    /// The part of the original text kept by fitting it.
+    fn kept(fitted: &str) -> &str {
+        fitted.trim_end_matches(' ').trim_end_matches('…')
+    }
+
+    mod tests {
+        #[cfg(feature = "unicode")]
+        use super::Unit;
+        use super::{
+            assert_eq,
+            kept,
+            COMBINING,
+            FAMILY,
+            UNITS,
+        };
+
+        #[cfg(feature = "unicode")]
+        #[test]
+        fn measure() {
+            assert_eq!(
+                [10, 5, 7],
+                [Unit::Bytes, Unit::Chars, Unit::Columns].map(|unit| unit.measure("ab日本ü"))
+            );
+        }
+
+        #[cfg(feature = "unicode")]
+        #[test]
+        fn fit() {
+            let fit =
+                |text| [Unit::Bytes, Unit::Chars, Unit::Columns].map(|unit| unit.fit(text, 6));
+
+            assert_eq!(["ab    ", "ab    ", "ab    "], fit("ab"));
+            assert_eq!(["日…", "日本ü   ", "日本ü "], fit("日本ü"));
+            assert_eq!(["日…", "日本日本日…", "日本… "], fit("日本日本日本日"));
+        }
+
+        #[test]
+        fn fit_every_width() {
+            for text in [FAMILY, COMBINING] {
+                for unit in UNITS {
+                    for width in 0..=unit.measure(text) + 1 {
+                        let fitted = unit.fit(text, width);
+
+                        assert!(text.starts_with(kept(&fitted)), "{unit:?} {width}");
+                        if width >= unit.measure("…") {
+                            assert_eq!(width, unit.measure(&fitted), "{unit:?} {width}");
+                        }
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "unicode")]
+        #[test]
+        fn fit_graphemes() {
+            use unicode_segmentation::UnicodeSegmentation;
+
+            for text in [FAMILY, COMBINING] {
+                for width in 0..=Unit::Graphemes.measure(text) + 1 {
+                    let kept = kept(&Unit::Graphemes.fit(text, width)).len();
+
+                    assert!(
+                        kept == text.len()
+                            || text.grapheme_indices(true).any(|(index, _)| index == kept),
+                        "{width}"
+                    );
+                }
+            }
+
+            assert_eq!("cafe\u{301}…", Unit::Graphemes.fit(COMBINING, 5));
+            assert_eq!("cafe…", Unit::Chars.fit(COMBINING, 5));
+            assert_eq!(FAMILY, Unit::Graphemes.fit(FAMILY, 1));
+            assert_eq!("👨…", Unit::Chars.fit(FAMILY, 2));
+        }
+    }
+}
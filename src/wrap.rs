@@ -0,0 +1,213 @@
+//! Hard wrapping long lines into several prefixed lines.
+
+use std::{
+    borrow::Cow,
+    io::Write,
+};
+
+use crate::{
+    PrefixWriter,
+    Unit,
+};
+
+/// Wrap the line into segments of at most `width`, joined by the
+/// continuation character and the separator. The continuation character
+/// counts towards the width of the segments it ends. A single character
+/// wider than the width gets a segment of its own.
+pub(crate) fn wrap<'a>(
+    line: &'a str,
+    unit: Unit,
+    width: usize,
+    continuation: Option<char>,
+    separator: &str,
+) -> Cow<'a, str> {
+    let mut remaining = unit.measure(line);
+    if remaining <= width {
+        return line.into();
+    }
+
+    let continuation = continuation.map(String::from).unwrap_or_default();
+    let available = width.saturating_sub(unit.measure(&continuation));
+
+    let mut wrapped = String::new();
+    let mut used = 0;
+    for segment in unit.segments(line) {
+        let segment_width = unit.measure(segment);
+
+        // The last segment does not need room for the continuation.
+        if used > 0 && used + remaining > width && used + segment_width > available {
+            wrapped.push_str(&continuation);
+            wrapped.push_str(separator);
+            used = 0;
+        }
+
+        wrapped.push_str(segment);
+        used += segment_width;
+        remaining -= segment_width;
+    }
+
+    wrapped.into()
+}
+
+/// Indentation of the content of a line whose prefix is on a line of its
+/// own.
+const OVERFLOW_INDENT: &str = "  ";
+
+/// Put the prefix on a line of its own, ended with the terminator and
+/// followed by the indentation of the content, when the prefix and the
+/// content together are wider than `width`, if set.
+pub(crate) fn overflow_prefix<'a>(
+    prefix: Cow<'a, str>,
+    content: &str,
+    unit: Unit,
+    width: Option<usize>,
+    terminator: &str,
+) -> Cow<'a, str> {
+    let Some(width) = width else {
+        return prefix;
+    };
+
+    if prefix.is_empty() || unit.measure(&prefix) + unit.measure(content) <= width {
+        return prefix;
+    }
+
+    format!("{}{terminator}{OVERFLOW_INDENT}", prefix.trim_end()).into()
+}
+
+impl<W: Write> PrefixWriter<W> {
+    /// Wrap lines wider than `width` into several prefixed lines, each at
+    /// most `width` wide without the prefix. Widths are measured in the
+    /// unit set by [`PrefixWriter::with_width_unit`]. A wrapped line is
+    /// still written in one write.
+    #[must_use]
+    pub fn with_wrap_width(self, width: usize) -> Self {
+        Self {
+            wrap_width: Some(width),
+            ..self
+        }
+    }
+
+    /// Write the prefix on a line of its own and the content indented on
+    /// the next line when the prefix and the content together are wider
+    /// than `width`, for long dynamic prefixes. Both lines are written in
+    /// one write. Widths are measured in the unit set by
+    /// [`PrefixWriter::with_width_unit`].
+    #[must_use]
+    pub fn with_prefix_overflow_wrap(self, width: usize) -> Self {
+        Self {
+            prefix_overflow_wrap: Some(width),
+            ..self
+        }
+    }
+
+    /// End every segment of a line wrapped by
+    /// [`PrefixWriter::with_wrap_width`] but the last one with the
+    /// character, like `\` for shell style continuations. The character
+    /// counts towards the wrap width.
+    #[must_use]
+    pub fn with_wrap_continuation_char(self, continuation: char) -> Self {
+        Self {
+            wrap_continuation: Some(continuation),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    #[cfg(feature = "unicode")]
+    use super::wrap;
+    use crate::PrefixWriter;
+    #[cfg(feature = "unicode")]
+    use crate::Unit;
+
+    const PREFIX: &str = "prefix: ";
+
+    mod tests {
+        use super::{
+            assert_eq,
+            PrefixWriter,
+            Write,
+            PREFIX,
+        };
+        #[cfg(feature = "unicode")]
+        use super::{
+            wrap,
+            Unit,
+        };
+
+        #[test]
+        fn continuation_char() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new())
+                .with_wrap_width(8)
+                .with_wrap_continuation_char('\\');
+
+            writer
+                .write_all(b"cargo build --release --locked\nshort\n12345678\n123456789\n")
+                .unwrap();
+
+            assert_eq!(
+                concat!(
+                    "prefix: cargo b\\\n",
+                    "prefix: uild --\\\n",
+                    "prefix: release\\\n",
+                    "prefix:  --lock\\\n",
+                    "prefix: ed\n",
+                    "prefix: short\n",
+                    "prefix: 12345678\n",
+                    "prefix: 1234567\\\n",
+                    "prefix: 89\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[test]
+        fn without_continuation_char() {
+            let mut writer = PrefixWriter::new(PREFIX.to_owned(), Vec::new()).with_wrap_width(4);
+
+            writer.write_all(b"abcdefghij\n").unwrap();
+
+            assert_eq!(
+                "prefix: abcd\nprefix: efgh\nprefix: ij\n",
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+
+        #[cfg(feature = "unicode")]
+        #[test]
+        fn wide_characters() {
+            assert_eq!(
+                "日本\\|日本\\|語",
+                wrap("日本日本語", Unit::Columns, 5, Some('\\'), "|")
+            );
+            assert_eq!(
+                "日\\|本\\|語",
+                wrap("日本語", Unit::Columns, 1, Some('\\'), "|")
+            );
+        }
+
+        #[test]
+        fn prefix_overflow() {
+            let prefix = "[web-7f9c8d-x2k/worker-3] ";
+            let mut writer =
+                PrefixWriter::new(prefix.to_owned(), Vec::new()).with_prefix_overflow_wrap(40);
+
+            writer.write_all(b"short line\n\n").unwrap();
+            writer.write_all(b"a line that is too long\n").unwrap();
+
+            assert_eq!(
+                concat!(
+                    "[web-7f9c8d-x2k/worker-3] short line\n",
+                    "\n",
+                    "[web-7f9c8d-x2k/worker-3]\n",
+                    "  a line that is too long\n",
+                ),
+                String::from_utf8_lossy(writer.get_ref())
+            );
+        }
+    }
+}
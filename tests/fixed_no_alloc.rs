@@ -0,0 +1,61 @@
+//! Checks that a [`FixedPrefixWriter`] does not allocate after it was
+//! constructed. Lives in its own test binary as it replaces the global
+//! allocator.
+
+use prefix_writer::{
+    FixedPrefixWriter,
+    Overflow,
+};
+use std::{
+    alloc::{
+        GlobalAlloc,
+        Layout,
+        System,
+    },
+    io::Write,
+    sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    },
+};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const INPUT: &[&[u8]] = &[b"fir", b"st\n\nsec", b"ond line that is longer", b"\nthird"];
+
+fn allocations(overflow: Overflow) -> usize {
+    let sink = Vec::with_capacity(4096);
+    let mut writer =
+        FixedPrefixWriter::<_, 16>::new("prefix: ".to_owned(), sink).with_overflow(overflow);
+
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+
+    for chunk in INPUT {
+        let _ = writer.write_all(chunk);
+    }
+    let _ = writer.flush();
+
+    ALLOCATIONS.load(Ordering::SeqCst) - before
+}
+
+#[test]
+fn no_allocations() {
+    assert_eq!(0, allocations(Overflow::Emit));
+    assert_eq!(0, allocations(Overflow::Error));
+}
@@ -0,0 +1,94 @@
+//! Appends lines from several child processes to one file.
+
+#![cfg(all(unix, feature = "flock"))]
+
+use std::{
+    io::Write,
+    process::{
+        Command,
+        Stdio,
+    },
+};
+
+use prefix_writer::PrefixWriter;
+use pretty_assertions::assert_eq;
+
+/// Set for the child processes to the file they append to.
+const CHILD_OUTPUT: &str = "PREFIX_WRITER_LOCKED_FILE_OUTPUT";
+
+/// Set for the child processes to their number.
+const CHILD_NUMBER: &str = "PREFIX_WRITER_LOCKED_FILE_NUMBER";
+
+const CHILDREN: usize = 4;
+const LINES: usize = 2000;
+
+/// Longer than `PIPE_BUF`, so unlocked appends could interleave.
+const LINE_LENGTH: usize = 5000;
+
+/// The content of a line written by a child.
+fn content(child: &str, line: usize) -> String {
+    format!("{line:05} {}", child.repeat(LINE_LENGTH))
+}
+
+/// Runs in the child processes, appends all lines.
+fn child(output: &str, number: &str) {
+    let mut writer = PrefixWriter::shared_file(output, format!("child {number}: ")).unwrap();
+
+    for line in 0..LINES {
+        writeln!(writer, "{}", content(number, line)).unwrap();
+    }
+
+    writer.flush().unwrap();
+}
+
+#[test]
+fn no_spliced_lines() {
+    if let (Ok(output), Ok(number)) = (std::env::var(CHILD_OUTPUT), std::env::var(CHILD_NUMBER)) {
+        child(&output, &number);
+        return;
+    }
+
+    let output =
+        std::env::temp_dir().join(format!("prefix_writer-locked_file-{}", std::process::id()));
+
+    let children = (0..CHILDREN)
+        .map(|number| {
+            Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "no_spliced_lines", "--nocapture"])
+                .env(CHILD_OUTPUT, &output)
+                .env(CHILD_NUMBER, number.to_string())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    for mut child in children {
+        assert!(child.wait().unwrap().success());
+    }
+
+    let written = std::fs::read_to_string(&output).unwrap();
+    std::fs::remove_file(&output).unwrap();
+
+    let mut next_lines = [0; CHILDREN];
+    let mut corrupted = 0;
+    for line in written.lines() {
+        let parsed = line
+            .strip_prefix("child ")
+            .and_then(|line| line.split_once(": "))
+            .and_then(|(number, content)| Some((number.parse::<usize>().ok()?, number, content)));
+
+        match parsed {
+            Some((number, name, line_content))
+                if number < CHILDREN && line_content == content(name, next_lines[number]) =>
+            {
+                next_lines[number] += 1;
+            }
+            _ => corrupted += 1,
+        }
+    }
+
+    assert_eq!(0, corrupted);
+    assert_eq!([LINES; CHILDREN], next_lines);
+}
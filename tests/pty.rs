@@ -0,0 +1,57 @@
+//! Runs small scripts on a pseudo-terminal and checks their prefixed
+//! output.
+
+#![cfg(feature = "pty")]
+
+use prefix_writer::{
+    portable_pty::{
+        CommandBuilder,
+        PtySize,
+    },
+    run_prefixed_pty,
+    run_prefixed_pty_with_size,
+};
+use pretty_assertions::assert_eq;
+
+fn script(script: &str) -> CommandBuilder {
+    let mut cmd = CommandBuilder::new("sh");
+    cmd.args(["-c", script]);
+
+    cmd
+}
+
+#[test]
+fn colored_progress() {
+    let mut sink = Vec::new();
+
+    let status = run_prefixed_pty(
+        script(r"printf '10%%\r20%%\rdone\n\033[31mred\033[0m\n'; exit 3"),
+        "out: ".to_owned(),
+        &mut sink,
+    )
+    .unwrap();
+
+    assert_eq!(3, status.exit_code());
+    assert_eq!(
+        "out: 10%\rout: 20%\rout: done\nout: \x1b[31mred\x1b[0m\n",
+        String::from_utf8_lossy(&sink)
+    );
+}
+
+#[test]
+fn terminal_size() {
+    let size = PtySize {
+        rows: 12,
+        cols: 42,
+        ..PtySize::default()
+    };
+
+    let mut sink = Vec::new();
+
+    let status =
+        run_prefixed_pty_with_size(script("stty size"), "out: ".to_owned(), size, &mut sink)
+            .unwrap();
+
+    assert!(status.success());
+    assert_eq!("out: 12 42\n", String::from_utf8_lossy(&sink));
+}
@@ -0,0 +1,67 @@
+//! Flushes registered writers of a child process when it receives a
+//! signal.
+
+#![cfg(all(unix, feature = "signal-hook"))]
+
+use std::{
+    io::Write,
+    os::unix::process::ExitStatusExt,
+    process::{
+        Command,
+        Stdio,
+    },
+    time::Duration,
+};
+
+use prefix_writer::{
+    install_flush_on_signal,
+    PrefixWriter,
+};
+use pretty_assertions::assert_eq;
+use signal_hook::consts::SIGTERM;
+
+/// Set for the child process to the file it writes to.
+const CHILD_OUTPUT: &str = "PREFIX_WRITER_SHUTDOWN_OUTPUT";
+
+/// Runs in the child process, writes an incomplete line and terminates
+/// itself.
+fn child(output: &str) {
+    let file = std::fs::File::create(output).unwrap();
+
+    let mut writer = PrefixWriter::new("prefix: ".to_owned(), file).register_for_shutdown();
+    writer.write_all(b"complete\npartial").unwrap();
+
+    install_flush_on_signal(&[SIGTERM]).unwrap();
+    signal_hook::low_level::raise(SIGTERM).unwrap();
+
+    // The signal terminates the process before this runs out.
+    std::thread::sleep(Duration::from_secs(10));
+    std::process::exit(1);
+}
+
+#[test]
+fn flush_on_sigterm() {
+    if let Ok(output) = std::env::var(CHILD_OUTPUT) {
+        child(&output);
+        return;
+    }
+
+    let output =
+        std::env::temp_dir().join(format!("prefix_writer-shutdown-{}", std::process::id()));
+
+    let status = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "flush_on_sigterm", "--nocapture"])
+        .env(CHILD_OUTPUT, &output)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+
+    assert_eq!(Some(SIGTERM), status.signal());
+    assert_eq!(
+        "prefix: complete\nprefix: partial",
+        std::fs::read_to_string(&output).unwrap()
+    );
+
+    std::fs::remove_file(&output).unwrap();
+}